@@ -1,5 +1,5 @@
 use std::path::PathBuf;
-use crate::models::PaletteCommand;
+use crate::models::{MergeStrategy, NvimInstance, PaletteCommand, PatchAction};
 
 #[derive(Debug, Clone)]
 pub enum Action {
@@ -15,24 +15,69 @@ pub enum Action {
 
     // Input
     Character(char),
+    /// Text from a bracketed paste
+    Paste(String),
     Backspace,
     Enter,
     Escape,
 
+    // Text editing
+    CursorLeft,
+    CursorRight,
+    CursorHome,
+    CursorEnd,
+    WordLeft,
+    WordRight,
+    DeleteChar,
+    DeleteWord,
+    DeleteToStart,
+    Newline,
+
+    // Bindable commands
+    New,
+    Delete,
+    Merge,
+    Yes,
+    No,
+    Toggle,
+    ToggleSort,
+    Diff,
+    NextHunk,
+    PrevHunk,
+    Stage,
+    Unstage,
+    Commit,
+    ToggleStaged,
+    LineUp,
+    LineDown,
+    Pick,
+    PickFile,
+    Compare,
+
     // Session actions
     SwitchSession(String),
+    NewSession(String),
     CreateSession(String, Option<PathBuf>),
+    CreateLayoutSession { name: String, layout: String },
     KillSession(String),
 
     // File actions
-    OpenFile(PathBuf),
-    OpenBuffer { socket: PathBuf, bufnr: i64 },
+    /// `position` is a 1-based line and column
+    OpenFile { path: PathBuf, position: Option<(usize, usize)> },
+    OpenBuffer { nvim: NvimInstance, bufnr: i64 },
 
     // Worktree actions
     SwitchWorktree(PathBuf),
+    NewWorktree,
+    ShowExistingBranches,
     CreateWorktree(String),
+    CreateWorktreeFrom { branch: String, base: String, track: Option<bool> },
+    CheckoutWorktreeBranch(String),
     DeleteWorktree(PathBuf),
-    MergeWorktree(PathBuf),
+    ChooseMergeStrategy { path: PathBuf, branch: String },
+    MergeWorktree { path: PathBuf, strategy: MergeStrategy, message: Option<String> },
+    LaunchAgent { profile: String, branch: String },
+    OpenConflicts { path: PathBuf, files: Vec<String> },
 
     // Command palette
     ExecuteCommand(PaletteCommand),
@@ -41,6 +86,8 @@ pub enum Action {
     ShowInput { title: String, callback: InputCallback },
     ShowConfirm { title: String, message: String, callback: ConfirmCallback },
     CloseDialog,
+    /// Run the commands of the project config loaded without them
+    TrustProject,
 
     // View switching
     ShowSessionPicker,
@@ -51,17 +98,58 @@ pub enum Action {
 
     // Git
     ShowGitDiff,
+    /// Uncommitted changes of the worktree containing the path
+    ShowDiff(PathBuf),
+    /// Differences between two worktrees, for copying from `source` into `target`
+    ShowCompare { target: PathBuf, source: PathBuf },
+    /// Apply a patch made by the diff viewer to the index or the worktree at `root`
+    ApplyPatch { root: PathBuf, patch: String, action: PatchAction },
+    CommitStaged { root: PathBuf, message: String },
 }
 
 #[derive(Debug, Clone)]
 pub enum InputCallback {
     CreateSession,
     CreateWorktree,
+    LaunchAgent(String),
+    SquashMessage(PathBuf),
+    CommitMessage(PathBuf),
+}
+
+impl InputCallback {
+    /// Dialogs sharing a history share a key.
+    pub fn history_key(&self) -> &'static str {
+        match self {
+            Self::CreateSession => "session",
+            Self::CreateWorktree | Self::LaunchAgent(_) => "branch",
+            Self::SquashMessage(_) | Self::CommitMessage(_) => "commit_message",
+        }
+    }
+
+    /// Commit messages get a body; names fit on a line.
+    pub fn is_multiline(&self) -> bool {
+        matches!(self, Self::SquashMessage(_) | Self::CommitMessage(_))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SelectCallback {
+    /// The first option is a plain session; the rest are layout names
+    SessionLayout(String),
+    LaunchAgent,
+    NewWorktree,
+    WorktreeBase(String),
+    WorktreeTracking { branch: String, base: String },
+    ExistingBranch,
+    MergeStrategy(PathBuf),
+    /// The first option opens every file; the rest are the conflicting files
+    OpenConflicts(PathBuf),
 }
 
 #[derive(Debug, Clone)]
 pub enum ConfirmCallback {
     DeleteWorktree(PathBuf),
-    MergeWorktree(PathBuf),
     KillSession(String),
+    DiscardChanges { root: PathBuf, patch: String },
+    TrustProject,
 }
@@ -1,3 +1,3 @@
 mod action;
 
-pub use action::{Action, ConfirmCallback, InputCallback};
+pub use action::{Action, ConfirmCallback, InputCallback, SelectCallback};
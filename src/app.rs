@@ -1,16 +1,26 @@
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
+use crossterm::event::KeyEvent;
 use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::Frame;
 
-use crate::actions::{Action, ConfirmCallback, InputCallback};
+use crate::actions::{Action, ConfirmCallback, InputCallback, SelectCallback};
 use crate::components::{
-    BufferPicker, CommandPalette, Component, ConfirmDialog, FilePicker, HelpBar, InputDialog,
-    SessionPicker, WorktreePicker,
+    BufferPicker, CommandPalette, Component, ConfigViewer, ConfirmDialog, DiffViewer, FilePicker,
+    HelpBar, InputDialog, SelectDialog, SessionPicker, WorktreePicker,
 };
-use crate::error::Result;
-use crate::integrations::{GitClient, NvimIntegration, TmuxClient};
-use crate::models::PaletteCommand;
-use crate::tui::{key_to_action, Event, EventHandler, Tui};
+use crate::config::{Config, PROJECT_FILE};
+use crate::error::{PmanError, Result};
+use crate::integrations::{
+    nvim_command, shell_quote, worktree_session, worktree_session_name, EditorBackend,
+    GitBackend, GitClient, InputHistory, NvimIntegration, SnapshotStore, TmuxBackend, TmuxClient,
+    TrustStore, WorktreeHooks,
+};
+use crate::models::{MergeStrategy, PaletteCommand, RefKind};
+use crate::tui::{Event, EventHandler, KeyContext, Keymap, Tui};
 
 #[derive(Clone, Copy, PartialEq)]
 pub enum View {
@@ -19,22 +29,48 @@ pub enum View {
     FilePicker,
     WorktreePicker,
     BufferPicker,
+    ConfigViewer,
+    DiffViewer,
 }
 
 pub enum Dialog {
     None,
     Input(InputDialog),
     Confirm(ConfirmDialog),
+    Select(SelectDialog),
+}
+
+/// The integrations App drives; tests swap in fakes.
+pub struct Backends {
+    pub tmux: Rc<dyn TmuxBackend>,
+    /// `None` outside a git repository
+    pub git: Option<Rc<dyn GitBackend>>,
+    pub editor: Rc<dyn EditorBackend>,
+}
+
+impl Backends {
+    pub fn new(current_path: &Path, config: &Config) -> Self {
+        let tmux: Rc<dyn TmuxBackend> = Rc::new(TmuxClient::new(config));
+        let git = GitClient::new(current_path, config)
+            .ok()
+            .map(|git| Rc::new(git) as Rc<dyn GitBackend>);
+        let editor = Rc::new(NvimIntegration::new(tmux.clone()));
+        Self { tmux, git, editor }
+    }
 }
 
 pub struct App {
-    tui: Tui,
+    /// `None` when running headless under test
+    tui: Option<Tui>,
     event_handler: EventHandler,
     view: View,
     view_history: Vec<View>,
     dialog: Dialog,
     running: bool,
     current_path: PathBuf,
+    config: Config,
+    keymap: Keymap,
+    history: InputHistory,
 
     // Components
     session_picker: SessionPicker,
@@ -42,77 +78,245 @@ pub struct App {
     file_picker: Option<FilePicker>,
     worktree_picker: Option<WorktreePicker>,
     buffer_picker: Option<BufferPicker>,
+    config_viewer: Option<ConfigViewer>,
+    diff_viewer: Option<DiffViewer>,
 
     // Integrations
-    tmux: TmuxClient,
+    tmux: Rc<dyn TmuxBackend>,
+    git: Option<Rc<dyn GitBackend>>,
+    editor: Rc<dyn EditorBackend>,
+    /// Builds the integrations again when the config changes; `None` keeps the ones
+    /// handed in, e.g. fakes under test
+    rebuild_backends: Option<fn(&Path, &Config) -> Backends>,
+    next_snapshot_check: Instant,
 }
 
 impl App {
-    pub fn new(initial_view: View) -> Result<Self> {
-        let tmux = TmuxClient::new();
-        let current_path = tmux.current_path().unwrap_or_else(|_| PathBuf::from("."));
+    pub fn new(initial_view: View, config: Config) -> Result<Self> {
+        let current_path = TmuxClient::new(&config)
+            .current_path()
+            .unwrap_or_else(|_| PathBuf::from("."));
+        let backends = Backends::new(&current_path, &config);
+        let mut app =
+            Self::with_backends(initial_view, config, current_path, backends, Some(Tui::new()?))?;
+        app.rebuild_backends = Some(Backends::new);
+        Ok(app)
+    }
+
+    pub fn with_backends(
+        initial_view: View,
+        config: Config,
+        current_path: PathBuf,
+        backends: Backends,
+        tui: Option<Tui>,
+    ) -> Result<Self> {
+        let Backends { tmux, git, editor } = backends;
+        let keymap = Keymap::new(&config.keys)?;
+        let history = InputHistory::load(config.history.path.clone(), config.history.max_entries);
 
-        let mut session_picker = SessionPicker::new();
+        let mut session_picker = SessionPicker::new(tmux.clone(), &config);
         session_picker.refresh()?;
 
         // Initialize component based on initial view
         let command_palette = match &initial_view {
-            View::CommandPalette => Some(CommandPalette::new(&current_path)),
+            View::CommandPalette => Some(CommandPalette::new(git.is_some())),
             _ => None,
         };
 
         let worktree_picker = match &initial_view {
-            View::WorktreePicker => Some(WorktreePicker::new(&current_path)),
+            View::WorktreePicker => Some(WorktreePicker::new(git.clone(), tmux.clone())),
+            _ => None,
+        };
+
+        let file_picker = match &initial_view {
+            View::FilePicker => Some(FilePicker::new(&current_path)),
             _ => None,
         };
 
+        let diff_viewer = match &initial_view {
+            View::DiffViewer => Some(DiffViewer::new(git.clone(), current_path.clone())),
+            _ => None,
+        };
+
+        // Ask before running or writing anywhere a cloned repository's .pman.toml asks for
+        let dialog = match config.untrusted {
+            Some(ref untrusted) => {
+                let dir = untrusted.file.parent().and_then(Path::file_name).unwrap_or_default();
+                Dialog::Confirm(ConfirmDialog::new(
+                    "Trust Project Config",
+                    format!(
+                        "Allow {}\nfrom {}/{}?",
+                        untrusted.keys.join(", "),
+                        dir.to_string_lossy(),
+                        PROJECT_FILE
+                    ),
+                    ConfirmCallback::TrustProject,
+                ))
+            }
+            None => Dialog::None,
+        };
+
         Ok(Self {
-            tui: Tui::new()?,
+            tui,
             event_handler: EventHandler::new(100),
             view: initial_view,
             view_history: Vec::new(),
-            dialog: Dialog::None,
+            dialog,
             running: true,
             current_path,
+            config,
+            keymap,
+            history,
             session_picker,
             command_palette,
-            file_picker: None,
+            file_picker,
             worktree_picker,
             buffer_picker: None,
+            config_viewer: None,
+            diff_viewer,
             tmux,
+            git,
+            editor,
+            rebuild_backends: None,
+            next_snapshot_check: Instant::now(),
         })
     }
 
+    /// Switch to `config`, rebuilding everything that was built from the old one.
+    fn apply_config(&mut self, config: Config) -> Result<()> {
+        self.keymap = Keymap::new(&config.keys)?;
+        self.history = InputHistory::load(config.history.path.clone(), config.history.max_entries);
+        if let Some(rebuild) = self.rebuild_backends {
+            let Backends { tmux, git, editor } = rebuild(&self.current_path, &config);
+            self.tmux = tmux;
+            self.git = git;
+            self.editor = editor;
+        }
+        self.session_picker = SessionPicker::new(self.tmux.clone(), &config);
+        self.session_picker.refresh()?;
+        self.config = config;
+
+        // The other components hold the old integrations; open ones start over
+        self.command_palette = None;
+        self.worktree_picker = None;
+        self.buffer_picker = None;
+        self.diff_viewer = None;
+        self.config_viewer = None;
+        match self.view {
+            View::CommandPalette => {
+                self.command_palette = Some(CommandPalette::new(self.git.is_some()));
+            }
+            View::WorktreePicker => {
+                let picker = WorktreePicker::new(self.git.clone(), self.tmux.clone());
+                self.worktree_picker = Some(picker);
+            }
+            View::BufferPicker => {
+                self.buffer_picker = Some(BufferPicker::new(self.editor.clone()));
+            }
+            View::DiffViewer => {
+                let viewer = DiffViewer::new(self.git.clone(), self.current_path.clone());
+                self.diff_viewer = Some(viewer);
+            }
+            View::ConfigViewer => self.config_viewer = Some(ConfigViewer::new(&self.config)),
+            View::SessionPicker | View::FilePicker => {}
+        }
+        Ok(())
+    }
+
+    /// Save a snapshot in the background when the last one is older than
+    /// `snapshot.auto_interval_secs`.
+    fn auto_snapshot(&mut self) {
+        let interval = Duration::from_secs(self.config.snapshot.auto_interval_secs);
+        if interval.is_zero() || Instant::now() < self.next_snapshot_check {
+            return;
+        }
+        self.next_snapshot_check = Instant::now() + interval;
+
+        // A client of its own, since the save runs on another thread
+        let tmux = TmuxClient::new(&self.config);
+        let store = SnapshotStore::new(tmux, self.config.snapshot.path.clone());
+        if store.is_older_than(interval) {
+            // Slow with many panes, and a failure has nowhere useful to go
+            std::thread::spawn(move || {
+                let _ = store.save();
+            });
+        }
+    }
 
     pub fn run(&mut self) -> Result<()> {
-        self.tui.enter()?;
+        self.enter_tui()?;
 
         while self.running {
             self.render()?;
 
             match self.event_handler.next()? {
-                Event::Key(key) => {
-                    if let Some(action) = key_to_action(key) {
-                        self.handle_action(action)?;
-                    }
-                }
-                Event::Resize(_, _) => {
+                Event::Key(key) => self.handle_key(key)?,
+                Event::Paste(text) => self.handle_action(Action::Paste(text))?,
+                Event::Resize => {
                     // Terminal will handle resize automatically
                 }
                 Event::Tick => {
-                    // Could be used for async updates
+                    self.auto_snapshot();
+                    match self.view {
+                        View::SessionPicker => self.session_picker.tick(),
+                        View::WorktreePicker => {
+                            if let Some(ref mut picker) = self.worktree_picker {
+                                picker.tick();
+                            }
+                        }
+                        View::FilePicker => {
+                            if let Some(ref mut picker) = self.file_picker {
+                                picker.tick();
+                            }
+                        }
+                        _ => {}
+                    }
                 }
             }
         }
 
-        self.tui.exit()?;
+        self.exit_tui()?;
         Ok(())
     }
 
+    fn handle_key(&mut self, key: KeyEvent) -> Result<()> {
+        let context = self.key_context();
+        let typing = self.is_typing();
+        for action in self.keymap.handle_key(context, key, typing) {
+            self.handle_action(action)?;
+        }
+        Ok(())
+    }
+
+    fn enter_tui(&mut self) -> Result<()> {
+        match self.tui {
+            Some(ref mut tui) => tui.enter(),
+            None => Ok(()),
+        }
+    }
+
+    /// Hand the terminal back, e.g. before tmux switches the client away.
+    fn exit_tui(&mut self) -> Result<()> {
+        match self.tui {
+            Some(ref mut tui) => tui.exit(),
+            None => Ok(()),
+        }
+    }
+
     fn render(&mut self) -> Result<()> {
+        // Taken out so drawing can borrow the rest of self
+        if let Some(mut tui) = self.tui.take() {
+            let result = tui.terminal().draw(|frame| self.draw(frame)).map(|_| ());
+            self.tui = Some(tui);
+            result?;
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
         let help_text = self.current_help_text();
 
-        self.tui.terminal().draw(|frame| {
+        {
             let chunks = Layout::default()
                 .direction(Direction::Vertical)
                 .constraints([Constraint::Min(1), Constraint::Length(1)])
@@ -143,13 +347,23 @@ impl App {
                         picker.render(frame, chunks[0]);
                     }
                 }
+                View::ConfigViewer => {
+                    if let Some(ref mut viewer) = self.config_viewer {
+                        viewer.render(frame, chunks[0]);
+                    }
+                }
+                View::DiffViewer => {
+                    if let Some(ref mut viewer) = self.diff_viewer {
+                        viewer.render(frame, chunks[0]);
+                    }
+                }
             }
 
             // Render help bar
-            HelpBar::render(frame, chunks[1], help_text);
+            HelpBar::render(frame, chunks[1], &help_text);
 
             // Render dialog if active
-            match &self.dialog {
+            match &mut self.dialog {
                 Dialog::None => {}
                 Dialog::Input(dialog) => {
                     dialog.render(frame, frame.area());
@@ -157,38 +371,99 @@ impl App {
                 Dialog::Confirm(dialog) => {
                     dialog.render(frame, frame.area());
                 }
+                Dialog::Select(dialog) => {
+                    dialog.render(frame, frame.area());
+                }
             }
-        })?;
-
-        Ok(())
+        }
     }
 
-    fn current_help_text(&self) -> &'static str {
+    fn current_help_text(&self) -> String {
+        let keymap = &self.keymap;
         match &self.dialog {
-            Dialog::Input(_) => "Enter:confirm  Esc:cancel",
-            Dialog::Confirm(_) => "Y:yes  N:no  ←→:select  Esc:cancel",
+            Dialog::Input(_) => keymap.help_text(
+                KeyContext::InputDialog,
+                &[("confirm", "confirm"), ("back", "cancel")],
+            ),
+            Dialog::Confirm(_) => keymap.help_text(
+                KeyContext::ConfirmDialog,
+                &[
+                    ("yes", "yes"),
+                    ("no", "no"),
+                    ("toggle", "select"),
+                    ("back", "cancel"),
+                ],
+            ),
+            Dialog::Select(_) => keymap.help_text(
+                KeyContext::SelectDialog,
+                &[("confirm", "select"), ("back", "cancel")],
+            ),
             Dialog::None => match &self.view {
-                View::SessionPicker => self.session_picker.help_text(),
+                View::SessionPicker => self.session_picker.help_text(keymap),
                 View::CommandPalette => self
                     .command_palette
                     .as_ref()
-                    .map(|p| p.help_text())
-                    .unwrap_or(""),
+                    .map(|p| p.help_text(keymap))
+                    .unwrap_or_default(),
                 View::FilePicker => self
                     .file_picker
                     .as_ref()
-                    .map(|p| p.help_text())
-                    .unwrap_or(""),
+                    .map(|p| p.help_text(keymap))
+                    .unwrap_or_default(),
                 View::WorktreePicker => self
                     .worktree_picker
                     .as_ref()
-                    .map(|p| p.help_text())
-                    .unwrap_or(""),
+                    .map(|p| p.help_text(keymap))
+                    .unwrap_or_default(),
                 View::BufferPicker => self
                     .buffer_picker
                     .as_ref()
-                    .map(|p| p.help_text())
-                    .unwrap_or(""),
+                    .map(|p| p.help_text(keymap))
+                    .unwrap_or_default(),
+                View::ConfigViewer => self
+                    .config_viewer
+                    .as_ref()
+                    .map(|v| v.help_text(keymap))
+                    .unwrap_or_default(),
+                View::DiffViewer => self
+                    .diff_viewer
+                    .as_ref()
+                    .map(|v| v.help_text(keymap))
+                    .unwrap_or_default(),
+            },
+        }
+    }
+
+    fn key_context(&self) -> KeyContext {
+        match &self.dialog {
+            Dialog::Input(_) => KeyContext::InputDialog,
+            Dialog::Confirm(_) => KeyContext::ConfirmDialog,
+            Dialog::Select(_) => KeyContext::SelectDialog,
+            Dialog::None => match &self.view {
+                View::SessionPicker => KeyContext::SessionPicker,
+                View::CommandPalette => KeyContext::CommandPalette,
+                View::FilePicker => KeyContext::FilePicker,
+                View::WorktreePicker => KeyContext::WorktreePicker,
+                View::BufferPicker => KeyContext::BufferPicker,
+                View::ConfigViewer => KeyContext::ConfigViewer,
+                View::DiffViewer => KeyContext::DiffViewer,
+            },
+        }
+    }
+
+    fn is_typing(&self) -> bool {
+        match &self.dialog {
+            Dialog::Input(dialog) => dialog.is_typing(),
+            Dialog::Select(dialog) => dialog.is_typing(),
+            Dialog::Confirm(_) => false,
+            Dialog::None => match &self.view {
+                View::SessionPicker => self.session_picker.is_typing(),
+                View::CommandPalette => self.command_palette.as_ref().is_some_and(|p| p.is_typing()),
+                View::FilePicker => self.file_picker.as_ref().is_some_and(|p| p.is_typing()),
+                View::WorktreePicker => self.worktree_picker.as_ref().is_some_and(|p| p.is_typing()),
+                View::BufferPicker => self.buffer_picker.as_ref().is_some_and(|p| p.is_typing()),
+                View::ConfigViewer => self.config_viewer.as_ref().is_some_and(|v| v.is_typing()),
+                View::DiffViewer => self.diff_viewer.as_ref().is_some_and(|v| v.is_typing()),
             },
         }
     }
@@ -199,6 +474,10 @@ impl App {
             if let Some(result_action) = dialog.handle_action(&action)? {
                 // Close dialog for all actions except Render
                 if !matches!(result_action, Action::Render) {
+                    if !matches!(result_action, Action::CloseDialog) {
+                        // Not worth failing what was entered over
+                        let _ = self.history.add(dialog.history_key(), dialog.value());
+                    }
                     self.dialog = Dialog::None;
                 }
                 return self.handle_action(result_action);
@@ -217,6 +496,17 @@ impl App {
             return Ok(());
         }
 
+        if let Dialog::Select(ref mut dialog) = self.dialog {
+            if let Some(result_action) = dialog.handle_action(&action)? {
+                // Close dialog for all actions except Render
+                if !matches!(result_action, Action::Render) {
+                    self.dialog = Dialog::None;
+                }
+                return self.handle_action(result_action);
+            }
+            return Ok(());
+        }
+
         // Handle global actions
         match action {
             Action::Quit => {
@@ -236,7 +526,26 @@ impl App {
                 return Ok(());
             }
             Action::ShowInput { title, callback } => {
-                self.dialog = Dialog::Input(InputDialog::new(title, callback));
+                let prefix = match callback {
+                    InputCallback::CreateWorktree | InputCallback::LaunchAgent(_) => {
+                        self.config.worktree.branch_prefix.clone()
+                    }
+                    _ => String::new(),
+                };
+                let history = self.history.entries(callback.history_key());
+                let dialog = InputDialog::new(title, callback)
+                    .with_value(prefix)
+                    .with_history(history);
+                self.dialog = Dialog::Input(dialog);
+                return Ok(());
+            }
+            Action::TrustProject => {
+                if let Some(untrusted) = self.config.untrusted.take() {
+                    TrustStore::load(untrusted.trust_path)
+                        .trust(&untrusted.file, &untrusted.contents)?;
+                    self.apply_config(*untrusted.config)?;
+                }
+                self.dialog = Dialog::None;
                 return Ok(());
             }
             Action::ShowConfirm {
@@ -248,13 +557,44 @@ impl App {
                 return Ok(());
             }
             Action::SwitchSession(name) => {
-                self.tui.exit()?;
+                self.exit_tui()?;
+                self.tmux.switch_session(&name)?;
+                self.running = false;
+                return Ok(());
+            }
+            Action::NewSession(name) => {
+                if self.config.layouts.is_empty() {
+                    return self.handle_action(Action::CreateSession(name, None));
+                }
+                let options = std::iter::once("Single window".to_string())
+                    .chain(self.config.layouts.keys().cloned())
+                    .collect();
+                self.dialog = Dialog::Select(SelectDialog::new(
+                    format!("Layout for '{}'", name),
+                    options,
+                    "",
+                    SelectCallback::SessionLayout(name),
+                ));
+                return Ok(());
+            }
+            Action::CreateLayoutSession { name, layout } => {
+                let layout = self.config.layouts.get(&layout).ok_or_else(|| {
+                    PmanError::Config(format!("no layout named '{}'", layout))
+                })?;
+                let path = self.tmux.current_path()?;
+                let name = self.tmux.create_session(&name, Some(&path))?;
+                if let Err(e) = self.tmux.apply_layout(&name, &path, layout) {
+                    // Don't leave a half-built session behind
+                    let _ = self.tmux.kill_session(&name);
+                    return Err(e);
+                }
                 self.tmux.switch_session(&name)?;
+                self.dialog = Dialog::None;
                 self.running = false;
                 return Ok(());
             }
             Action::CreateSession(name, path) => {
-                self.tmux.create_session(&name, path.as_ref())?;
+                let name = self.tmux.create_session(&name, path.as_ref())?;
                 self.tmux.switch_session(&name)?;
                 self.dialog = Dialog::None;
                 self.running = false;
@@ -266,43 +606,99 @@ impl App {
                 self.session_picker.refresh()?;
                 return Ok(());
             }
-            Action::OpenFile(path) => {
-                self.tui.exit()?;
-                let nvim = NvimIntegration::new(TmuxClient::new());
-                nvim.open_file(&path)?;
+            Action::OpenFile { path, position } => {
+                self.exit_tui()?;
+                self.editor.open_file(&path, position)?;
                 self.running = false;
                 return Ok(());
             }
-            Action::OpenBuffer { socket, bufnr } => {
-                self.tui.exit()?;
-                let nvim = NvimIntegration::new(TmuxClient::new());
-                nvim.open_buffer(&socket, bufnr)?;
+            Action::OpenBuffer { nvim, bufnr } => {
+                self.exit_tui()?;
+                self.editor.open_buffer(&nvim, bufnr)?;
                 self.running = false;
                 return Ok(());
             }
             Action::SwitchWorktree(path) => {
                 // Create or switch to session for this worktree
-                let session_name = path
-                    .file_name()
-                    .map(|n| n.to_string_lossy().to_string())
-                    .unwrap_or_else(|| "worktree".to_string());
-
-                // Check if session exists
-                let sessions = self.tmux.list_sessions()?;
-                let session_exists = sessions.iter().any(|s| s.name == session_name);
+                let session_name = self.worktree_session(&path)?;
 
-                if !session_exists {
-                    self.tmux.create_session(&session_name, Some(&path))?;
-                }
-
-                self.tui.exit()?;
+                self.exit_tui()?;
                 self.tmux.switch_session(&session_name)?;
                 self.running = false;
                 return Ok(());
             }
+            Action::NewWorktree => {
+                self.dialog = Dialog::Select(SelectDialog::new(
+                    "New Worktree",
+                    vec!["New branch".to_string(), "Existing branch".to_string()],
+                    "",
+                    SelectCallback::NewWorktree,
+                ));
+                return Ok(());
+            }
+            Action::ShowExistingBranches => {
+                if let Some(git) = self.git.clone() {
+                    let checked_out: Vec<String> =
+                        git.list_worktrees()?.into_iter().map(|wt| wt.branch).collect();
+                    let branches = git
+                        .list_refs()?
+                        .into_iter()
+                        .filter(|r| r.kind != RefKind::Tag && !checked_out.contains(&r.name))
+                        .map(|r| r.name)
+                        .collect();
+
+                    self.dialog = Dialog::Select(
+                        SelectDialog::new(
+                            "Check Out Branch",
+                            branches,
+                            "Every branch already has a worktree",
+                            SelectCallback::ExistingBranch,
+                        )
+                        .with_search(),
+                    );
+                    return Ok(());
+                }
+                self.dialog = Dialog::None;
+                return Ok(());
+            }
             Action::CreateWorktree(branch_name) => {
-                if let Some(ref git) = GitClient::new(&self.current_path).ok() {
+                if let Some(git) = self.git.clone() {
+                    let refs = git.list_refs()?;
+                    let remotes: Vec<String> = refs
+                        .iter()
+                        .filter(|r| r.remote_parts().is_some_and(|(_, name)| name == branch_name))
+                        .map(|r| r.name.clone())
+                        .collect();
+
+                    // New branch names need a starting point; existing ones are checked out,
+                    // as are ones on a single remote. On several, the user picks which.
+                    if remotes.len() != 1 && !git.branch_exists(&branch_name) {
+                        let title = if remotes.is_empty() {
+                            format!("Base for '{}'", branch_name)
+                        } else {
+                            format!("Base for '{}' (on {} remotes)", branch_name, remotes.len())
+                        };
+                        let mut bases = remotes.clone();
+                        bases.push("HEAD".to_string());
+                        bases.extend(
+                            refs.into_iter()
+                                .map(|r| r.name)
+                                .filter(|name| !remotes.contains(name)),
+                        );
+                        self.dialog = Dialog::Select(
+                            SelectDialog::new(
+                                title,
+                                bases,
+                                "",
+                                SelectCallback::WorktreeBase(branch_name),
+                            )
+                            .with_search(),
+                        );
+                        return Ok(());
+                    }
+
                     let worktree_path = git.create_worktree(&branch_name)?;
+                    self.run_worktree_hooks(git.as_ref(), &worktree_path)?;
                     self.dialog = Dialog::None;
 
                     // Switch to the new worktree session
@@ -311,8 +707,113 @@ impl App {
                 self.dialog = Dialog::None;
                 return Ok(());
             }
+            Action::CreateWorktreeFrom {
+                branch,
+                base,
+                track,
+            } => {
+                if let Some(git) = self.git.clone() {
+                    let track = match track {
+                        Some(track) => track,
+                        None => {
+                            let is_remote = git
+                                .list_refs()?
+                                .iter()
+                                .any(|r| r.kind == RefKind::RemoteBranch && r.name == base);
+                            if is_remote {
+                                self.dialog = Dialog::Select(SelectDialog::new(
+                                    format!("Upstream for '{}'", branch),
+                                    vec![format!("Track {}", base), "Don't track".to_string()],
+                                    "",
+                                    SelectCallback::WorktreeTracking { branch, base },
+                                ));
+                                return Ok(());
+                            }
+                            false
+                        }
+                    };
+
+                    let worktree_path = git.add_worktree(&branch, Some(&base), track)?;
+                    self.run_worktree_hooks(git.as_ref(), &worktree_path)?;
+                    self.dialog = Dialog::None;
+                    return self.handle_action(Action::SwitchWorktree(worktree_path));
+                }
+                self.dialog = Dialog::None;
+                return Ok(());
+            }
+            Action::CheckoutWorktreeBranch(name) => {
+                if let Some(git) = self.git.clone() {
+                    if !git.branch_exists(&name) {
+                        // A remote branch: create a local branch of the same name from it
+                        if let Some((_, branch)) = name.split_once('/') {
+                            return self.handle_action(Action::CreateWorktreeFrom {
+                                branch: branch.to_string(),
+                                base: name.clone(),
+                                track: None,
+                            });
+                        }
+                    }
+                    let worktree_path = git.create_worktree(&name)?;
+                    self.run_worktree_hooks(git.as_ref(), &worktree_path)?;
+                    self.dialog = Dialog::None;
+                    return self.handle_action(Action::SwitchWorktree(worktree_path));
+                }
+                self.dialog = Dialog::None;
+                return Ok(());
+            }
+            Action::LaunchAgent { profile, branch } => {
+                let agent = self.config.agents.get(&profile).cloned().ok_or_else(|| {
+                    PmanError::Config(format!("Unknown agent profile '{}'", profile))
+                })?;
+                let command = agent.command_line()?;
+                let git = self.git.clone().ok_or(PmanError::NotGitRepo)?;
+
+                let worktree_path = git.create_worktree(&branch)?;
+                let session_name = self.tmux.create_session(
+                    &worktree_session_name(&git.main_worktree()?, &branch),
+                    Some(&worktree_path),
+                )?;
+                let started = self.start_agent(
+                    git.as_ref(),
+                    &session_name,
+                    &worktree_path,
+                    &profile,
+                    command,
+                    &agent.env,
+                );
+                if let Err(e) = started {
+                    // Don't leave a session behind without its agent
+                    let _ = self.tmux.kill_session(&session_name);
+                    return Err(e);
+                }
+
+                self.exit_tui()?;
+                self.tmux.switch_session(&session_name)?;
+                self.running = false;
+                self.dialog = Dialog::None;
+                return Ok(());
+            }
+            Action::OpenConflicts { path, files } => {
+                // Resolve in the worktree's own session, next to the agent that made the changes
+                let session_name = self.worktree_session(&path)?;
+
+                let args: Vec<String> = files.iter().map(|f| shell_quote(f)).collect();
+                let command = nvim_command(&format!("-- {}", args.join(" ")));
+                self.tmux.new_window(
+                    &session_name,
+                    "conflicts",
+                    &path,
+                    &command,
+                    &BTreeMap::new(),
+                )?;
+
+                self.exit_tui()?;
+                self.tmux.switch_session(&session_name)?;
+                self.running = false;
+                return Ok(());
+            }
             Action::DeleteWorktree(path) => {
-                if let Some(ref git) = GitClient::new(&self.current_path).ok() {
+                if let Some(git) = self.git.clone() {
                     git.delete_worktree(&path)?;
                     if let Some(ref mut picker) = self.worktree_picker {
                         picker.refresh()?;
@@ -321,12 +822,70 @@ impl App {
                 self.dialog = Dialog::None;
                 return Ok(());
             }
-            Action::MergeWorktree(path) => {
-                if let Some(ref git) = GitClient::new(&self.current_path).ok() {
+            Action::ChooseMergeStrategy { path, branch } => {
+                let default = self.config.merge.strategy;
+                let options = MergeStrategy::ALL
+                    .iter()
+                    .map(|strategy| {
+                        if *strategy == default {
+                            format!("{} (default)", strategy.label())
+                        } else {
+                            strategy.label().to_string()
+                        }
+                    })
+                    .collect();
+                let selected = MergeStrategy::ALL
+                    .iter()
+                    .position(|strategy| *strategy == default)
+                    .unwrap_or(0);
+
+                self.dialog = Dialog::Select(
+                    SelectDialog::new(
+                        format!("Merge '{}' to Main and Delete Worktree", branch),
+                        options,
+                        "",
+                        SelectCallback::MergeStrategy(path),
+                    )
+                    .with_selected(selected),
+                );
+                return Ok(());
+            }
+            Action::MergeWorktree {
+                path,
+                strategy,
+                message,
+            } => {
+                if let Some(git) = self.git.clone() {
                     // Get branch name from worktree
                     let worktrees = git.list_worktrees()?;
                     if let Some(wt) = worktrees.iter().find(|w| w.path == path) {
-                        git.merge_to_main(&path, &wt.branch)?;
+                        if strategy == MergeStrategy::Squash && message.is_none() {
+                            let subjects = git.branch_subjects(&wt.branch)?;
+                            self.dialog = Dialog::Input(
+                                InputDialog::new(
+                                    format!("Squash Commit Message for '{}'", wt.branch),
+                                    InputCallback::SquashMessage(path.clone()),
+                                )
+                                .with_value(MergeStrategy::squash_message(
+                                    &wt.branch, &subjects,
+                                )),
+                            );
+                            return Ok(());
+                        }
+                        match git.merge_to_main(&path, &wt.branch, strategy, message.as_deref()) {
+                            Err(PmanError::MergeConflict { files }) => {
+                                let mut options = vec!["Open all in nvim".to_string()];
+                                options.extend(files);
+                                self.dialog = Dialog::Select(SelectDialog::new(
+                                    format!("Conflicts Merging '{}'", wt.branch),
+                                    options,
+                                    "",
+                                    SelectCallback::OpenConflicts(path.clone()),
+                                ));
+                                return Ok(());
+                            }
+                            result => result?,
+                        }
                     }
                     if let Some(ref mut picker) = self.worktree_picker {
                         picker.refresh()?;
@@ -352,7 +911,7 @@ impl App {
                 }
                 self.view = View::CommandPalette;
                 if self.command_palette.is_none() {
-                    self.command_palette = Some(CommandPalette::new(&self.current_path));
+                    self.command_palette = Some(CommandPalette::new(self.git.is_some()));
                 }
                 return Ok(());
             }
@@ -372,7 +931,8 @@ impl App {
                 }
                 self.view = View::WorktreePicker;
                 if self.worktree_picker.is_none() {
-                    self.worktree_picker = Some(WorktreePicker::new(&self.current_path));
+                    let picker = WorktreePicker::new(self.git.clone(), self.tmux.clone());
+                    self.worktree_picker = Some(picker);
                 }
                 return Ok(());
             }
@@ -381,14 +941,55 @@ impl App {
                     self.view_history.push(self.view);
                 }
                 self.view = View::BufferPicker;
-                self.buffer_picker = Some(BufferPicker::new());
+                self.buffer_picker = Some(BufferPicker::new(self.editor.clone()));
                 return Ok(());
             }
             Action::ShowGitDiff => {
-                self.tui.exit()?;
-                self.tmux
-                    .popup_command("git diff HEAD | delta", "90%", "90%")?;
-                self.tui.enter()?;
+                let Some(git_diff) = self.config.commands.git_diff.clone() else {
+                    return self.handle_action(Action::ShowDiff(self.current_path.clone()));
+                };
+                self.exit_tui()?;
+                let size = &self.config.popup.git_diff;
+                self.tmux.popup_command(&git_diff, &size.width, &size.height)?;
+                self.enter_tui()?;
+                return Ok(());
+            }
+            Action::ShowDiff(path) => {
+                if self.view != View::DiffViewer {
+                    self.view_history.push(self.view);
+                }
+                self.view = View::DiffViewer;
+                self.diff_viewer = Some(DiffViewer::new(self.git.clone(), path));
+                return Ok(());
+            }
+            Action::ShowCompare { target, source } => {
+                if self.view != View::DiffViewer {
+                    self.view_history.push(self.view);
+                }
+                self.view = View::DiffViewer;
+                self.diff_viewer = Some(DiffViewer::compare(self.git.clone(), target, source));
+                return Ok(());
+            }
+            Action::ApplyPatch {
+                root,
+                patch,
+                action,
+            } => {
+                self.dialog = Dialog::None;
+                let result = match self.git {
+                    Some(ref git) => git.apply_patch(&root, &patch, action),
+                    None => Err(PmanError::NotGitRepo),
+                };
+                self.refresh_diff(result);
+                return Ok(());
+            }
+            Action::CommitStaged { root, message } => {
+                self.dialog = Dialog::None;
+                let result = match self.git {
+                    Some(ref git) => git.commit(&root, &message),
+                    None => Err(PmanError::NotGitRepo),
+                };
+                self.refresh_diff(result);
                 return Ok(());
             }
             Action::Render => {
@@ -421,6 +1022,16 @@ impl App {
                 .as_mut()
                 .and_then(|p| p.handle_action(&action).ok())
                 .flatten(),
+            View::ConfigViewer => self
+                .config_viewer
+                .as_mut()
+                .and_then(|v| v.handle_action(&action).ok())
+                .flatten(),
+            View::DiffViewer => self
+                .diff_viewer
+                .as_mut()
+                .and_then(|v| v.handle_action(&action).ok())
+                .flatten(),
         };
 
         if let Some(result_action) = result_action {
@@ -430,6 +1041,54 @@ impl App {
         Ok(())
     }
 
+    /// Show the diff as it is after a git operation, with the reason if it failed. A failed
+    /// `git apply` or commit hook leaves the diff as it was, so it isn't worth leaving for.
+    fn refresh_diff(&mut self, result: Result<()>) {
+        if let Some(ref mut viewer) = self.diff_viewer {
+            viewer.refresh();
+            if let Err(e) = result {
+                viewer.set_status(e.to_string());
+            }
+        }
+    }
+
+    /// The session for the worktree at `path`, created if needed.
+    fn worktree_session(&self, path: &Path) -> Result<String> {
+        let git = self.git.as_ref().ok_or(PmanError::NotGitRepo)?;
+        worktree_session(self.tmux.as_ref(), git.as_ref(), path)
+    }
+
+    /// Set up the new worktree at `worktree_path` and start the agent `command` in a
+    /// window of `session_name`, its session.
+    fn start_agent(
+        &self,
+        git: &dyn GitBackend,
+        session_name: &str,
+        worktree_path: &Path,
+        profile: &str,
+        mut command: String,
+        env: &BTreeMap<String, String>,
+    ) -> Result<()> {
+        // The agent starts once setup, e.g. installing dependencies, is done
+        if let Some(wait) = self.run_worktree_hooks(git, worktree_path)? {
+            command = format!("{}; {}", wait, command);
+        }
+        self.tmux.set_session_option(session_name, "@pman_agent", profile)?;
+        self.tmux
+            .new_window(session_name, profile, worktree_path, &command, env)?;
+        Ok(())
+    }
+
+    /// Run the configured hooks for a new worktree in its session,
+    /// creating the session if needed. See `WorktreeHooks::run_for` for what it returns.
+    fn run_worktree_hooks(
+        &self,
+        git: &dyn GitBackend,
+        worktree_path: &Path,
+    ) -> Result<Option<String>> {
+        WorktreeHooks::new(self.tmux.clone(), self.config.hooks.clone()).run_for(git, worktree_path)
+    }
+
     fn execute_command(&mut self, cmd: PaletteCommand) -> Result<()> {
         match cmd {
             // Sessions
@@ -461,20 +1120,38 @@ impl App {
                 }
                 self.view = View::WorktreePicker;
                 if self.worktree_picker.is_none() {
-                    self.worktree_picker = Some(WorktreePicker::new(&self.current_path));
+                    let picker = WorktreePicker::new(self.git.clone(), self.tmux.clone());
+                    self.worktree_picker = Some(picker);
                 }
             }
             PaletteCommand::CreateWorktree => {
-                self.dialog = Dialog::Input(InputDialog::new(
-                    "New Worktree Branch",
-                    InputCallback::CreateWorktree,
+                return self.handle_action(Action::NewWorktree);
+            }
+            PaletteCommand::LaunchAgent => {
+                // Agents start in a new worktree, so there is nothing to offer outside a repo
+                let (profiles, empty_message) = match self.git {
+                    Some(_) => (
+                        self.config.agents.keys().cloned().collect(),
+                        "No [agents] profiles in config.toml".to_string(),
+                    ),
+                    None => (Vec::new(), PmanError::NotGitRepo.to_string()),
+                };
+                self.dialog = Dialog::Select(SelectDialog::new(
+                    "Launch Agent",
+                    profiles,
+                    empty_message,
+                    SelectCallback::LaunchAgent,
                 ));
             }
             // Files
             PaletteCommand::FindFiles => {
-                self.tui.exit()?;
-                let cmd = r#"sh -c 'file=$(fd --type f --hidden --exclude .git | fzf --preview "bat --color=always --style=numbers --line-range=:500 {}") && [ -n "$file" ] && nvim "$file"'"#;
-                self.tmux.popup_command(cmd, "90%", "90%")?;
+                let Some(find_files) = self.config.commands.find_files.clone() else {
+                    return self.handle_action(Action::ShowFilePicker);
+                };
+                self.exit_tui()?;
+                let cmd = format!("sh -c {}", shell_quote(&find_files));
+                let size = &self.config.popup.find_files;
+                self.tmux.popup_command(&cmd, &size.width, &size.height)?;
                 self.running = false;
                 return Ok(());
             }
@@ -483,13 +1160,30 @@ impl App {
                     self.view_history.push(self.view);
                 }
                 self.view = View::BufferPicker;
-                self.buffer_picker = Some(BufferPicker::new());
+                self.buffer_picker = Some(BufferPicker::new(self.editor.clone()));
             }
             // Git
             PaletteCommand::GitDiff => {
                 return self.handle_action(Action::ShowGitDiff);
             }
+            PaletteCommand::CompareWorktrees => {
+                self.handle_action(Action::ShowWorktreePicker)?;
+                if let Some(ref mut picker) = self.worktree_picker {
+                    picker.start_compare();
+                }
+            }
+            // Config
+            PaletteCommand::ShowConfig => {
+                if self.view != View::ConfigViewer {
+                    self.view_history.push(self.view);
+                }
+                self.view = View::ConfigViewer;
+                self.config_viewer = Some(ConfigViewer::new(&self.config));
+            }
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests;
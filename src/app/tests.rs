@@ -0,0 +1,606 @@
+//! Drives App with key presses against fake backends and checks what it asked of them
+//! and what it drew.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use tempfile::TempDir;
+use ratatui::{backend::TestBackend, Terminal};
+
+use super::{App, Backends, View};
+use crate::actions::Action;
+use crate::components::Component;
+use crate::config::{AgentProfile, Config, UntrustedProject};
+use crate::error::PmanError;
+use crate::integrations::fake::{FakeEditor, FakeGit, FakeTmux};
+use crate::integrations::{TmuxBackend, TrustStore};
+use crate::models::{GitRef, NvimBuffer, NvimInstance, PaletteCommand, RefKind, TmuxPane};
+
+struct Harness {
+    app: App,
+    tmux: Rc<FakeTmux>,
+    git: Rc<FakeGit>,
+    editor: Rc<FakeEditor>,
+    terminal: Terminal<TestBackend>,
+    /// Holds the input history file
+    state: TempDir,
+}
+
+impl Harness {
+    fn new(view: View, tmux: FakeTmux, git: FakeGit, editor: FakeEditor) -> Self {
+        Self::with_config(view, Config::default(), tmux, git, editor)
+    }
+
+    fn with_config(
+        view: View,
+        mut config: Config,
+        tmux: FakeTmux,
+        git: FakeGit,
+        editor: FakeEditor,
+    ) -> Self {
+        let tmux = Rc::new(tmux);
+        let git = Rc::new(git);
+        let editor = Rc::new(editor);
+        let backends = Backends {
+            tmux: tmux.clone(),
+            git: Some(git.clone()),
+            editor: editor.clone(),
+        };
+        let state = tempfile::tempdir().unwrap();
+        config.history.path = state.path().join("history.json");
+        let app = App::with_backends(
+            view,
+            config,
+            PathBuf::from("/work/repo"),
+            backends,
+            None,
+        )
+        .unwrap();
+
+        Self {
+            app,
+            tmux,
+            git,
+            editor,
+            terminal: Terminal::new(TestBackend::new(100, 30)).unwrap(),
+            state,
+        }
+    }
+
+    fn sessions(names: &[&str]) -> Self {
+        let tmux = FakeTmux::with_sessions(names);
+        Self::new(View::SessionPicker, tmux, FakeGit::new(), FakeEditor::default())
+    }
+
+    fn press(&mut self, code: KeyCode) {
+        self.app.handle_key(KeyEvent::from(code)).unwrap();
+    }
+
+    fn press_with(&mut self, modifiers: KeyModifiers, code: KeyCode) {
+        self.app.handle_key(KeyEvent::new(code, modifiers)).unwrap();
+    }
+
+    fn type_text(&mut self, text: &str) {
+        for c in text.chars() {
+            self.press(KeyCode::Char(c));
+        }
+    }
+
+    /// The screen App would draw now, one string per row.
+    fn screen(&mut self) -> String {
+        let app = &mut self.app;
+        self.terminal.draw(|frame| app.draw(frame)).unwrap();
+
+        let buffer = self.terminal.backend().buffer();
+        let width = buffer.area.width as usize;
+        buffer
+            .content
+            .chunks(width)
+            .map(|row| row.iter().map(|cell| cell.symbol()).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[test]
+fn lists_sessions() {
+    let mut h = Harness::sessions(&["api", "web"]);
+
+    let screen = h.screen();
+    assert!(screen.contains("api"), "{}", screen);
+    assert!(screen.contains("web"), "{}", screen);
+}
+
+#[test]
+fn enter_switches_to_the_selected_session() {
+    let mut h = Harness::sessions(&["api", "web"]);
+
+    h.type_text("web");
+    h.press(KeyCode::Enter);
+
+    assert_eq!(h.tmux.calls(), ["switch_session web"]);
+    assert!(!h.app.running);
+}
+
+#[test]
+fn new_session_is_created_and_switched_to() {
+    let mut h = Harness::sessions(&["api"]);
+
+    h.press(KeyCode::Char('n'));
+    assert!(h.screen().contains("New Session"));
+    h.type_text("docs");
+    h.press(KeyCode::Enter);
+
+    assert_eq!(h.tmux.calls(), ["create_session docs", "switch_session docs"]);
+}
+
+#[test]
+fn input_dialog_edits_at_the_cursor_and_recalls_earlier_entries() {
+    let mut h = Harness::sessions(&["api"]);
+
+    h.press(KeyCode::Char('n'));
+    h.type_text("web srv");
+    h.press_with(KeyModifiers::CONTROL, KeyCode::Char('w'));
+    h.press(KeyCode::Backspace);
+    h.press(KeyCode::Home);
+    h.app.handle_action(Action::Paste("my-\n".to_string())).unwrap();
+    h.press(KeyCode::Enter);
+
+    // Saved, so a later run recalls it too
+    let saved = fs::read_to_string(h.state.path().join("history.json")).unwrap();
+    assert!(saved.contains("my-web"), "{}", saved);
+
+    h.press(KeyCode::Char('n'));
+    h.type_text("draft");
+    h.press(KeyCode::Up);
+    assert!(h.screen().contains("> my-web"), "{}", h.screen());
+    h.press(KeyCode::Down);
+    assert!(h.screen().contains("> draft"), "{}", h.screen());
+    h.press(KeyCode::Up);
+    h.press(KeyCode::Enter);
+
+    assert_eq!(
+        h.tmux.calls(),
+        [
+            "create_session my-web",
+            "switch_session my-web",
+            "create_session my-web",
+            "switch_session my-web",
+        ]
+    );
+}
+
+#[test]
+fn killing_a_session_asks_first() {
+    let mut h = Harness::sessions(&["api", "web"]);
+
+    h.press(KeyCode::Char('d'));
+    assert!(h.tmux.calls().is_empty());
+    h.press(KeyCode::Char('y'));
+
+    assert_eq!(h.tmux.calls().len(), 1);
+    assert!(h.tmux.calls()[0].starts_with("kill_session "));
+    assert_eq!(h.tmux.sessions.borrow().len(), 1);
+    assert!(h.app.running);
+}
+
+#[test]
+fn escape_quits_from_the_first_view() {
+    let mut h = Harness::sessions(&["api"]);
+
+    h.press(KeyCode::Esc);
+
+    assert!(!h.app.running);
+    assert!(h.tmux.calls().is_empty());
+}
+
+#[test]
+fn new_worktree_branches_from_the_chosen_base() {
+    let git = FakeGit::new();
+    let mut h = Harness::new(View::WorktreePicker, FakeTmux::default(), git, FakeEditor::default());
+
+    h.press(KeyCode::Char('n'));
+    // "New branch"
+    h.press(KeyCode::Enter);
+    h.type_text("feature");
+    h.press(KeyCode::Enter);
+    assert!(h.screen().contains("Base for 'feature'"));
+    // HEAD
+    h.press(KeyCode::Enter);
+
+    assert_eq!(h.git.calls(), ["add_worktree feature HEAD false"]);
+    assert_eq!(
+        h.tmux.calls(),
+        ["create_session repo/feature /work/feature", "switch_session repo/feature"]
+    );
+}
+
+#[test]
+fn a_branch_on_several_remotes_asks_which_to_start_from() {
+    let mut git = FakeGit::new();
+    for name in ["origin/feature", "upstream/feature"] {
+        git.refs.push(GitRef {
+            name: name.to_string(),
+            kind: RefKind::RemoteBranch,
+        });
+    }
+    let mut h = Harness::new(View::WorktreePicker, FakeTmux::default(), git, FakeEditor::default());
+
+    h.app.handle_action(Action::CreateWorktree("feature".to_string())).unwrap();
+    assert!(h.screen().contains("Base for 'feature' (on 2 remotes)"), "{}", h.screen());
+    h.press(KeyCode::Down);
+    h.press(KeyCode::Enter);
+    // "Track upstream/feature"
+    h.press(KeyCode::Enter);
+
+    assert_eq!(h.git.calls(), ["add_worktree feature upstream/feature true"]);
+}
+
+#[test]
+fn worktree_sessions_are_found_by_path_and_named_after_repo_and_branch() {
+    let git = FakeGit::new().with_worktree("fix/v1.2").with_worktree("docs");
+    let tmux = FakeTmux::default();
+    // Whatever it is called, the session started in the worktree is its session
+    tmux.create_session("notes", Some(&PathBuf::from("/work/docs"))).unwrap();
+    let mut h = Harness::new(View::WorktreePicker, tmux, git, FakeEditor::default());
+
+    h.app.handle_action(Action::SwitchWorktree(PathBuf::from("/work/docs"))).unwrap();
+    h.app.handle_action(Action::SwitchWorktree(PathBuf::from("/work/fix/v1.2"))).unwrap();
+
+    assert_eq!(
+        h.tmux.calls(),
+        [
+            "create_session notes /work/docs",
+            "switch_session notes",
+            "create_session repo/fix-v1_2 /work/fix/v1.2",
+            "switch_session repo/fix-v1_2",
+        ]
+    );
+}
+
+#[test]
+fn merge_conflicts_offer_to_open_the_files() {
+    let mut git = FakeGit::new().with_worktree("feature");
+    git.conflicts = vec!["src/lib.rs".to_string()];
+    let mut h = Harness::new(View::WorktreePicker, FakeTmux::default(), git, FakeEditor::default());
+
+    h.press(KeyCode::Down);
+    h.press(KeyCode::Char('m'));
+    // The configured default strategy is preselected
+    h.press(KeyCode::Enter);
+
+    assert_eq!(h.git.calls(), ["merge_to_main feature Merge"]);
+    let screen = h.screen();
+    assert!(screen.contains("Conflicts Merging 'feature'"), "{}", screen);
+    assert!(screen.contains("src/lib.rs"), "{}", screen);
+}
+
+#[test]
+fn buffers_open_in_their_nvim() {
+    let buffer = NvimBuffer {
+        bufnr: 3,
+        name: PathBuf::from("/work/repo/src/main.rs"),
+        modified: false,
+    };
+    let nvim = NvimInstance {
+        socket: PathBuf::from("/tmp/nvim.sock"),
+        session: "api".to_string(),
+        window: "editor".to_string(),
+        pane_id: "%4".to_string(),
+    };
+    let editor = FakeEditor {
+        buffers: vec![(nvim, buffer)],
+        ..FakeEditor::default()
+    };
+    let tmux = FakeTmux::with_sessions(&["api"]);
+    let mut h = Harness::new(View::SessionPicker, tmux, FakeGit::new(), editor);
+
+    // No default key; bound through `[keys]` or reached from the palette
+    h.app.handle_action(Action::ShowBufferPicker).unwrap();
+    let screen = h.screen();
+    assert!(screen.contains("main.rs  api:editor"), "{}", screen);
+    h.press(KeyCode::Enter);
+
+    assert_eq!(h.editor.calls(), ["open_buffer %4 3"]);
+}
+
+#[test]
+fn file_finder_skips_ignored_files_and_opens_the_choice() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir_all(dir.path().join(".git")).unwrap();
+    fs::create_dir_all(dir.path().join("src")).unwrap();
+    fs::create_dir_all(dir.path().join("target")).unwrap();
+    fs::write(dir.path().join(".gitignore"), "target/\n").unwrap();
+    fs::write(dir.path().join("src/main.rs"), "fn main() {}\n").unwrap();
+    fs::write(dir.path().join("target/build.log"), "").unwrap();
+
+    let mut h = Harness::sessions(&["api"]);
+    h.app.current_path = dir.path().to_path_buf();
+    h.app.handle_action(Action::ShowFilePicker).unwrap();
+
+    // The walk runs on another thread
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !h.screen().contains("Files (2)") {
+        assert!(Instant::now() < deadline, "{}", h.screen());
+        h.app.file_picker.as_mut().unwrap().tick();
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    let screen = h.screen();
+    assert!(screen.contains(".gitignore"), "{}", screen);
+    assert!(!screen.contains("build.log"), "{}", screen);
+
+    h.type_text("main");
+    let screen = h.screen();
+    assert!(screen.contains("fn main() {}"), "{}", screen);
+    h.press(KeyCode::Enter);
+
+    let opened = format!("open_file {}", dir.path().join("src/main.rs").display());
+    assert_eq!(h.editor.calls(), [opened]);
+}
+
+#[test]
+fn diff_viewer_jumps_between_hunks_and_opens_them() {
+    let mut git = FakeGit::new();
+    git.diff = "\
+diff --git a/src/lib.rs b/src/lib.rs
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,2 +1,2 @@
+-fn old() {}
++fn new() {}
+ fn kept() {}
+@@ -20,3 +20,4 @@ impl App {
+ a
+ b
++c
+ d
+"
+    .to_string();
+    let mut h = Harness::new(View::WorktreePicker, FakeTmux::default(), git, FakeEditor::default());
+
+    h.press(KeyCode::Char('v'));
+    let screen = h.screen();
+    assert!(screen.contains("M src/lib.rs  +2 -1"), "{}", screen);
+    assert!(screen.contains("@@ -20,3 +20,4 @@ impl App {"), "{}", screen);
+
+    h.press(KeyCode::Char(']'));
+    h.press(KeyCode::Enter);
+
+    assert_eq!(h.editor.calls(), ["open_file /work/repo/src/lib.rs:22:1"]);
+}
+
+#[test]
+fn diff_viewer_stages_hunks_and_lines_and_commits() {
+    let mut git = FakeGit::new();
+    git.diff = "\
+diff --git a/src/lib.rs b/src/lib.rs
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,3 +1,2 @@
+-a
+-b
++c
+ d
+"
+    .to_string();
+    let mut h = Harness::new(View::WorktreePicker, FakeTmux::default(), git, FakeEditor::default());
+
+    h.press(KeyCode::Char('v'));
+    h.press(KeyCode::Char('s'));
+
+    // Mark "-a" and "+c"
+    h.press(KeyCode::Char(' '));
+    h.press(KeyCode::Char('j'));
+    h.press(KeyCode::Char('j'));
+    h.press(KeyCode::Char(' '));
+    h.press(KeyCode::Char('s'));
+
+    h.press(KeyCode::Char('d'));
+    assert!(h.screen().contains("Discard this hunk in src/lib.rs?"), "{}", h.screen());
+    h.press(KeyCode::Char('y'));
+
+    h.press(KeyCode::Char('c'));
+    h.type_text("Fix lib");
+    h.press(KeyCode::Enter);
+
+    assert_eq!(
+        h.git.calls(),
+        [
+            "apply_patch /work/repo Stage @@ -1,3 +1,2 @@\n-a\n-b\n+c\n d\n",
+            "apply_patch /work/repo Stage @@ -1,3 +1,3 @@\n-a\n b\n+c\n d\n",
+            "apply_patch /work/repo Discard @@ -1,3 +1,2 @@\n-a\n-b\n+c\n d\n",
+            "commit /work/repo Fix lib",
+        ]
+    );
+}
+
+#[test]
+fn compared_worktrees_copy_hunks_and_files_into_the_first_pick() {
+    let mut git = FakeGit::new().with_worktree("agent-a").with_worktree("agent-b");
+    git.compare_diff = "\
+diff --git a/src/lib.rs b/src/lib.rs
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,2 +1,2 @@
+-fn slow() {}
++fn fast() {}
+ fn kept() {}
+@@ -9,1 +9,2 @@
+ }
++// done
+"
+    .to_string();
+    let mut h = Harness::new(View::CommandPalette, FakeTmux::default(), git, FakeEditor::default());
+
+    h.type_text("compare work");
+    h.press(KeyCode::Enter);
+    h.press(KeyCode::Down);
+    h.press(KeyCode::Enter);
+    assert!(h.screen().contains("Compare 'agent-a' with…"), "{}", h.screen());
+    h.press(KeyCode::Down);
+    h.press(KeyCode::Enter);
+
+    let screen = h.screen();
+    assert!(screen.contains("agent-a ← agent-b (1)"), "{}", screen);
+    h.press(KeyCode::Char('p'));
+    h.press(KeyCode::Char('P'));
+
+    // Swapped, the copies go the other way
+    h.press(KeyCode::Tab);
+    assert!(h.screen().contains("agent-b ← agent-a (1)"), "{}", h.screen());
+    h.press(KeyCode::Char(']'));
+    h.press(KeyCode::Char('p'));
+
+    let first = "@@ -1,2 +1,2 @@\n-fn slow() {}\n+fn fast() {}\n fn kept() {}\n";
+    let second = "@@ -9,1 +9,2 @@\n }\n+// done\n";
+    assert_eq!(
+        h.git.calls(),
+        [
+            format!("apply_patch /work/agent-a Copy {first}"),
+            format!("apply_patch /work/agent-a Copy {first}{second}"),
+            format!("apply_patch /work/agent-b Copy {second}"),
+        ]
+    );
+}
+
+#[test]
+fn project_commands_run_only_once_the_project_is_trusted() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = dir.path().join("repo/.pman.toml");
+    let mut trusted = Config::default();
+    trusted.hooks.setup = vec!["npm install".to_string()];
+    trusted.keys = toml::from_str("[session_picker]\n\"ctrl-b\" = \"palette\"\n").unwrap();
+    let mut config = Config::default();
+    config.untrusted = Some(UntrustedProject {
+        file: project.clone(),
+        contents: "[hooks]\nsetup = [\"npm install\"]\n".to_string(),
+        keys: vec!["hooks.setup".to_string()],
+        trust_path: dir.path().join("trusted.json"),
+        config: Box::new(trusted),
+    });
+    let tmux = FakeTmux::default();
+    let git = FakeGit::new();
+    let mut h = Harness::with_config(View::SessionPicker, config, tmux, git, FakeEditor::default());
+
+    let screen = h.screen();
+    assert!(screen.contains("Allow hooks.setup"), "{}", screen);
+    assert!(screen.contains("from repo/.pman.toml?"), "{}", screen);
+    assert!(h.app.config.hooks.setup.is_empty());
+
+    h.press(KeyCode::Char('y'));
+
+    assert_eq!(h.app.config.hooks.setup, ["npm install"]);
+    assert!(h.app.config.untrusted.is_none());
+    let store = TrustStore::load(dir.path().join("trusted.json"));
+    assert!(store.is_trusted(&project, "[hooks]\nsetup = [\"npm install\"]\n"));
+
+    // Keys and the rest take effect without a restart
+    h.press_with(KeyModifiers::CONTROL, KeyCode::Char('b'));
+    assert!(h.app.view == View::CommandPalette);
+}
+
+#[test]
+fn session_statuses_update_while_the_picker_is_open() {
+    let mut config = Config::default();
+    config.status.refresh_ms = 0;
+    let tmux = FakeTmux::with_sessions(&["api"]);
+    let mut h = Harness::with_config(
+        View::SessionPicker,
+        config,
+        tmux,
+        FakeGit::new(),
+        FakeEditor::default(),
+    );
+    assert!(!h.screen().contains('✕'));
+
+    h.tmux.panes.borrow_mut().push(TmuxPane {
+        session: "api".to_string(),
+        window_name: "claude".to_string(),
+        window_id: "@1".to_string(),
+        pane_id: "%1".to_string(),
+        pid: 1,
+        current_command: "claude".to_string(),
+        dead: true,
+        dead_status: Some(1),
+        window_activity: 0,
+    });
+    h.app.session_picker.tick();
+
+    assert!(h.screen().contains("✕ ○ api"), "{}", h.screen());
+}
+
+/// A harness with a `claude` agent profile and a setup hook.
+fn agent_harness(tmux: FakeTmux) -> Harness {
+    let mut config = Config::default();
+    config.hooks.setup = vec!["npm install".to_string()];
+    config.agents.insert(
+        "claude".to_string(),
+        AgentProfile {
+            command: "claude".to_string(),
+            args: Vec::new(),
+            env: BTreeMap::new(),
+            prompt_file: None,
+        },
+    );
+    Harness::with_config(
+        View::CommandPalette,
+        config,
+        tmux,
+        FakeGit::new(),
+        FakeEditor::default(),
+    )
+}
+
+fn launch_claude(branch: &str) -> Action {
+    Action::LaunchAgent {
+        profile: "claude".to_string(),
+        branch: branch.to_string(),
+    }
+}
+
+#[test]
+fn launched_agents_wait_for_setup_to_finish() {
+    let mut h = agent_harness(FakeTmux::default());
+
+    h.app.handle_action(launch_claude("feat")).unwrap();
+
+    let calls = h.tmux.calls();
+    let setup = calls.iter().find(|c| c.starts_with("new_window repo/feat setup ")).unwrap();
+    // The setup script is itself quoted for `sh -c`
+    assert!(setup.contains("tmux wait-for -S '\\''pman-setup-repo/feat'\\''"), "{}", setup);
+    let agent = "new_window repo/feat claude tmux wait-for 'pman-setup-repo/feat'; claude";
+    assert!(calls.iter().any(|c| c == agent), "{:?}", calls);
+}
+
+#[test]
+fn a_failed_agent_launch_kills_the_session_it_created() {
+    let tmux = FakeTmux::default();
+    tmux.failing_windows.borrow_mut().push("setup".to_string());
+    let mut h = agent_harness(tmux);
+
+    let error = h.app.handle_action(launch_claude("feat")).unwrap_err();
+
+    assert_eq!(error.to_string(), "Tmux error: could not open window 'setup'");
+    assert_eq!(h.tmux.calls().last().unwrap(), "kill_session repo/feat");
+    assert!(h.tmux.list_sessions().unwrap().is_empty());
+    assert!(h.app.running);
+}
+
+#[test]
+fn launching_an_agent_outside_a_repository_is_an_error() {
+    let mut h = agent_harness(FakeTmux::default());
+    h.app.git = None;
+
+    let error = h.app.handle_action(launch_claude("feat")).unwrap_err();
+    assert!(matches!(error, PmanError::NotGitRepo), "{}", error);
+    assert!(h.tmux.calls().is_empty(), "{:?}", h.tmux.calls());
+
+    // The palette says so before asking for a profile
+    h.app.execute_command(PaletteCommand::LaunchAgent).unwrap();
+    let screen = h.screen();
+    assert!(screen.contains("Not in a git repository"), "{}", screen);
+}
@@ -0,0 +1,287 @@
+//! Non-interactive subcommands for scripts and shell aliases.
+
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use clap::Subcommand;
+use serde::de::value::{Error as ValueError, StrDeserializer};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::error::{PmanError, Result};
+use crate::integrations::{
+    ActivityMonitor, EditorBackend, GitBackend, GitClient, NvimIntegration, TmuxBackend, TmuxClient,
+    WorktreeHooks,
+};
+use crate::models::{
+    AgentStatus, GitWorktree, MergeStrategy, NvimBuffer, NvimInstance, TmuxSession,
+};
+
+#[derive(Subcommand)]
+pub enum SessionCommand {
+    /// List sessions with their agent status
+    List,
+    /// Create a detached session
+    New {
+        name: String,
+        /// Starting directory (default: the current one)
+        #[arg(long)]
+        path: Option<PathBuf>,
+        /// Layout from `[layouts]` to apply
+        #[arg(long)]
+        layout: Option<String>,
+    },
+    /// Kill a session
+    Kill { name: String },
+    /// Switch the current tmux client to a session
+    Switch { name: String },
+}
+
+#[derive(Subcommand)]
+pub enum WorktreeCommand {
+    /// List the repository's worktrees
+    List,
+    /// Create a worktree, checking out the branch or creating it
+    New {
+        branch: String,
+        /// Ref a new branch starts from (default: HEAD)
+        #[arg(long)]
+        base: Option<String>,
+        /// Track `--base` when it is a remote branch
+        #[arg(long, requires = "base")]
+        track: bool,
+    },
+    /// Remove a worktree, refusing if it has uncommitted changes
+    Rm {
+        /// Branch name or path of the worktree
+        worktree: String,
+    },
+    /// Merge a worktree's branch into main, then remove the worktree and branch
+    Merge {
+        /// Branch name or path of the worktree
+        worktree: String,
+        /// merge, squash, rebase or ff-only (default: `merge.strategy`)
+        #[arg(long, value_parser = parse_strategy)]
+        strategy: Option<MergeStrategy>,
+        /// Squash commit message (default: the branch's commit subjects)
+        #[arg(long)]
+        message: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum BufferCommand {
+    /// List buffers open in every running nvim
+    List,
+    /// Open a file in the current session's nvim, starting one if there is none
+    Open {
+        file: PathBuf,
+        #[arg(long)]
+        line: Option<usize>,
+        #[arg(long, requires = "line")]
+        column: Option<usize>,
+    },
+}
+
+/// A buffer and the nvim instance it is open in.
+#[derive(Serialize)]
+struct BufferEntry<'a> {
+    #[serde(flatten)]
+    nvim: &'a NvimInstance,
+    #[serde(flatten)]
+    buffer: &'a NvimBuffer,
+}
+
+pub fn session(config: &Config, command: SessionCommand, json: bool) -> Result<()> {
+    let tmux = Rc::new(TmuxClient::new(config));
+
+    match command {
+        SessionCommand::List => {
+            let mut sessions = tmux.list_sessions()?;
+            ActivityMonitor::new(tmux.clone(), config).classify(&mut sessions)?;
+            if json {
+                return print_json(&sessions);
+            }
+            for session in &sessions {
+                let attached = if session.attached { "attached" } else { "" };
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    session.name,
+                    session.status.label(),
+                    attached,
+                    display_path(session.path.as_deref())
+                );
+            }
+        }
+        SessionCommand::New { name, path, layout } => {
+            let path = match path {
+                Some(path) => path,
+                None => std::env::current_dir()?,
+            };
+            let layout = layout
+                .map(|name| {
+                    config.layouts.get(&name).ok_or_else(|| {
+                        PmanError::Config(format!("no layout named '{}'", name))
+                    })
+                })
+                .transpose()?;
+
+            let name = tmux.create_session(&name, Some(&path))?;
+            if let Some(layout) = layout {
+                if let Err(e) = tmux.apply_layout(&name, &path, layout) {
+                    let _ = tmux.kill_session(&name);
+                    return Err(e);
+                }
+            }
+
+            if json {
+                // A session whose shell exits at once is already gone; still report it
+                let session = tmux
+                    .list_sessions()?
+                    .into_iter()
+                    .find(|s| s.name == name)
+                    .unwrap_or(TmuxSession {
+                        name,
+                        attached: false,
+                        path: Some(path),
+                        agent: None,
+                        status: AgentStatus::Unknown,
+                    });
+                return print_json(&session);
+            }
+            println!("{}", name);
+        }
+        SessionCommand::Kill { name } => tmux.kill_session(&name)?,
+        SessionCommand::Switch { name } => tmux.switch_session(&name)?,
+    }
+
+    Ok(())
+}
+
+pub fn worktree(config: &Config, dir: &Path, command: WorktreeCommand, json: bool) -> Result<()> {
+    let git = GitClient::new(dir, config)?;
+
+    match command {
+        WorktreeCommand::List => {
+            let worktrees = git.list_worktrees()?;
+            if json {
+                return print_json(&worktrees);
+            }
+            for worktree in &worktrees {
+                println!("{}\t{}", worktree.display_name(), worktree.path.display());
+            }
+        }
+        WorktreeCommand::New {
+            branch,
+            base,
+            track,
+        } => {
+            // An existing branch is checked out as it is
+            if base.is_some() && git.branch_exists(&branch) {
+                return Err(PmanError::Usage(format!(
+                    "branch '{}' already exists; --base and --track only apply to new branches",
+                    branch
+                )));
+            }
+            let path = git.add_worktree(&branch, base.as_deref(), track)?;
+            WorktreeHooks::new(Rc::new(TmuxClient::new(config)), config.hooks.clone())
+                .run_for(&git, &path)?;
+
+            if json {
+                let worktree = find_worktree(&git, &path.to_string_lossy())?;
+                return print_json(&worktree);
+            }
+            println!("{}", path.display());
+        }
+        WorktreeCommand::Rm { worktree } => {
+            let worktree = find_worktree(&git, &worktree)?;
+            git.delete_worktree(&worktree.path)?;
+        }
+        WorktreeCommand::Merge {
+            worktree,
+            strategy,
+            message,
+        } => {
+            let worktree = find_worktree(&git, &worktree)?;
+            let strategy = strategy.unwrap_or(config.merge.strategy);
+            let message = match message {
+                None if strategy == MergeStrategy::Squash => {
+                    let subjects = git.branch_subjects(&worktree.branch)?;
+                    Some(MergeStrategy::squash_message(&worktree.branch, &subjects))
+                }
+                message => message,
+            };
+            git.merge_to_main(&worktree.path, &worktree.branch, strategy, message.as_deref())?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn buffer(config: &Config, command: BufferCommand, json: bool) -> Result<()> {
+    let nvim = NvimIntegration::new(Rc::new(TmuxClient::new(config)));
+
+    match command {
+        BufferCommand::List => {
+            let buffers = nvim.list_buffers()?;
+            if json {
+                let entries: Vec<BufferEntry> = buffers
+                    .iter()
+                    .map(|(nvim, buffer)| BufferEntry { nvim, buffer })
+                    .collect();
+                return print_json(&entries);
+            }
+            for (nvim, buffer) in &buffers {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    nvim.label(),
+                    buffer.bufnr,
+                    buffer.name.display(),
+                    nvim.socket.display()
+                );
+            }
+        }
+        BufferCommand::Open { file, line, column } => {
+            let file = std::env::current_dir()?.join(file);
+            let position = line.map(|line| (line, column.unwrap_or(1)));
+            nvim.open_file(&file, position)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Report a failed subcommand on stderr and exit with the code for its kind of error.
+pub fn exit_on_error(result: Result<()>) -> Result<()> {
+    result.or_else(|e| exit_with(e))
+}
+
+pub fn exit_with(error: PmanError) -> ! {
+    eprintln!("Error: {}", error);
+    std::process::exit(error.exit_code().into());
+}
+
+/// The worktree whose branch is `target`, or whose path is `target`.
+fn find_worktree(git: &GitClient, target: &str) -> Result<GitWorktree> {
+    let path = Path::new(target);
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+    git.list_worktrees()?
+        .into_iter()
+        .find(|w| w.branch == target || w.path == path)
+        .ok_or_else(|| PmanError::Git(format!("no worktree for '{}'", target)))
+}
+
+fn parse_strategy(value: &str) -> std::result::Result<MergeStrategy, String> {
+    MergeStrategy::deserialize(StrDeserializer::<ValueError>::new(value)).map_err(|e| e.to_string())
+}
+
+fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    let json = serde_json::to_string_pretty(value).map_err(std::io::Error::from)?;
+    println!("{}", json);
+    Ok(())
+}
+
+fn display_path(path: Option<&Path>) -> String {
+    path.map(|p| p.display().to_string()).unwrap_or_default()
+}
@@ -1,41 +1,60 @@
-use std::path::PathBuf;
+use std::rc::Rc;
 
-use ratatui::{layout::Rect, Frame};
+use ratatui::{
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+    Frame,
+};
 
 use crate::actions::Action;
 use crate::components::{Component, FuzzyList};
 use crate::error::Result;
-use crate::integrations::NvimIntegration;
-use crate::models::NvimBuffer;
+use crate::integrations::EditorBackend;
+use crate::models::{NvimBuffer, NvimInstance};
+use crate::tui::{KeyContext, Keymap};
 
 #[derive(Clone)]
 pub struct BufferEntry {
-    pub socket: PathBuf,
+    pub nvim: NvimInstance,
     pub buffer: NvimBuffer,
 }
 
 impl BufferEntry {
     pub fn display_name(&self) -> String {
-        self.buffer.display_name()
+        format!("{}  {}", self.buffer.display_name(), self.nvim.label())
+    }
+
+    pub fn display_line(&self) -> Line<'static> {
+        Line::from(vec![
+            Span::raw(self.buffer.display_name()),
+            Span::styled(
+                format!("  {}", self.nvim.label()),
+                Style::default().fg(Color::DarkGray),
+            ),
+        ])
     }
 
     pub fn search_text(&self) -> String {
-        self.buffer.search_text()
+        format!("{} {}", self.buffer.search_text(), self.nvim.label())
     }
 }
 
 pub struct BufferPicker {
     fuzzy_list: FuzzyList<BufferEntry>,
+    editor: Rc<dyn EditorBackend>,
 }
 
 impl BufferPicker {
-    pub fn new() -> Self {
+    pub fn new(editor: Rc<dyn EditorBackend>) -> Self {
         let mut picker = Self {
             fuzzy_list: FuzzyList::new(
                 "Nvim Buffers",
                 BufferEntry::display_name,
                 BufferEntry::search_text,
-            ),
+            )
+            .with_line_fn(BufferEntry::display_line),
+            editor,
         };
 
         let _ = picker.refresh();
@@ -43,11 +62,11 @@ impl BufferPicker {
     }
 
     pub fn refresh(&mut self) -> Result<()> {
-        let buffers = NvimIntegration::list_buffers().unwrap_or_default();
+        let buffers = self.editor.list_buffers().unwrap_or_default();
 
         let entries: Vec<BufferEntry> = buffers
             .into_iter()
-            .map(|(socket, buffer)| BufferEntry { socket, buffer })
+            .map(|(nvim, buffer)| BufferEntry { nvim, buffer })
             .collect();
 
         self.fuzzy_list.set_items(entries);
@@ -55,12 +74,6 @@ impl BufferPicker {
     }
 }
 
-impl Default for BufferPicker {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
 impl Component for BufferPicker {
     fn handle_action(&mut self, action: &Action) -> Result<Option<Action>> {
         match action {
@@ -80,18 +93,10 @@ impl Component for BufferPicker {
                 self.fuzzy_list.page_down(10);
                 Ok(Some(Action::Render))
             }
-            Action::Character(c) => {
-                self.fuzzy_list.push_char(*c);
-                Ok(Some(Action::Render))
-            }
-            Action::Backspace => {
-                self.fuzzy_list.pop_char();
-                Ok(Some(Action::Render))
-            }
             Action::Enter => {
                 if let Some(entry) = self.fuzzy_list.selected() {
                     Ok(Some(Action::OpenBuffer {
-                        socket: entry.socket.clone(),
+                        nvim: entry.nvim.clone(),
                         bufnr: entry.buffer.bufnr,
                     }))
                 } else {
@@ -106,7 +111,7 @@ impl Component for BufferPicker {
                     Ok(Some(Action::GoBack))
                 }
             }
-            _ => Ok(None),
+            _ => Ok(self.fuzzy_list.handle_input(action).then_some(Action::Render)),
         }
     }
 
@@ -114,7 +119,11 @@ impl Component for BufferPicker {
         self.fuzzy_list.render(frame, area);
     }
 
-    fn help_text(&self) -> &'static str {
-        "Enter:open  Esc:back"
+    fn help_text(&self, keymap: &Keymap) -> String {
+        keymap.help_text(KeyContext::BufferPicker, &[("confirm", "open"), ("back", "back")])
+    }
+
+    fn is_typing(&self) -> bool {
+        !self.fuzzy_list.query().is_empty()
     }
 }
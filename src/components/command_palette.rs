@@ -1,12 +1,10 @@
-use std::path::Path;
-
 use ratatui::{layout::Rect, Frame};
 
 use crate::actions::Action;
 use crate::components::{Component, FuzzyList};
 use crate::error::Result;
-use crate::integrations::GitClient;
 use crate::models::PaletteCommand;
+use crate::tui::{KeyContext, Keymap};
 
 pub struct CommandPalette {
     fuzzy_list: FuzzyList<PaletteCommand>,
@@ -14,9 +12,7 @@ pub struct CommandPalette {
 }
 
 impl CommandPalette {
-    pub fn new(current_path: &Path) -> Self {
-        let is_git_repo = GitClient::is_git_repo(current_path);
-
+    pub fn new(is_git_repo: bool) -> Self {
         let mut palette = Self {
             fuzzy_list: FuzzyList::new(
                 "Commands",
@@ -38,11 +34,6 @@ impl CommandPalette {
         };
         self.fuzzy_list.set_items(commands);
     }
-
-    pub fn set_git_repo(&mut self, is_git_repo: bool) {
-        self.is_git_repo = is_git_repo;
-        self.refresh();
-    }
 }
 
 impl Component for CommandPalette {
@@ -64,14 +55,6 @@ impl Component for CommandPalette {
                 self.fuzzy_list.page_down(10);
                 Ok(Some(Action::Render))
             }
-            Action::Character(c) => {
-                self.fuzzy_list.push_char(*c);
-                Ok(Some(Action::Render))
-            }
-            Action::Backspace => {
-                self.fuzzy_list.pop_char();
-                Ok(Some(Action::Render))
-            }
             Action::Enter => {
                 if let Some(cmd) = self.fuzzy_list.selected() {
                     Ok(Some(Action::ExecuteCommand(*cmd)))
@@ -87,7 +70,7 @@ impl Component for CommandPalette {
                     Ok(Some(Action::GoBack))
                 }
             }
-            _ => Ok(None),
+            _ => Ok(self.fuzzy_list.handle_input(action).then_some(Action::Render)),
         }
     }
 
@@ -95,7 +78,11 @@ impl Component for CommandPalette {
         self.fuzzy_list.render(frame, area);
     }
 
-    fn help_text(&self) -> &'static str {
-        "Enter:execute  Esc:back"
+    fn help_text(&self, keymap: &Keymap) -> String {
+        keymap.help_text(KeyContext::CommandPalette, &[("confirm", "execute"), ("back", "back")])
+    }
+
+    fn is_typing(&self) -> bool {
+        !self.fuzzy_list.query().is_empty()
     }
 }
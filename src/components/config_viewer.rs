@@ -0,0 +1,92 @@
+use ratatui::{
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+    Frame,
+};
+
+use crate::actions::Action;
+use crate::components::{Component, FuzzyList};
+use crate::config::{Config, ConfigEntry, ConfigSource};
+use crate::error::Result;
+use crate::tui::{KeyContext, Keymap};
+
+/// The effective config after merging, one value per line with its source.
+pub struct ConfigViewer {
+    fuzzy_list: FuzzyList<ConfigEntry>,
+}
+
+impl ConfigViewer {
+    pub fn new(config: &Config) -> Self {
+        let mut fuzzy_list =
+            FuzzyList::new("Effective Config", entry_text, entry_text).with_line_fn(entry_line);
+        fuzzy_list.set_items(config.entries());
+        Self { fuzzy_list }
+    }
+}
+
+fn entry_text(entry: &ConfigEntry) -> String {
+    format!("{} = {}  ({})", entry.key, entry.value, entry.source.label())
+}
+
+fn entry_line(entry: &ConfigEntry) -> Line<'static> {
+    let source_color = match entry.source {
+        ConfigSource::Default => Color::DarkGray,
+        ConfigSource::User(_) => Color::Yellow,
+        ConfigSource::Project(_) => Color::Green,
+    };
+
+    Line::from(vec![
+        Span::styled(entry.key.clone(), Style::default().fg(Color::Cyan)),
+        Span::raw(" = "),
+        Span::raw(entry.value.clone()),
+        Span::styled(
+            format!("  ({})", entry.source.label()),
+            Style::default().fg(source_color),
+        ),
+    ])
+}
+
+impl Component for ConfigViewer {
+    fn handle_action(&mut self, action: &Action) -> Result<Option<Action>> {
+        match action {
+            Action::MoveUp => {
+                self.fuzzy_list.move_up();
+                Ok(Some(Action::Render))
+            }
+            Action::MoveDown => {
+                self.fuzzy_list.move_down();
+                Ok(Some(Action::Render))
+            }
+            Action::PageUp => {
+                self.fuzzy_list.page_up(10);
+                Ok(Some(Action::Render))
+            }
+            Action::PageDown => {
+                self.fuzzy_list.page_down(10);
+                Ok(Some(Action::Render))
+            }
+            Action::Escape => {
+                if !self.fuzzy_list.query().is_empty() {
+                    self.fuzzy_list.clear_query();
+                    Ok(Some(Action::Render))
+                } else {
+                    Ok(Some(Action::GoBack))
+                }
+            }
+            _ => Ok(self.fuzzy_list.handle_input(action).then_some(Action::Render)),
+        }
+    }
+
+    fn render(&mut self, frame: &mut Frame, area: Rect) {
+        self.fuzzy_list.render(frame, area);
+    }
+
+    fn help_text(&self, keymap: &Keymap) -> String {
+        keymap.help_text(KeyContext::ConfigViewer, &[("back", "back")])
+    }
+
+    fn is_typing(&self) -> bool {
+        !self.fuzzy_list.query().is_empty()
+    }
+}
@@ -7,6 +7,7 @@ use ratatui::{
 
 use crate::actions::{Action, ConfirmCallback};
 use crate::error::Result;
+use crate::models::PatchAction;
 
 pub struct ConfirmDialog {
     title: String,
@@ -27,14 +28,14 @@ impl ConfirmDialog {
 
     pub fn handle_action(&mut self, action: &Action) -> Result<Option<Action>> {
         match action {
-            Action::Character('y') | Action::Character('Y') => {
+            Action::Yes => {
                 self.selected = true;
                 Ok(Some(self.confirm()))
             }
-            Action::Character('n') | Action::Character('N') => {
+            Action::No => {
                 Ok(Some(Action::CloseDialog))
             }
-            Action::MoveUp | Action::MoveDown | Action::Character('h') | Action::Character('l') => {
+            Action::MoveUp | Action::MoveDown | Action::Toggle => {
                 self.selected = !self.selected;
                 Ok(Some(Action::Render))
             }
@@ -53,14 +54,19 @@ impl ConfirmDialog {
     fn confirm(&self) -> Action {
         match &self.callback {
             ConfirmCallback::DeleteWorktree(path) => Action::DeleteWorktree(path.clone()),
-            ConfirmCallback::MergeWorktree(path) => Action::MergeWorktree(path.clone()),
             ConfirmCallback::KillSession(name) => Action::KillSession(name.clone()),
+            ConfirmCallback::DiscardChanges { root, patch } => Action::ApplyPatch {
+                root: root.clone(),
+                patch: patch.clone(),
+                action: PatchAction::Discard,
+            },
+            ConfirmCallback::TrustProject => Action::TrustProject,
         }
     }
 
     pub fn render(&self, frame: &mut Frame, area: Rect) {
         let dialog_width = 50.min(area.width.saturating_sub(4));
-        let dialog_height = 7;
+        let dialog_height = 8;
 
         let dialog_area = centered_rect(dialog_width, dialog_height, area);
 
@@ -0,0 +1,681 @@
+use std::collections::BTreeSet;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    Frame,
+};
+
+use crate::actions::{Action, ConfirmCallback, InputCallback};
+use crate::components::Component;
+use crate::error::{PmanError, Result};
+use crate::integrations::GitBackend;
+use crate::models::{Diff, DiffLine, FileDiff, FileStatus, Hunk, LineKind, PatchAction};
+use crate::tui::{KeyContext, Keymap};
+
+/// Changed files on the left, the selected file's hunks on the right.
+///
+/// For the uncommitted changes of a worktree, hunks or lines marked in them can be
+/// staged, unstaged and discarded, and what is staged committed. Comparing two worktrees,
+/// they can be copied from one into the other.
+pub struct DiffViewer {
+    git: Option<Rc<dyn GitBackend>>,
+    source: Source,
+    diff: Diff,
+    /// Why the diff could not be read
+    error: Option<String>,
+    /// Outcome of the last operation that failed, until the next key
+    status: Option<String>,
+    file_state: ListState,
+    /// Current hunk of the selected file
+    hunk: usize,
+    /// Line of the current hunk the cursor is on
+    line: usize,
+    /// Lines of the current hunk marked for a line-level operation
+    marked: BTreeSet<usize>,
+    scroll: u16,
+    /// Height of the hunk pane when last drawn, for paging
+    page_height: u16,
+}
+
+/// Where the diff comes from.
+enum Source {
+    /// Uncommitted changes of the worktree containing `path`
+    Worktree { path: PathBuf, staged: bool },
+    /// What would turn the `target` worktree into `source`, which is what gets copied
+    Compare { target: PathBuf, source: PathBuf },
+}
+
+impl DiffViewer {
+    /// Changes in the worktree containing `path`, starting with the unstaged ones unless
+    /// everything is staged.
+    pub fn new(git: Option<Rc<dyn GitBackend>>, path: PathBuf) -> Self {
+        let mut viewer = Self::with_source(git, Source::Worktree { path, staged: false });
+        if viewer.diff.files.is_empty() && viewer.error.is_none() {
+            viewer.toggle_sides();
+            if viewer.diff.files.is_empty() {
+                viewer.toggle_sides();
+            }
+        }
+        viewer
+    }
+
+    /// Differences between two worktrees, for copying files and hunks from `source` into
+    /// `target`.
+    pub fn compare(git: Option<Rc<dyn GitBackend>>, target: PathBuf, source: PathBuf) -> Self {
+        Self::with_source(git, Source::Compare { target, source })
+    }
+
+    fn with_source(git: Option<Rc<dyn GitBackend>>, source: Source) -> Self {
+        let mut viewer = Self {
+            git,
+            source,
+            diff: Diff::default(),
+            error: None,
+            status: None,
+            file_state: ListState::default(),
+            hunk: 0,
+            line: 0,
+            marked: BTreeSet::new(),
+            scroll: 0,
+            page_height: 0,
+        };
+        viewer.load();
+        viewer
+    }
+
+    /// Re-read the diff after it changed, staying on the same file and hunk if they are
+    /// still there.
+    pub fn refresh(&mut self) {
+        let path = self.selected_file().map(|file| file.path.clone());
+        let hunk = self.hunk;
+        self.load();
+
+        let Some(index) = self.diff.files.iter().position(|f| Some(&f.path) == path.as_ref())
+        else {
+            return;
+        };
+        self.select_file(Some(index));
+        let hunks = self.diff.files[index].hunks.len();
+        self.select_hunk(hunk.min(hunks.saturating_sub(1)));
+    }
+
+    /// Show why an operation failed.
+    pub fn set_status(&mut self, message: impl Into<String>) {
+        self.status = Some(message.into());
+    }
+
+    fn load(&mut self) {
+        let diff = match (&self.git, &self.source) {
+            (Some(git), Source::Worktree { path, staged }) => git.diff(path, *staged),
+            (Some(git), Source::Compare { target, source }) => git.compare(target, source),
+            (None, _) => Err(PmanError::NotGitRepo),
+        };
+        match diff {
+            Ok(diff) => {
+                self.diff = diff;
+                self.error = None;
+            }
+            Err(e) => {
+                self.diff = Diff::default();
+                self.error = Some(e.to_string());
+            }
+        }
+
+        let selected = (!self.diff.files.is_empty()).then_some(0);
+        self.select_file(selected);
+    }
+
+    /// Whether the staged changes are shown, or `None` when comparing worktrees.
+    fn staged(&self) -> Option<bool> {
+        match self.source {
+            Source::Worktree { staged, .. } => Some(staged),
+            Source::Compare { .. } => None,
+        }
+    }
+
+    fn selected_file(&self) -> Option<&FileDiff> {
+        self.diff.files.get(self.file_state.selected()?)
+    }
+
+    fn current_hunk(&self) -> Option<&Hunk> {
+        self.selected_file()?.hunks.get(self.hunk)
+    }
+
+    fn select_file(&mut self, index: Option<usize>) {
+        self.file_state.select(index);
+        self.select_hunk(0);
+    }
+
+    fn select_hunk(&mut self, hunk: usize) {
+        self.hunk = hunk;
+        self.line = self.current_hunk().map_or(0, Hunk::first_change);
+        self.marked.clear();
+        self.scroll_to_hunk();
+    }
+
+    fn move_file(&mut self, forward: bool) {
+        let count = self.diff.files.len();
+        let Some(current) = self.file_state.selected().filter(|_| count > 0) else {
+            return;
+        };
+        let next = if forward {
+            (current + 1) % count
+        } else {
+            (current + count - 1) % count
+        };
+        self.select_file(Some(next));
+    }
+
+    /// Go to the next hunk, continuing with the next file after the last one.
+    fn next_hunk(&mut self) {
+        let Some(index) = self.file_state.selected() else {
+            return;
+        };
+        if self.hunk + 1 < self.diff.files[index].hunks.len() {
+            self.select_hunk(self.hunk + 1);
+        } else if index + 1 < self.diff.files.len() {
+            self.select_file(Some(index + 1));
+        }
+    }
+
+    /// Go to the previous hunk, continuing with the last hunk of the previous file.
+    fn prev_hunk(&mut self) {
+        let Some(index) = self.file_state.selected() else {
+            return;
+        };
+        if self.hunk > 0 {
+            self.select_hunk(self.hunk - 1);
+        } else if index > 0 {
+            self.select_file(Some(index - 1));
+            let last = self.diff.files[index - 1].hunks.len().saturating_sub(1);
+            self.select_hunk(last);
+        }
+    }
+
+    fn move_line(&mut self, down: bool) {
+        let Some(lines) = self.current_hunk().map(|hunk| hunk.lines.len()) else {
+            return;
+        };
+        self.line = if down {
+            (self.line + 1).min(lines.saturating_sub(1))
+        } else {
+            self.line.saturating_sub(1)
+        };
+
+        // Keep the cursor on screen
+        let cursor = (self.hunk_offset() + 1 + self.line) as u16;
+        if cursor < self.scroll {
+            self.scroll = cursor;
+        } else if self.page_height > 0 && cursor >= self.scroll + self.page_height {
+            self.scroll = cursor + 1 - self.page_height;
+        }
+    }
+
+    /// Mark or unmark the changed line under the cursor.
+    fn toggle_line(&mut self) {
+        let changed = self
+            .current_hunk()
+            .and_then(|hunk| hunk.lines.get(self.line))
+            .is_some_and(|line| line.kind != LineKind::Context);
+        if changed && !self.marked.remove(&self.line) {
+            self.marked.insert(self.line);
+        }
+    }
+
+    /// Lines of the selected file before the current hunk, each hunk being drawn as its
+    /// header and its lines.
+    fn hunk_offset(&self) -> usize {
+        self.selected_file().map_or(0, |file| {
+            file.hunks[..self.hunk.min(file.hunks.len())]
+                .iter()
+                .map(|h| h.lines.len() + 1)
+                .sum()
+        })
+    }
+
+    fn scroll_to_hunk(&mut self) {
+        self.scroll = self.hunk_offset() as u16;
+    }
+
+    fn scroll_by(&mut self, down: bool) {
+        let step = (self.page_height / 2).max(1);
+        let total = self.selected_file().map_or(0, |file| {
+            file.hunks.iter().map(|h| h.lines.len() + 1).sum::<usize>()
+        });
+        self.scroll = if down {
+            (self.scroll + step).min(total.saturating_sub(1) as u16)
+        } else {
+            self.scroll.saturating_sub(step)
+        };
+    }
+
+    /// Open the selected file in nvim at the cursor.
+    fn open_hunk(&self) -> Option<Action> {
+        let file = self.selected_file()?;
+        if file.status == FileStatus::Deleted {
+            return None;
+        }
+        let line = file.hunks.get(self.hunk).map_or(1, |hunk| hunk.new_line(self.line));
+        // The new side of a comparison is the source worktree
+        let root = match self.source {
+            Source::Worktree { .. } => &self.diff.root,
+            Source::Compare { ref source, .. } => source,
+        };
+        Some(Action::OpenFile {
+            path: root.join(&file.path),
+            position: Some((line, 1)),
+        })
+    }
+
+    /// A patch of the marked lines of the current hunk, or of all of it.
+    fn patch(&mut self, action: PatchAction) -> Option<String> {
+        let file = self.selected_file()?;
+        let partial = !self.marked.is_empty();
+        if partial && matches!(file.status, FileStatus::Added | FileStatus::Deleted) {
+            self.set_status("Lines of added or deleted files can only be taken whole");
+            return None;
+        }
+        let lines = partial.then_some(&self.marked);
+        Some(file.patch(self.hunk, lines, action.is_reverse()))
+    }
+
+    fn apply(&mut self, action: PatchAction) -> Option<Action> {
+        let patch = self.patch(action)?;
+        Some(Action::ApplyPatch {
+            root: self.diff.root.clone(),
+            patch,
+            action,
+        })
+    }
+
+    /// Copy the current file from the source worktree into the target.
+    fn copy_file(&mut self) -> Option<Action> {
+        let file = self.selected_file()?;
+        if file.binary {
+            self.set_status("Binary files can't be copied");
+            return None;
+        }
+        Some(Action::ApplyPatch {
+            root: self.diff.root.clone(),
+            patch: file.file_patch(),
+            action: PatchAction::Copy,
+        })
+    }
+
+    fn confirm_discard(&mut self) -> Option<Action> {
+        if self.staged()? {
+            self.set_status("Unstage changes before discarding them");
+            return None;
+        }
+        let what = if self.marked.is_empty() {
+            "this hunk"
+        } else {
+            "the marked lines"
+        };
+        let path = self.selected_file()?.path.display().to_string();
+        let patch = self.patch(PatchAction::Discard)?;
+        Some(Action::ShowConfirm {
+            title: "Discard Changes".to_string(),
+            message: format!("Discard {} in {}? This cannot be undone.", what, path),
+            callback: ConfirmCallback::DiscardChanges {
+                root: self.diff.root.clone(),
+                patch,
+            },
+        })
+    }
+
+    fn commit(&self) -> Option<Action> {
+        if self.error.is_some() || self.staged().is_none() {
+            return None;
+        }
+        Some(Action::ShowInput {
+            title: "Commit Message".to_string(),
+            callback: InputCallback::CommitMessage(self.diff.root.clone()),
+        })
+    }
+
+    /// Switch between unstaged and staged changes, or swap the compared worktrees.
+    fn toggle_sides(&mut self) {
+        match self.source {
+            Source::Worktree { ref mut staged, .. } => *staged = !*staged,
+            Source::Compare {
+                ref mut target,
+                ref mut source,
+            } => std::mem::swap(target, source),
+        }
+        self.load();
+    }
+
+    fn render_files(&mut self, frame: &mut Frame, area: Rect) {
+        let items: Vec<ListItem> =
+            self.diff.files.iter().map(|f| ListItem::new(file_line(f))).collect();
+        let title = match self.source {
+            Source::Worktree { staged, .. } => {
+                let side = if staged { "Staged" } else { "Unstaged" };
+                format!(" {} · {} ({}) ", dir_name(&self.diff.root), side, items.len())
+            }
+            Source::Compare {
+                ref target,
+                ref source,
+            } => format!(" {} ← {} ({}) ", dir_name(target), dir_name(source), items.len()),
+        };
+
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .highlight_style(Style::default().bg(Color::Blue).add_modifier(Modifier::BOLD))
+            .highlight_symbol("▶ ");
+
+        frame.render_stateful_widget(list, area, &mut self.file_state);
+    }
+
+    fn render_hunks(&mut self, frame: &mut Frame, area: Rect) {
+        let mut block = Block::default().borders(Borders::ALL);
+        if let Some(ref status) = self.status {
+            let status = status.lines().collect::<Vec<_>>().join(" ");
+            block = block.title_bottom(Span::styled(
+                format!(" {} ", status),
+                Style::default().fg(Color::Red),
+            ));
+        }
+        self.page_height = block.inner(area).height;
+
+        let dim = Style::default().fg(Color::DarkGray);
+        let Some(file) = self.selected_file() else {
+            let message = match self.error {
+                Some(ref error) => Span::styled(error.clone(), Style::default().fg(Color::Red)),
+                None => match self.staged() {
+                    Some(true) => Span::styled("Nothing staged", dim),
+                    Some(false) => Span::styled("No unstaged changes", dim),
+                    None => Span::styled("No differences", dim),
+                },
+            };
+            frame.render_widget(Paragraph::new(message).block(block.title(" Diff ")), area);
+            return;
+        };
+
+        let title = match file.old_path {
+            Some(ref old) => format!(" {} → {} ", old.display(), file.path.display()),
+            None => format!(" {} ", file.path.display()),
+        };
+
+        let mut lines = Vec::new();
+        if file.binary {
+            lines.push(Line::from(Span::styled("Binary file", dim)));
+        } else if file.hunks.is_empty() {
+            lines.push(Line::from(Span::styled("No content changes", dim)));
+        }
+        for (index, hunk) in file.hunks.iter().enumerate() {
+            let mut header = Style::default().fg(Color::Cyan);
+            let cursor = (index == self.hunk).then_some((self.line, &self.marked));
+            if cursor.is_some() {
+                header = header.add_modifier(Modifier::REVERSED);
+            }
+            lines.push(Line::from(Span::styled(hunk.header(), header)));
+            lines.extend(hunk_lines(hunk, cursor));
+        }
+
+        let paragraph = Paragraph::new(lines)
+            .block(block.title(title))
+            .scroll((self.scroll, 0));
+        frame.render_widget(paragraph, area);
+    }
+}
+
+fn dir_name(path: &Path) -> String {
+    path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+fn file_line(file: &FileDiff) -> Line<'static> {
+    let color = match file.status {
+        FileStatus::Added => Color::Green,
+        FileStatus::Deleted => Color::Red,
+        FileStatus::Modified => Color::Yellow,
+        FileStatus::Renamed => Color::Cyan,
+    };
+    Line::from(vec![
+        Span::styled(format!("{} ", file.status.marker()), Style::default().fg(color)),
+        Span::raw(file.path.display().to_string()),
+        Span::styled(format!("  +{}", file.additions()), Style::default().fg(Color::Green)),
+        Span::styled(format!(" -{}", file.deletions()), Style::default().fg(Color::Red)),
+    ])
+}
+
+/// The hunk's lines with old and new line numbers, the changed words highlighted. The
+/// current hunk also shows the cursor line and the marked lines.
+fn hunk_lines(hunk: &Hunk, cursor: Option<(usize, &BTreeSet<usize>)>) -> Vec<Line<'static>> {
+    let dim = Style::default().fg(Color::DarkGray);
+    let number = |n: Option<usize>| n.map(|n| n.to_string()).unwrap_or_default();
+
+    let (mut old, mut new) = (hunk.old_start, hunk.new_start);
+    let changes = word_changes(&hunk.lines);
+    hunk.lines
+        .iter()
+        .zip(changes)
+        .enumerate()
+        .map(|(index, (line, change))| {
+            let (sign, color, numbers) = match line.kind {
+                LineKind::Context => (' ', Color::Reset, (Some(old), Some(new))),
+                LineKind::Removed => ('-', Color::Red, (Some(old), None)),
+                LineKind::Added => ('+', Color::Green, (None, Some(new))),
+            };
+            if line.kind != LineKind::Added {
+                old += 1;
+            }
+            if line.kind != LineKind::Removed {
+                new += 1;
+            }
+
+            let marker = match cursor {
+                Some((line, _)) if line == index => Span::styled("▶", Color::Yellow),
+                Some((_, marked)) if marked.contains(&index) => Span::styled("●", Color::Yellow),
+                _ => Span::raw(" "),
+            };
+            let style = Style::default().fg(color);
+            let gutter = format!("{:>4} {:>4} ", number(numbers.0), number(numbers.1));
+            let mut spans = vec![
+                marker,
+                Span::styled(gutter, dim),
+                Span::styled(sign.to_string(), style),
+            ];
+            let content = &line.content;
+            match change {
+                Some(range) => {
+                    let strong = Style::default().fg(Color::Black).bg(color);
+                    spans.push(Span::styled(display_text(&content[..range.start]), style));
+                    spans.push(Span::styled(display_text(&content[range.clone()]), strong));
+                    spans.push(Span::styled(display_text(&content[range.end..]), style));
+                }
+                None => spans.push(Span::styled(display_text(content), style)),
+            }
+            Line::from(spans)
+        })
+        .collect()
+}
+
+fn display_text(text: &str) -> String {
+    text.replace('\t', "    ").replace('\r', "")
+}
+
+/// Byte range of the changed words in each line, for runs of removed lines followed by
+/// as many added ones.
+fn word_changes(lines: &[DiffLine]) -> Vec<Option<Range<usize>>> {
+    let mut changes = vec![None; lines.len()];
+    let mut start = 0;
+    while start < lines.len() {
+        let kind_run = |from: usize, kind: LineKind| {
+            lines[from..].iter().take_while(|line| line.kind == kind).count()
+        };
+        let removed = kind_run(start, LineKind::Removed);
+        let added = kind_run(start + removed, LineKind::Added);
+
+        if removed > 0 && removed == added {
+            for i in 0..removed {
+                let (old, new) = (start + i, start + removed + i);
+                let (old_change, new_change) =
+                    changed_words(&lines[old].content, &lines[new].content);
+                changes[old] = old_change;
+                changes[new] = new_change;
+            }
+        }
+        start += (removed + added).max(1);
+    }
+    changes
+}
+
+/// What lies between the words two lines start and end with in common. Lines with
+/// nothing in common get no highlight, as it would cover them entirely.
+fn changed_words(old: &str, new: &str) -> (Option<Range<usize>>, Option<Range<usize>>) {
+    let old_words = words(old);
+    let new_words = words(new);
+
+    let prefix = old_words.iter().zip(&new_words).take_while(|(a, b)| a == b).count();
+    let suffix = old_words
+        .iter()
+        .rev()
+        .zip(new_words.iter().rev())
+        .take(old_words.len().min(new_words.len()) - prefix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    if prefix + suffix == 0 {
+        return (None, None);
+    }
+
+    let range = |words: &[&str], line: &str| {
+        let start: usize = words[..prefix].iter().map(|w| w.len()).sum();
+        let end = line.len() - words[words.len() - suffix..].iter().map(|w| w.len()).sum::<usize>();
+        (start < end).then_some(start..end)
+    };
+    (range(&old_words, old), range(&new_words, new))
+}
+
+/// `line` split into runs of letters, digits and `_`, and single other characters.
+fn words(line: &str) -> Vec<&str> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+
+    let mut words = Vec::new();
+    let mut chars = line.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let mut end = start + c.len_utf8();
+        if is_word(c) {
+            while let Some(&(i, next)) = chars.peek().filter(|(_, next)| is_word(*next)) {
+                end = i + next.len_utf8();
+                chars.next();
+            }
+        }
+        words.push(&line[start..end]);
+    }
+    words
+}
+
+impl Component for DiffViewer {
+    fn handle_action(&mut self, action: &Action) -> Result<Option<Action>> {
+        self.status = None;
+        match action {
+            Action::MoveUp => {
+                self.move_file(false);
+                Ok(Some(Action::Render))
+            }
+            Action::MoveDown => {
+                self.move_file(true);
+                Ok(Some(Action::Render))
+            }
+            Action::LineUp => {
+                self.move_line(false);
+                Ok(Some(Action::Render))
+            }
+            Action::LineDown => {
+                self.move_line(true);
+                Ok(Some(Action::Render))
+            }
+            Action::PageUp => {
+                self.scroll_by(false);
+                Ok(Some(Action::Render))
+            }
+            Action::PageDown => {
+                self.scroll_by(true);
+                Ok(Some(Action::Render))
+            }
+            Action::NextHunk => {
+                self.next_hunk();
+                Ok(Some(Action::Render))
+            }
+            Action::PrevHunk => {
+                self.prev_hunk();
+                Ok(Some(Action::Render))
+            }
+            Action::Toggle => {
+                self.toggle_line();
+                Ok(Some(Action::Render))
+            }
+            Action::ToggleStaged => {
+                self.toggle_sides();
+                Ok(Some(Action::Render))
+            }
+            Action::Stage if self.staged() == Some(false) => Ok(self.apply(PatchAction::Stage)),
+            Action::Unstage if self.staged() == Some(true) => {
+                Ok(self.apply(PatchAction::Unstage))
+            }
+            Action::Pick if self.staged().is_none() => Ok(self.apply(PatchAction::Copy)),
+            Action::PickFile if self.staged().is_none() => Ok(self.copy_file()),
+            Action::Delete => Ok(self.confirm_discard()),
+            Action::Commit => Ok(self.commit()),
+            Action::Enter => Ok(self.open_hunk()),
+            Action::Escape => Ok(Some(Action::GoBack)),
+            _ => Ok(None),
+        }
+    }
+
+    fn render(&mut self, frame: &mut Frame, area: Rect) {
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(30), Constraint::Percentage(70)])
+            .split(area);
+
+        self.render_files(frame, chunks[0]);
+        self.render_hunks(frame, chunks[1]);
+    }
+
+    fn help_text(&self, keymap: &Keymap) -> String {
+        let hints: &[(&str, &str)] = match self.staged() {
+            Some(false) => &[
+                ("next_hunk", "next hunk"),
+                ("toggle", "mark line"),
+                ("stage", "stage"),
+                ("delete", "discard"),
+                ("commit", "commit"),
+                ("staged", "staged"),
+                ("confirm", "open"),
+                ("back", "back"),
+            ],
+            Some(true) => &[
+                ("next_hunk", "next hunk"),
+                ("toggle", "mark line"),
+                ("unstage", "unstage"),
+                ("commit", "commit"),
+                ("staged", "unstaged"),
+                ("confirm", "open"),
+                ("back", "back"),
+            ],
+            None => &[
+                ("next_hunk", "next hunk"),
+                ("toggle", "mark line"),
+                ("pick", "copy hunk"),
+                ("pick_file", "copy file"),
+                ("staged", "swap"),
+                ("confirm", "open"),
+                ("back", "back"),
+            ],
+        };
+        keymap.help_text(KeyContext::DiffViewer, hints)
+    }
+
+    fn is_typing(&self) -> bool {
+        false
+    }
+}
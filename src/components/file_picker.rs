@@ -1,41 +1,75 @@
-use std::fs;
+use std::fs::File;
+use std::io::Read;
 use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::OnceLock;
+use std::thread;
 
-use ratatui::{layout::Rect, Frame};
+use ignore::WalkBuilder;
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style},
+    text::{Line, Span, Text},
+    widgets::{Block, Borders, Paragraph},
+    Frame,
+};
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
 
 use crate::actions::Action;
 use crate::components::{Component, FuzzyList};
 use crate::error::Result;
+use crate::tui::{KeyContext, Keymap};
+
+/// Paths sent from the walker thread at a time.
+const BATCH_SIZE: usize = 512;
+/// How much of a file the preview reads and highlights.
+const PREVIEW_BYTES: u64 = 64 * 1024;
+const PREVIEW_LINES: usize = 200;
+const PREVIEW_THEME: &str = "base16-ocean.dark";
 
 #[derive(Clone)]
 pub struct FileEntry {
     pub path: PathBuf,
-    pub is_dir: bool,
-    pub name: String,
+    /// Path below the directory the picker was opened in
+    pub relative: String,
 }
 
 impl FileEntry {
+    fn new(root: &Path, path: PathBuf) -> Self {
+        let relative = path.strip_prefix(root).unwrap_or(&path).display().to_string();
+        Self { path, relative }
+    }
+
     pub fn display_name(&self) -> String {
-        if self.is_dir {
-            format!("📁 {}/", self.name)
-        } else {
-            format!("   {}", self.name)
-        }
+        self.relative.clone()
     }
 
     pub fn search_text(&self) -> String {
-        self.name.clone()
+        self.relative.clone()
     }
 }
 
+/// Every file below a directory, found by a background walk that honours `.gitignore`,
+/// with a highlighted preview of the selected one.
 pub struct FilePicker {
     fuzzy_list: FuzzyList<FileEntry>,
-    current_dir: PathBuf,
+    /// Batches from the walker; `None` once it has finished
+    walker: Option<Receiver<Vec<FileEntry>>>,
+    found: usize,
+    preview: Option<Preview>,
+}
+
+struct Preview {
+    path: PathBuf,
+    text: Text<'static>,
 }
 
 impl FilePicker {
     pub fn new(start_path: &Path) -> Self {
-        let current_dir = if start_path.is_dir() {
+        let root = if start_path.is_dir() {
             start_path.to_path_buf()
         } else {
             start_path
@@ -45,71 +79,176 @@ impl FilePicker {
         };
 
         let mut picker = Self {
-            fuzzy_list: FuzzyList::new(
-                "Files",
-                FileEntry::display_name,
-                FileEntry::search_text,
-            ),
-            current_dir,
+            fuzzy_list: FuzzyList::new("Files", FileEntry::display_name, FileEntry::search_text),
+            walker: Some(walk(root)),
+            found: 0,
+            preview: None,
         };
-
-        let _ = picker.refresh();
+        picker.update_title();
         picker
     }
 
-    pub fn refresh(&mut self) -> Result<()> {
-        let mut entries: Vec<FileEntry> = Vec::new();
+    fn update_title(&mut self) {
+        let title = if self.walker.is_some() {
+            format!("Files ({}, scanning)", self.found)
+        } else {
+            format!("Files ({})", self.found)
+        };
+        self.fuzzy_list.set_title(title);
+    }
 
-        // Add parent directory entry
-        if let Some(parent) = self.current_dir.parent() {
-            entries.push(FileEntry {
-                path: parent.to_path_buf(),
-                is_dir: true,
-                name: "..".to_string(),
-            });
-        }
+    /// Move whatever the walker has found so far into the list.
+    fn receive(&mut self) {
+        let Some(ref walker) = self.walker else {
+            return;
+        };
 
-        // Read directory contents
-        if let Ok(read_dir) = fs::read_dir(&self.current_dir) {
-            let mut dir_entries: Vec<FileEntry> = read_dir
-                .filter_map(|entry| {
-                    let entry = entry.ok()?;
-                    let path = entry.path();
-                    let is_dir = path.is_dir();
-                    let name = entry.file_name().to_string_lossy().to_string();
-
-                    // Skip hidden files by default
-                    if name.starts_with('.') {
-                        return None;
-                    }
-
-                    Some(FileEntry { path, is_dir, name })
-                })
-                .collect();
+        let mut entries = Vec::new();
+        let finished = loop {
+            match walker.try_recv() {
+                Ok(batch) => entries.extend(batch),
+                Err(TryRecvError::Empty) => break false,
+                Err(TryRecvError::Disconnected) => break true,
+            }
+        };
 
-            // Sort: directories first, then files, alphabetically
-            dir_entries.sort_by(|a, b| match (a.is_dir, b.is_dir) {
-                (true, false) => std::cmp::Ordering::Less,
-                (false, true) => std::cmp::Ordering::Greater,
-                _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
-            });
+        self.found += entries.len();
+        self.fuzzy_list.extend_items(entries);
+        if finished {
+            self.walker = None;
+        }
+        self.update_title();
+    }
 
-            entries.extend(dir_entries);
+    fn render_preview(&mut self, frame: &mut Frame, area: Rect) {
+        let block = Block::default().borders(Borders::ALL);
+
+        let Some(path) = self.fuzzy_list.selected().map(|entry| entry.path.clone()) else {
+            frame.render_widget(block.title(" Preview "), area);
+            return;
+        };
+        if self.preview.as_ref().is_none_or(|p| p.path != path) {
+            let text = preview_file(&path);
+            self.preview = Some(Preview { path, text });
         }
 
-        self.fuzzy_list.set_items(entries);
-        Ok(())
+        let Some(ref preview) = self.preview else {
+            return;
+        };
+        let title = preview
+            .path
+            .file_name()
+            .map(|name| format!(" {} ", name.to_string_lossy()))
+            .unwrap_or_default();
+        let paragraph = Paragraph::new(preview.text.clone()).block(block.title(title));
+
+        frame.render_widget(paragraph, area);
     }
+}
+
+/// Walk `root` on a background thread, sending the files it finds in batches.
+///
+/// Hidden files are included, but `.git` and anything ignored by git are not. The walk
+/// stops once the receiver is dropped.
+fn walk(root: PathBuf) -> Receiver<Vec<FileEntry>> {
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        let walker = WalkBuilder::new(&root)
+            .hidden(false)
+            .filter_entry(|entry| entry.file_name() != ".git")
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .build();
+
+        let mut batch = Vec::with_capacity(BATCH_SIZE);
+        for entry in walker.flatten() {
+            if !entry.file_type().is_some_and(|t| t.is_file()) {
+                continue;
+            }
+            batch.push(FileEntry::new(&root, entry.into_path()));
+            if batch.len() == BATCH_SIZE {
+                let full = std::mem::replace(&mut batch, Vec::with_capacity(BATCH_SIZE));
+                if sender.send(full).is_err() {
+                    return;
+                }
+            }
+        }
+        let _ = sender.send(batch);
+    });
+
+    receiver
+}
+
+fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn theme() -> &'static Theme {
+    static THEME: OnceLock<Theme> = OnceLock::new();
+    THEME.get_or_init(|| {
+        let mut themes = ThemeSet::load_defaults();
+        themes.themes.remove(PREVIEW_THEME).unwrap_or_default()
+    })
+}
 
-    pub fn current_dir(&self) -> &Path {
-        &self.current_dir
+/// The start of `path`, highlighted by its file name or first line.
+fn preview_file(path: &Path) -> Text<'static> {
+    let mut bytes = Vec::new();
+    let read = File::open(path).and_then(|file| file.take(PREVIEW_BYTES).read_to_end(&mut bytes));
+    if let Err(e) = read {
+        return dim(format!("Could not read file: {}", e));
+    }
+    // The same test git and grep use
+    if bytes.contains(&0) {
+        return dim("Binary file".to_string());
     }
 
-    fn navigate_to(&mut self, path: PathBuf) -> Result<()> {
-        self.current_dir = path;
-        self.fuzzy_list.clear_query();
-        self.refresh()
+    let content = String::from_utf8_lossy(&bytes);
+    let syntaxes = syntax_set();
+    let syntax = find_syntax(syntaxes, path, &content);
+    let mut highlighter = HighlightLines::new(syntax, theme());
+
+    let mut lines = Vec::new();
+    for line in LinesWithEndings::from(&content).take(PREVIEW_LINES) {
+        let spans = match highlighter.highlight_line(line, syntaxes) {
+            Ok(ranges) => ranges
+                .into_iter()
+                .map(|(style, text)| {
+                    let color = style.foreground;
+                    Span::styled(
+                        display_text(text),
+                        Style::default().fg(Color::Rgb(color.r, color.g, color.b)),
+                    )
+                })
+                .collect(),
+            Err(_) => vec![Span::raw(display_text(line))],
+        };
+        lines.push(Line::from(spans));
     }
+
+    Text::from(lines)
+}
+
+fn find_syntax<'a>(syntaxes: &'a SyntaxSet, path: &Path, content: &str) -> &'a SyntaxReference {
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    let extension = path.extension().map(|e| e.to_string_lossy()).unwrap_or_default();
+
+    syntaxes
+        .find_syntax_by_extension(&name)
+        .or_else(|| syntaxes.find_syntax_by_extension(&extension))
+        .or_else(|| syntaxes.find_syntax_by_first_line(content))
+        .unwrap_or_else(|| syntaxes.find_syntax_plain_text())
+}
+
+/// Text as the preview shows it: without the line ending, and with tabs expanded so they
+/// don't leave gaps in the terminal.
+fn display_text(text: &str) -> String {
+    text.trim_end_matches(['\n', '\r']).replace('\t', "    ")
+}
+
+fn dim(message: String) -> Text<'static> {
+    Text::from(Span::styled(message, Style::default().fg(Color::DarkGray)))
 }
 
 impl Component for FilePicker {
@@ -131,27 +270,10 @@ impl Component for FilePicker {
                 self.fuzzy_list.page_down(10);
                 Ok(Some(Action::Render))
             }
-            Action::Character(c) => {
-                self.fuzzy_list.push_char(*c);
-                Ok(Some(Action::Render))
-            }
-            Action::Backspace => {
-                self.fuzzy_list.pop_char();
-                Ok(Some(Action::Render))
-            }
-            Action::Enter => {
-                if let Some(entry) = self.fuzzy_list.selected() {
-                    if entry.is_dir {
-                        let path = entry.path.clone();
-                        self.navigate_to(path)?;
-                        Ok(Some(Action::Render))
-                    } else {
-                        Ok(Some(Action::OpenFile(entry.path.clone())))
-                    }
-                } else {
-                    Ok(None)
-                }
-            }
+            Action::Enter => Ok(self.fuzzy_list.selected().map(|entry| Action::OpenFile {
+                path: entry.path.clone(),
+                position: None,
+            })),
             Action::Escape => {
                 if !self.fuzzy_list.query().is_empty() {
                     self.fuzzy_list.clear_query();
@@ -160,15 +282,36 @@ impl Component for FilePicker {
                     Ok(Some(Action::GoBack))
                 }
             }
-            _ => Ok(None),
+            _ => Ok(self.fuzzy_list.handle_input(action).then_some(Action::Render)),
         }
     }
 
     fn render(&mut self, frame: &mut Frame, area: Rect) {
-        self.fuzzy_list.render(frame, area);
+        // Too narrow for a useful preview
+        if area.width < 80 {
+            self.fuzzy_list.render(frame, area);
+            return;
+        }
+
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+            .split(area);
+
+        self.fuzzy_list.render(frame, chunks[0]);
+        self.render_preview(frame, chunks[1]);
+    }
+
+    fn help_text(&self, keymap: &Keymap) -> String {
+        keymap.help_text(KeyContext::FilePicker, &[("confirm", "open"), ("back", "back")])
+    }
+
+    fn is_typing(&self) -> bool {
+        !self.fuzzy_list.query().is_empty()
     }
 
-    fn help_text(&self) -> &'static str {
-        "Enter:open/navigate  Esc:back"
+    /// Pick up files the walker found since the last tick.
+    fn tick(&mut self) {
+        self.receive();
     }
 }
@@ -10,14 +10,18 @@ use ratatui::{
     Frame,
 };
 
+use crate::actions::Action;
+use crate::components::TextInput;
+
 pub struct FuzzyList<T> {
     items: Vec<T>,
     filtered_indices: Vec<usize>,
-    query: String,
+    query: TextInput,
     list_state: ListState,
     matcher: Matcher,
     title: String,
     display_fn: fn(&T) -> String,
+    line_fn: Option<fn(&T) -> Line<'static>>,
     search_fn: fn(&T) -> String,
 }
 
@@ -33,32 +37,59 @@ impl<T: Clone> FuzzyList<T> {
         Self {
             items: Vec::new(),
             filtered_indices: Vec::new(),
-            query: String::new(),
+            query: TextInput::new(),
             list_state,
             matcher: Matcher::new(Config::DEFAULT),
             title: title.into(),
             display_fn,
+            line_fn: None,
             search_fn,
         }
     }
 
+    /// Render items as styled lines instead of plain `display_fn` text.
+    pub fn with_line_fn(mut self, line_fn: fn(&T) -> Line<'static>) -> Self {
+        self.line_fn = Some(line_fn);
+        self
+    }
+
     pub fn set_items(&mut self, items: Vec<T>) {
         self.items = items;
         self.update_filter();
     }
 
-    pub fn query(&self) -> &str {
-        &self.query
+    /// The items, for changes that leave their search text alone.
+    pub fn items_mut(&mut self) -> &mut [T] {
+        &mut self.items
     }
 
-    pub fn push_char(&mut self, c: char) {
-        self.query.push(c);
+    /// Append items as they arrive, keeping the query and the selection.
+    pub fn extend_items(&mut self, items: Vec<T>) {
+        if items.is_empty() {
+            return;
+        }
+        self.items.extend(items);
         self.update_filter();
     }
 
-    pub fn pop_char(&mut self) {
-        self.query.pop();
-        self.update_filter();
+    pub fn set_title(&mut self, title: impl Into<String>) {
+        self.title = title.into();
+    }
+
+    pub fn query(&self) -> &str {
+        self.query.text()
+    }
+
+    /// Edit the query with a typing or editing action, returning whether it was one.
+    pub fn handle_input(&mut self, action: &Action) -> bool {
+        let before = self.query.text().to_string();
+        if !self.query.handle_action(action) {
+            return false;
+        }
+        if self.query.text() != before {
+            self.update_filter();
+        }
+        true
     }
 
     pub fn clear_query(&mut self) {
@@ -116,16 +147,12 @@ impl<T: Clone> FuzzyList<T> {
         self.items.get(*item_idx)
     }
 
-    pub fn selected_index(&self) -> Option<usize> {
-        let selected_idx = self.list_state.selected()?;
-        self.filtered_indices.get(selected_idx).copied()
-    }
-
     fn update_filter(&mut self) {
         if self.query.is_empty() {
             self.filtered_indices = (0..self.items.len()).collect();
         } else {
-            let pattern = Pattern::parse(&self.query, CaseMatching::Ignore, Normalization::Smart);
+            let pattern =
+                Pattern::parse(self.query.text(), CaseMatching::Ignore, Normalization::Smart);
             let mut scored: Vec<(usize, u32)> = self
                 .items
                 .iter()
@@ -138,13 +165,17 @@ impl<T: Clone> FuzzyList<T> {
                 })
                 .collect();
 
-            scored.sort_by(|a, b| b.1.cmp(&a.1));
+            scored.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
             self.filtered_indices = scored.into_iter().map(|(idx, _)| idx).collect();
         }
 
         if !self.filtered_indices.is_empty() {
-            let current = self.list_state.selected().unwrap_or(0);
-            if current >= self.filtered_indices.len() {
+            // Also reselect after a filter that matched nothing
+            let in_range = self
+                .list_state
+                .selected()
+                .is_some_and(|current| current < self.filtered_indices.len());
+            if !in_range {
                 self.list_state.select(Some(0));
             }
         } else {
@@ -163,14 +194,15 @@ impl<T: Clone> FuzzyList<T> {
             .borders(Borders::ALL)
             .title(format!(" {} ", self.title));
 
-        let input = Paragraph::new(Line::from(vec![
-            Span::styled("> ", Style::default().fg(Color::Yellow)),
-            Span::raw(&self.query),
-            Span::styled("│", Style::default().fg(Color::Gray)),
-        ]))
-        .block(input_block);
+        let input = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Length(2), Constraint::Min(1)])
+            .split(input_block.inner(chunks[0]));
+        frame.render_widget(input_block, chunks[0]);
 
-        frame.render_widget(input, chunks[0]);
+        let prompt = Span::styled("> ", Style::default().fg(Color::Yellow));
+        frame.render_widget(Paragraph::new(prompt), input[0]);
+        self.query.render(frame, input[1]);
 
         // List
         let items: Vec<ListItem> = self
@@ -178,8 +210,10 @@ impl<T: Clone> FuzzyList<T> {
             .iter()
             .map(|&idx| {
                 let item = &self.items[idx];
-                let display = (self.display_fn)(item);
-                ListItem::new(display)
+                match self.line_fn {
+                    Some(line_fn) => ListItem::new(line_fn(item)),
+                    None => ListItem::new((self.display_fn)(item)),
+                }
             })
             .collect();
 
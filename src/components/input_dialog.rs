@@ -1,61 +1,102 @@
 use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Style},
-    text::{Line, Span},
+    text::Span,
     widgets::{Block, Borders, Clear, Paragraph},
     Frame,
 };
 
 use crate::actions::{Action, InputCallback};
+use crate::components::TextInput;
 use crate::error::Result;
+use crate::models::MergeStrategy;
+
+/// Lines of text a multi-line dialog shows at once.
+const MULTILINE_HEIGHT: u16 = 6;
 
 pub struct InputDialog {
     title: String,
-    input: String,
+    input: TextInput,
     callback: InputCallback,
 }
 
 impl InputDialog {
     pub fn new(title: impl Into<String>, callback: InputCallback) -> Self {
+        let input = if callback.is_multiline() {
+            TextInput::new().multiline()
+        } else {
+            TextInput::new()
+        };
         Self {
             title: title.into(),
-            input: String::new(),
+            input,
             callback,
         }
     }
 
+    /// Start with `value` already entered.
+    pub fn with_value(mut self, value: impl Into<String>) -> Self {
+        self.input.set_text(value);
+        self
+    }
+
+    /// Earlier entries to recall with up and down, oldest first.
+    pub fn with_history(mut self, history: Vec<String>) -> Self {
+        self.input = self.input.with_history(history);
+        self
+    }
+
+    pub fn value(&self) -> &str {
+        self.input.text()
+    }
+
+    pub fn history_key(&self) -> &'static str {
+        self.callback.history_key()
+    }
+
+    pub fn is_typing(&self) -> bool {
+        !self.input.is_empty()
+    }
+
     pub fn handle_action(&mut self, action: &Action) -> Result<Option<Action>> {
         match action {
-            Action::Character(c) => {
-                self.input.push(*c);
-                Ok(Some(Action::Render))
-            }
-            Action::Backspace => {
-                self.input.pop();
-                Ok(Some(Action::Render))
-            }
             Action::Enter => {
                 if self.input.is_empty() {
                     return Ok(Some(Action::CloseDialog));
                 }
+                let value = self.input.text().to_string();
                 let result = match &self.callback {
-                    InputCallback::CreateSession => {
-                        Action::CreateSession(self.input.clone(), None)
-                    }
-                    InputCallback::CreateWorktree => {
-                        Action::CreateWorktree(self.input.clone())
-                    }
+                    InputCallback::CreateSession => Action::NewSession(value),
+                    InputCallback::CreateWorktree => Action::CreateWorktree(value),
+                    InputCallback::LaunchAgent(profile) => Action::LaunchAgent {
+                        profile: profile.clone(),
+                        branch: value,
+                    },
+                    InputCallback::SquashMessage(path) => Action::MergeWorktree {
+                        path: path.clone(),
+                        strategy: MergeStrategy::Squash,
+                        message: Some(value),
+                    },
+                    InputCallback::CommitMessage(root) => Action::CommitStaged {
+                        root: root.clone(),
+                        message: value,
+                    },
                 };
                 Ok(Some(result))
             }
             Action::Escape => Ok(Some(Action::CloseDialog)),
-            _ => Ok(None),
+            _ => Ok(self.input.handle_action(action).then_some(Action::Render)),
         }
     }
 
     pub fn render(&self, frame: &mut Frame, area: Rect) {
-        let dialog_width = 50.min(area.width.saturating_sub(4));
-        let dialog_height = 5;
+        let (width, input_height, hint) = if self.input.is_multiline() {
+            (70, MULTILINE_HEIGHT, "Enter: confirm  M-Enter: newline  Esc: cancel")
+        } else {
+            (50, 1, "Enter: confirm  Esc: cancel")
+        };
+        let dialog_width = width.min(area.width.saturating_sub(4));
+        let dialog_height = input_height + 5;
 
         let dialog_area = centered_rect(dialog_width, dialog_height, area);
 
@@ -71,20 +112,19 @@ impl InputDialog {
 
         let chunks = Layout::default()
             .direction(Direction::Vertical)
-            .constraints([Constraint::Length(1), Constraint::Length(1)])
+            .constraints([Constraint::Length(input_height), Constraint::Length(1)])
             .margin(1)
             .split(inner);
 
-        let input_line = Line::from(vec![
-            Span::styled("> ", Style::default().fg(Color::Yellow)),
-            Span::raw(&self.input),
-            Span::styled("│", Style::default().fg(Color::Gray)),
-        ]);
-
-        let input = Paragraph::new(input_line);
-        frame.render_widget(input, chunks[0]);
+        let input = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Length(2), Constraint::Min(1)])
+            .split(chunks[0]);
+        let prompt = Span::styled("> ", Style::default().fg(Color::Yellow));
+        frame.render_widget(Paragraph::new(prompt), input[0]);
+        self.input.render(frame, input[1]);
 
-        let hint = Paragraph::new("Enter: confirm  Esc: cancel")
+        let hint = Paragraph::new(hint)
             .style(Style::default().fg(Color::DarkGray))
             .alignment(Alignment::Center);
         frame.render_widget(hint, chunks[1]);
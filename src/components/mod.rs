@@ -1,21 +1,29 @@
 mod buffer_picker;
 mod command_palette;
+mod config_viewer;
 mod confirm_dialog;
+mod diff_viewer;
 mod file_picker;
 mod fuzzy_list;
 mod help_bar;
 mod input_dialog;
+mod select_dialog;
 mod session_picker;
+mod text_input;
 mod traits;
 mod worktree_picker;
 
 pub use buffer_picker::BufferPicker;
 pub use command_palette::CommandPalette;
+pub use config_viewer::ConfigViewer;
 pub use confirm_dialog::ConfirmDialog;
+pub use diff_viewer::DiffViewer;
 pub use file_picker::FilePicker;
 pub use fuzzy_list::FuzzyList;
 pub use help_bar::HelpBar;
 pub use input_dialog::InputDialog;
+pub use select_dialog::SelectDialog;
 pub use session_picker::SessionPicker;
+pub use text_input::TextInput;
 pub use traits::Component;
 pub use worktree_picker::WorktreePicker;
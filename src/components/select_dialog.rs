@@ -0,0 +1,254 @@
+use nucleo_matcher::{
+    pattern::{CaseMatching, Normalization, Pattern},
+    Config, Matcher, Utf32Str,
+};
+use ratatui::{
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph},
+    Frame,
+};
+
+use crate::actions::{Action, InputCallback, SelectCallback};
+use crate::error::Result;
+use crate::models::MergeStrategy;
+
+pub struct SelectDialog {
+    title: String,
+    options: Vec<String>,
+    empty_message: String,
+    callback: SelectCallback,
+    list_state: ListState,
+    /// Filter typed by the user; `None` if the dialog is not searchable
+    query: Option<String>,
+    /// Indices into `options` that match the query, best match first
+    visible: Vec<usize>,
+}
+
+impl SelectDialog {
+    pub fn new(
+        title: impl Into<String>,
+        options: Vec<String>,
+        empty_message: impl Into<String>,
+        callback: SelectCallback,
+    ) -> Self {
+        let mut list_state = ListState::default();
+        if !options.is_empty() {
+            list_state.select(Some(0));
+        }
+
+        Self {
+            title: title.into(),
+            visible: (0..options.len()).collect(),
+            options,
+            empty_message: empty_message.into(),
+            callback,
+            list_state,
+            query: None,
+        }
+    }
+
+    /// Preselect the option at `index`.
+    pub fn with_selected(mut self, index: usize) -> Self {
+        if index < self.options.len() {
+            self.list_state.select(Some(index));
+        }
+        self
+    }
+
+    /// Let the user narrow long option lists by typing.
+    pub fn with_search(mut self) -> Self {
+        self.query = Some(String::new());
+        self
+    }
+
+    pub fn is_typing(&self) -> bool {
+        self.query.as_deref().is_some_and(|q| !q.is_empty())
+    }
+
+    fn update_filter(&mut self) {
+        let query = self.query.as_deref().unwrap_or_default();
+        if query.is_empty() {
+            self.visible = (0..self.options.len()).collect();
+        } else {
+            let pattern = Pattern::parse(query, CaseMatching::Ignore, Normalization::Smart);
+            let mut matcher = Matcher::new(Config::DEFAULT);
+            let mut buf = Vec::new();
+            let mut scored: Vec<(usize, u32)> = self
+                .options
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, option)| {
+                    let score = pattern.score(Utf32Str::new(option, &mut buf), &mut matcher)?;
+                    Some((idx, score))
+                })
+                .collect();
+            scored.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+            self.visible = scored.into_iter().map(|(idx, _)| idx).collect();
+        }
+
+        let selected = if self.visible.is_empty() { None } else { Some(0) };
+        self.list_state.select(selected);
+    }
+
+    pub fn handle_action(&mut self, action: &Action) -> Result<Option<Action>> {
+        match action {
+            Action::MoveUp => {
+                if let Some(current) = self.list_state.selected() {
+                    let new = current.checked_sub(1).unwrap_or(self.visible.len() - 1);
+                    self.list_state.select(Some(new));
+                }
+                Ok(Some(Action::Render))
+            }
+            Action::MoveDown => {
+                if let Some(current) = self.list_state.selected() {
+                    self.list_state.select(Some((current + 1) % self.visible.len()));
+                }
+                Ok(Some(Action::Render))
+            }
+            Action::Enter => {
+                let selected = self
+                    .list_state
+                    .selected()
+                    .and_then(|row| self.visible.get(row))
+                    .map(|&idx| (idx, &self.options[idx]));
+
+                match selected {
+                    Some((index, option)) => Ok(Some(self.confirm(index, option.clone()))),
+                    None => Ok(Some(Action::CloseDialog)),
+                }
+            }
+            Action::Character(c) => match self.query {
+                Some(ref mut query) => {
+                    query.push(*c);
+                    self.update_filter();
+                    Ok(Some(Action::Render))
+                }
+                None => Ok(None),
+            },
+            Action::Backspace => match self.query {
+                Some(ref mut query) => {
+                    query.pop();
+                    self.update_filter();
+                    Ok(Some(Action::Render))
+                }
+                None => Ok(None),
+            },
+            Action::Escape => Ok(Some(Action::CloseDialog)),
+            _ => Ok(None),
+        }
+    }
+
+    fn confirm(&self, index: usize, option: String) -> Action {
+        match &self.callback {
+            SelectCallback::SessionLayout(name) if index == 0 => {
+                Action::CreateSession(name.clone(), None)
+            }
+            SelectCallback::SessionLayout(name) => Action::CreateLayoutSession {
+                name: name.clone(),
+                layout: option,
+            },
+            SelectCallback::LaunchAgent => Action::ShowInput {
+                title: format!("Worktree Branch for {}", option),
+                callback: InputCallback::LaunchAgent(option),
+            },
+            SelectCallback::NewWorktree if index == 0 => Action::ShowInput {
+                title: "New Worktree Branch".to_string(),
+                callback: InputCallback::CreateWorktree,
+            },
+            SelectCallback::NewWorktree => Action::ShowExistingBranches,
+            SelectCallback::WorktreeBase(branch) => Action::CreateWorktreeFrom {
+                branch: branch.clone(),
+                base: option,
+                track: None,
+            },
+            SelectCallback::WorktreeTracking { branch, base } => Action::CreateWorktreeFrom {
+                branch: branch.clone(),
+                base: base.clone(),
+                track: Some(index == 0),
+            },
+            SelectCallback::ExistingBranch => Action::CheckoutWorktreeBranch(option),
+            SelectCallback::MergeStrategy(path) => Action::MergeWorktree {
+                path: path.clone(),
+                strategy: MergeStrategy::ALL[index],
+                message: None,
+            },
+            SelectCallback::OpenConflicts(path) => {
+                let files = if index == 0 {
+                    self.options[1..].to_vec()
+                } else {
+                    vec![option]
+                };
+                Action::OpenConflicts {
+                    path: path.clone(),
+                    files,
+                }
+            }
+        }
+    }
+
+    pub fn render(&mut self, frame: &mut Frame, area: Rect) {
+        let dialog_width = 50.min(area.width.saturating_sub(4));
+        let list_height = self.options.len().clamp(1, 10) as u16;
+        let search_height = if self.query.is_some() { 1 } else { 0 };
+        let dialog_height = list_height + search_height + 4;
+
+        let dialog_area = centered_rect(dialog_width, dialog_height, area);
+
+        frame.render_widget(Clear, dialog_area);
+
+        let block = Block::default()
+            .title(format!(" {} ", self.title))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Cyan));
+
+        let inner = block.inner(dialog_area);
+        frame.render_widget(block, dialog_area);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(search_height), Constraint::Min(1)])
+            .margin(1)
+            .split(inner);
+
+        if let Some(ref query) = self.query {
+            let input = Paragraph::new(Line::from(vec![
+                Span::styled("> ", Style::default().fg(Color::Yellow)),
+                Span::raw(query.as_str()),
+                Span::styled("│", Style::default().fg(Color::Gray)),
+            ]));
+            frame.render_widget(input, chunks[0]);
+        }
+
+        if self.options.is_empty() {
+            let message = Paragraph::new(self.empty_message.as_str())
+                .style(Style::default().fg(Color::DarkGray))
+                .alignment(Alignment::Center);
+            frame.render_widget(message, chunks[1]);
+            return;
+        }
+
+        let items: Vec<ListItem> = self
+            .visible
+            .iter()
+            .map(|&idx| ListItem::new(self.options[idx].as_str()))
+            .collect();
+
+        let list = List::new(items)
+            .highlight_style(
+                Style::default()
+                    .bg(Color::Blue)
+                    .add_modifier(Modifier::BOLD),
+            )
+            .highlight_symbol("▶ ");
+
+        frame.render_stateful_widget(list, chunks[1], &mut self.list_state);
+    }
+}
+
+fn centered_rect(width: u16, height: u16, area: Rect) -> Rect {
+    let x = area.x + (area.width.saturating_sub(width)) / 2;
+    let y = area.y + (area.height.saturating_sub(height)) / 2;
+    Rect::new(x, y, width, height)
+}
@@ -1,41 +1,92 @@
-use ratatui::{layout::Rect, Frame};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use ansi_to_tui::IntoText;
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    text::Text,
+    widgets::{Block, Borders, Paragraph},
+    Frame,
+};
 
 use crate::actions::{Action, ConfirmCallback, InputCallback};
 use crate::components::{Component, FuzzyList};
+use crate::config::{Config, PreviewConfig};
 use crate::error::Result;
-use crate::integrations::TmuxClient;
+use crate::integrations::{ActivityMonitor, TmuxBackend};
 use crate::models::TmuxSession;
+use crate::tui::{KeyContext, Keymap};
 
 pub struct SessionPicker {
     fuzzy_list: FuzzyList<TmuxSession>,
-    tmux: TmuxClient,
+    tmux: Rc<dyn TmuxBackend>,
+    monitor: ActivityMonitor,
+    status_refresh: Duration,
+    classified_at: Instant,
+    sort_by_attention: bool,
+    preview_config: PreviewConfig,
+    preview: Option<Preview>,
+}
+
+/// Captured content of a session's active pane.
+struct Preview {
+    session: String,
+    text: Text<'static>,
+    captured_at: Instant,
 }
 
 impl SessionPicker {
-    pub fn new() -> Self {
+    pub fn new(tmux: Rc<dyn TmuxBackend>, config: &Config) -> Self {
         Self {
             fuzzy_list: FuzzyList::new(
                 "Sessions",
                 TmuxSession::display_name,
                 TmuxSession::search_text,
-            ),
-            tmux: TmuxClient::new(),
+            )
+            .with_line_fn(TmuxSession::display_line),
+            monitor: ActivityMonitor::new(tmux.clone(), config),
+            status_refresh: Duration::from_millis(config.status.refresh_ms),
+            classified_at: Instant::now(),
+            tmux,
+            sort_by_attention: false,
+            preview_config: config.preview.clone(),
+            preview: None,
         }
     }
 
+    fn render_preview(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::default().borders(Borders::ALL);
+
+        let Some(ref preview) = self.preview else {
+            frame.render_widget(block.title(" Preview "), area);
+            return;
+        };
+
+        // Keep the bottom of the pane in view, where agents print their latest output
+        let inner_height = block.inner(area).height;
+        let scroll = (preview.text.lines.len() as u16).saturating_sub(inner_height);
+        let paragraph = Paragraph::new(preview.text.clone())
+            .block(block.title(format!(" {} ", preview.session)))
+            .scroll((scroll, 0));
+
+        frame.render_widget(paragraph, area);
+    }
+
     pub fn refresh(&mut self) -> Result<()> {
-        let sessions = self.tmux.list_sessions()?;
+        let mut sessions = self.tmux.list_sessions()?;
+        // Status is best effort; sessions are still listed without it
+        let _ = self.monitor.classify(&mut sessions);
+        self.classified_at = Instant::now();
+
+        if self.sort_by_attention {
+            sessions.sort_by_key(|s| s.status.attention_rank());
+        }
+
         self.fuzzy_list.set_items(sessions);
         Ok(())
     }
 }
 
-impl Default for SessionPicker {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
 impl Component for SessionPicker {
     fn handle_action(&mut self, action: &Action) -> Result<Option<Action>> {
         match action {
@@ -55,36 +106,25 @@ impl Component for SessionPicker {
                 self.fuzzy_list.page_down(10);
                 Ok(Some(Action::Render))
             }
-            Action::Character(c) => {
-                match c {
-                    'd' if self.fuzzy_list.query().is_empty() => {
-                        // Delete session
-                        if let Some(session) = self.fuzzy_list.selected() {
-                            return Ok(Some(Action::ShowConfirm {
-                                title: "Delete Session".to_string(),
-                                message: format!("Delete session '{}'?", session.name),
-                                callback: ConfirmCallback::KillSession(session.name.clone()),
-                            }));
-                        }
-                        Ok(None)
-                    }
-                    'n' if self.fuzzy_list.query().is_empty() => {
-                        // New session
-                        Ok(Some(Action::ShowInput {
-                            title: "New Session".to_string(),
-                            callback: InputCallback::CreateSession,
-                        }))
-                    }
-                    _ => {
-                        self.fuzzy_list.push_char(*c);
-                        Ok(Some(Action::Render))
-                    }
+            Action::Delete => {
+                if let Some(session) = self.fuzzy_list.selected() {
+                    return Ok(Some(Action::ShowConfirm {
+                        title: "Delete Session".to_string(),
+                        message: format!("Delete session '{}'?", session.name),
+                        callback: ConfirmCallback::KillSession(session.name.clone()),
+                    }));
                 }
+                Ok(None)
             }
-            Action::Backspace => {
-                self.fuzzy_list.pop_char();
+            Action::ToggleSort => {
+                self.sort_by_attention = !self.sort_by_attention;
+                self.refresh()?;
                 Ok(Some(Action::Render))
             }
+            Action::New => Ok(Some(Action::ShowInput {
+                title: "New Session".to_string(),
+                callback: InputCallback::CreateSession,
+            })),
             Action::Enter => {
                 if let Some(session) = self.fuzzy_list.selected() {
                     Ok(Some(Action::SwitchSession(session.name.clone())))
@@ -100,15 +140,94 @@ impl Component for SessionPicker {
                     Ok(Some(Action::GoBack))
                 }
             }
-            _ => Ok(None),
+            _ => Ok(self.fuzzy_list.handle_input(action).then_some(Action::Render)),
         }
     }
 
     fn render(&mut self, frame: &mut Frame, area: Rect) {
-        self.fuzzy_list.render(frame, area);
+        // Too narrow for a useful preview
+        if !self.preview_config.enabled || area.width < 80 {
+            self.fuzzy_list.render(frame, area);
+            return;
+        }
+
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+            .split(area);
+
+        self.fuzzy_list.render(frame, chunks[0]);
+        self.render_preview(frame, chunks[1]);
+    }
+
+    fn help_text(&self, keymap: &Keymap) -> String {
+        keymap.help_text(
+            KeyContext::SessionPicker,
+            &[
+                ("confirm", "switch"),
+                ("new", "new"),
+                ("delete", "delete"),
+                ("sort", if self.sort_by_attention { "unsort" } else { "sort" }),
+                ("back", "back"),
+            ],
+        )
+    }
+
+    fn is_typing(&self) -> bool {
+        !self.fuzzy_list.query().is_empty()
+    }
+
+    /// Re-check statuses and re-capture the preview when they are stale, or the
+    /// preview when the selection changed.
+    fn tick(&mut self) {
+        // In place, so the list does not reorder under the cursor; `sort` re-sorts
+        if self.classified_at.elapsed() >= self.status_refresh {
+            let _ = self.monitor.classify(self.fuzzy_list.items_mut());
+            self.classified_at = Instant::now();
+        }
+
+        if !self.preview_config.enabled {
+            return;
+        }
+
+        let Some(session) = self.fuzzy_list.selected().map(|s| s.name.clone()) else {
+            self.preview = None;
+            return;
+        };
+
+        let refresh = Duration::from_millis(self.preview_config.refresh_ms);
+        let fresh = self
+            .preview
+            .as_ref()
+            .is_some_and(|p| p.session == session && p.captured_at.elapsed() < refresh);
+        if fresh {
+            return;
+        }
+
+        // The session's current window and its active pane
+        let target = format!("={}:", session);
+        let text = self
+            .tmux
+            .capture_pane_ansi(&target, self.preview_config.lines)
+            .ok()
+            .and_then(|bytes| bytes.into_text().ok())
+            .unwrap_or_default();
+
+        self.preview = Some(Preview {
+            session,
+            text: trim_trailing_blank_lines(text),
+            captured_at: Instant::now(),
+        });
     }
+}
 
-    fn help_text(&self) -> &'static str {
-        "Enter:switch  n:new  d:delete  Esc:back"
+fn trim_trailing_blank_lines(mut text: Text<'static>) -> Text<'static> {
+    while text
+        .lines
+        .last()
+        .is_some_and(|line| line.spans.iter().all(|span| span.content.trim().is_empty()))
+    {
+        text.lines.pop();
     }
+    text
 }
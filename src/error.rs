@@ -8,6 +8,9 @@ pub enum PmanError {
     #[error("Tmux error: {0}")]
     Tmux(String),
 
+    #[error("Config error: {0}")]
+    Config(String),
+
     #[error("Git error: {0}")]
     Git(String),
 
@@ -17,17 +20,37 @@ pub enum PmanError {
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
-    #[error("Terminal error: {0}")]
-    Terminal(String),
+    /// Arguments that parse but do not make sense together
+    #[error("{0}")]
+    Usage(String),
 
     #[error("Not in a git repository")]
     NotGitRepo,
 
+    #[error("Merge conflicts in: {}", files.join(", "))]
+    MergeConflict { files: Vec<String> },
+
     #[error("Worktree has uncommitted changes")]
     UncommittedChanges,
+}
 
-    #[error("Operation cancelled")]
-    Cancelled,
+impl PmanError {
+    /// Process exit code for scripts, distinct for each kind of error. Codes start at 10
+    /// to stay clear of 1 (any error) and 2 (usage errors, as from argument parsing).
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::Usage(_) => 2,
+            Self::MissingPrerequisite(_) => 10,
+            Self::Tmux(_) => 11,
+            Self::Config(_) => 12,
+            Self::Git(_) => 13,
+            Self::Nvim(_) => 14,
+            Self::Io(_) => 15,
+            Self::NotGitRepo => 17,
+            Self::MergeConflict { .. } => 18,
+            Self::UncommittedChanges => 19,
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, PmanError>;
@@ -0,0 +1,375 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use regex::{Regex, RegexBuilder};
+
+use crate::config::{Config, DetectorConfig};
+use crate::error::Result;
+use crate::integrations::{TmuxBackend, SETUP_WINDOW};
+use crate::models::{AgentStatus, TmuxPane, TmuxSession};
+
+/// Programs that mean nothing is running in a pane.
+const SHELLS: &[&str] = &["bash", "zsh", "fish", "sh", "dash", "ksh", "tcsh", "nu"];
+
+struct Detector {
+    name: String,
+    commands: Vec<String>,
+    waiting: Vec<Regex>,
+    busy: Vec<Regex>,
+}
+
+impl Detector {
+    fn new(name: &str, config: &DetectorConfig) -> Self {
+        // Patterns are validated when the config is loaded
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .filter_map(|p| RegexBuilder::new(p).multi_line(true).build().ok())
+                .collect()
+        };
+
+        Self {
+            name: name.to_string(),
+            commands: config.commands.clone(),
+            waiting: compile(&config.waiting),
+            busy: compile(&config.busy),
+        }
+    }
+}
+
+/// What a pane showed when it was last captured.
+struct PaneSample {
+    /// The window's activity at the time; the content can't have changed while it stays
+    window_activity: u64,
+    content: String,
+    /// Unix time the content was last seen to change, 0 if it hasn't been yet
+    changed_at: u64,
+}
+
+/// Samples tmux panes and classifies what the agent in each session is doing.
+///
+/// tmux only tracks activity per window, so a pane that shares its window is judged by
+/// when its own content last changed. Panes are captured again only once their window
+/// has had output, which keeps quiet sessions free to poll.
+pub struct ActivityMonitor {
+    tmux: Rc<dyn TmuxBackend>,
+    detectors: Vec<Detector>,
+    idle_after_secs: u64,
+    scan_lines: usize,
+    samples: RefCell<HashMap<String, PaneSample>>,
+}
+
+impl ActivityMonitor {
+    pub fn new(tmux: Rc<dyn TmuxBackend>, config: &Config) -> Self {
+        let detectors = config
+            .status
+            .detectors
+            .iter()
+            .map(|(name, detector)| Detector::new(name, detector))
+            .collect();
+
+        Self {
+            tmux,
+            detectors,
+            idle_after_secs: config.status.idle_after_secs,
+            scan_lines: config.status.scan_lines,
+            samples: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Fill in `status` for every session.
+    pub fn classify(&self, sessions: &mut [TmuxSession]) -> Result<()> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        self.classify_at(sessions, now)
+    }
+
+    fn classify_at(&self, sessions: &mut [TmuxSession], now: u64) -> Result<()> {
+        let panes = self.tmux.list_panes()?;
+
+        let mut window_panes: HashMap<&str, usize> = HashMap::new();
+        for pane in &panes {
+            *window_panes.entry(pane.window_id.as_str()).or_default() += 1;
+        }
+        let split_windows: HashSet<&str> = window_panes
+            .into_iter()
+            .filter(|&(_, count)| count > 1)
+            .map(|(window, _)| window)
+            .collect();
+
+        let mut panes_by_session: HashMap<&str, Vec<&TmuxPane>> = HashMap::new();
+        for pane in &panes {
+            // A finished setup window is not an agent that exited
+            if pane.window_name == SETUP_WINDOW {
+                continue;
+            }
+            panes_by_session
+                .entry(pane.session.as_str())
+                .or_default()
+                .push(pane);
+        }
+
+        for session in sessions {
+            let panes = panes_by_session
+                .get(session.name.as_str())
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            session.status = self.classify_session(session, panes, &split_windows, now);
+        }
+
+        // Forget panes that have closed
+        let open: HashSet<&str> = panes.iter().map(|p| p.pane_id.as_str()).collect();
+        self.samples
+            .borrow_mut()
+            .retain(|pane_id, _| open.contains(pane_id.as_str()));
+
+        Ok(())
+    }
+
+    fn classify_session(
+        &self,
+        session: &TmuxSession,
+        panes: &[&TmuxPane],
+        split_windows: &HashSet<&str>,
+        now: u64,
+    ) -> AgentStatus {
+        if panes.is_empty() {
+            return AgentStatus::Unknown;
+        }
+
+        let profile_detector = session
+            .agent
+            .as_deref()
+            .and_then(|agent| self.detectors.iter().find(|d| d.name == agent));
+
+        // Sessions launched from a profile run the agent in a window named after it
+        if let Some(agent) = session.agent.as_deref() {
+            let agent_panes: Vec<&&TmuxPane> =
+                panes.iter().filter(|p| p.window_name == agent).collect();
+            let gone = agent_panes
+                .iter()
+                .all(|p| p.dead || SHELLS.contains(&p.current_command.as_str()));
+            if gone {
+                return AgentStatus::Exited;
+            }
+        }
+
+        let statuses: Vec<AgentStatus> = panes
+            .iter()
+            .map(|pane| {
+                let detector = if session.agent.as_deref() == Some(pane.window_name.as_str()) {
+                    profile_detector
+                } else {
+                    self.detectors
+                        .iter()
+                        .find(|d| d.commands.contains(&pane.current_command))
+                };
+                let split = split_windows.contains(pane.window_id.as_str());
+                self.classify_pane(pane, detector, split, now)
+            })
+            .collect();
+
+        [
+            AgentStatus::Waiting,
+            AgentStatus::Exited,
+            AgentStatus::Busy,
+            AgentStatus::Idle,
+        ]
+        .into_iter()
+        .find(|status| statuses.contains(status))
+        .unwrap_or(AgentStatus::Unknown)
+    }
+
+    /// `split` is whether the pane shares its window, and so its window's activity.
+    fn classify_pane(
+        &self,
+        pane: &TmuxPane,
+        detector: Option<&Detector>,
+        split: bool,
+        now: u64,
+    ) -> AgentStatus {
+        if pane.dead {
+            return AgentStatus::Exited;
+        }
+        let running = !SHELLS.contains(&pane.current_command.as_str());
+        if detector.is_none() && !running {
+            return AgentStatus::Idle;
+        }
+
+        let sample = if detector.is_some() || split {
+            let Some(sample) = self.sample(pane) else {
+                return AgentStatus::Unknown;
+            };
+            Some(sample)
+        } else {
+            None
+        };
+        let activity = match sample {
+            Some((_, changed_at)) if split => changed_at,
+            _ => pane.window_activity,
+        };
+        let recently_active = now.saturating_sub(activity) < self.idle_after_secs;
+
+        let (Some(detector), Some((content, _))) = (detector, sample) else {
+            return if recently_active {
+                AgentStatus::Busy
+            } else {
+                AgentStatus::Idle
+            };
+        };
+
+        // Panes are padded with blank lines below the cursor
+        let lines: Vec<&str> = content.trim_end().lines().collect();
+        let tail = lines[lines.len().saturating_sub(self.scan_lines)..].join("\n");
+
+        if detector.waiting.iter().any(|re| re.is_match(&tail)) {
+            AgentStatus::Waiting
+        } else if detector.busy.iter().any(|re| re.is_match(&tail)) || recently_active {
+            AgentStatus::Busy
+        } else {
+            AgentStatus::Idle
+        }
+    }
+
+    /// The pane's content and when it last changed. The pane is only captured again if
+    /// its window has had output since the last time.
+    fn sample(&self, pane: &TmuxPane) -> Option<(String, u64)> {
+        let mut samples = self.samples.borrow_mut();
+        let previous = samples.get(&pane.pane_id);
+        if let Some(sample) = previous.filter(|s| s.window_activity == pane.window_activity) {
+            return Some((sample.content.clone(), sample.changed_at));
+        }
+
+        let content = self.tmux.capture_pane(&pane.pane_id, self.scan_lines).ok()?;
+        let changed_at = match previous {
+            Some(sample) if sample.content == content => sample.changed_at,
+            // Changed some time since the last capture, most likely with the latest output
+            Some(_) => pane.window_activity,
+            // Nothing to compare with yet
+            None => 0,
+        };
+
+        samples.insert(
+            pane.pane_id.clone(),
+            PaneSample {
+                window_activity: pane.window_activity,
+                content: content.clone(),
+                changed_at,
+            },
+        );
+        Some((content, changed_at))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integrations::fake::FakeTmux;
+
+    const NOW: u64 = 1_000_000;
+    const LONG_AGO: u64 = NOW - 600;
+
+    fn pane(session: &str, window: &str, id: &str, command: &str, activity: u64) -> TmuxPane {
+        TmuxPane {
+            session: session.to_string(),
+            window_name: command.to_string(),
+            window_id: window.to_string(),
+            pane_id: id.to_string(),
+            pid: 1,
+            current_command: command.to_string(),
+            dead: false,
+            dead_status: None,
+            window_activity: activity,
+        }
+    }
+
+    /// The status of each session in `tmux`, in order.
+    fn statuses(monitor: &ActivityMonitor, tmux: &FakeTmux, now: u64) -> Vec<&'static str> {
+        let mut sessions = tmux.list_sessions().unwrap();
+        monitor.classify_at(&mut sessions, now).unwrap();
+        sessions.iter().map(|s| s.status.label()).collect()
+    }
+
+    fn show(tmux: &FakeTmux, pane_id: &str, content: &str) {
+        tmux.contents
+            .borrow_mut()
+            .insert(pane_id.to_string(), content.to_string());
+    }
+
+    #[test]
+    fn detectors_tell_waiting_busy_and_idle_agents_apart() {
+        let tmux = Rc::new(FakeTmux::with_sessions(&["ask", "work", "rest", "print"]));
+        tmux.panes.borrow_mut().extend([
+            pane("ask", "@1", "%1", "claude", LONG_AGO),
+            pane("work", "@2", "%2", "claude", LONG_AGO),
+            pane("rest", "@3", "%3", "claude", LONG_AGO),
+            pane("print", "@4", "%4", "claude", NOW - 2),
+        ]);
+        show(&tmux, "%1", "Edit main.rs?\nDo you want to make this edit?\n❯ 1. Yes\n\n\n");
+        show(&tmux, "%2", "✻ Thinking… (12s · esc to interrupt)\n");
+        show(&tmux, "%3", "Done.\n> \n");
+        show(&tmux, "%4", "Done.\n> \n");
+        let monitor = ActivityMonitor::new(tmux.clone(), &Config::default());
+
+        // Recent output counts as busy even with no busy pattern on screen
+        assert_eq!(statuses(&monitor, &tmux, NOW), ["waiting", "busy", "idle", "busy"]);
+    }
+
+    #[test]
+    fn other_programs_are_busy_while_they_print() {
+        let tmux = Rc::new(FakeTmux::with_sessions(&["build", "done", "shell"]));
+        tmux.panes.borrow_mut().extend([
+            pane("build", "@1", "%1", "cargo", NOW - 2),
+            pane("done", "@2", "%2", "cargo", LONG_AGO),
+            pane("shell", "@3", "%3", "zsh", NOW - 2),
+        ]);
+        let monitor = ActivityMonitor::new(tmux.clone(), &Config::default());
+
+        assert_eq!(statuses(&monitor, &tmux, NOW), ["busy", "idle", "idle"]);
+    }
+
+    #[test]
+    fn split_panes_are_judged_by_their_own_output() {
+        let tmux = Rc::new(FakeTmux::with_sessions(&["api"]));
+        tmux.panes.borrow_mut().extend([
+            pane("api", "@1", "%1", "claude", NOW),
+            pane("api", "@1", "%2", "zsh", NOW),
+        ]);
+        show(&tmux, "%1", "> \n");
+        let monitor = ActivityMonitor::new(tmux.clone(), &Config::default());
+
+        // Typing in the shell beside the agent moves the window's activity only
+        assert_eq!(statuses(&monitor, &tmux, NOW), ["idle"]);
+        for pane in tmux.panes.borrow_mut().iter_mut() {
+            pane.window_activity = NOW + 5;
+        }
+        assert_eq!(statuses(&monitor, &tmux, NOW + 5), ["idle"]);
+
+        show(&tmux, "%1", "Reading files\n> \n");
+        for pane in tmux.panes.borrow_mut().iter_mut() {
+            pane.window_activity = NOW + 6;
+        }
+        assert_eq!(statuses(&monitor, &tmux, NOW + 6), ["busy"]);
+        assert_eq!(statuses(&monitor, &tmux, NOW + 60), ["idle"]);
+    }
+
+    #[test]
+    fn quiet_panes_are_not_captured_again() {
+        let tmux = Rc::new(FakeTmux::with_sessions(&["api"]));
+        tmux.panes.borrow_mut().push(pane("api", "@1", "%1", "claude", LONG_AGO));
+        show(&tmux, "%1", "> \n");
+        let monitor = ActivityMonitor::new(tmux.clone(), &Config::default());
+        assert_eq!(statuses(&monitor, &tmux, NOW), ["idle"]);
+
+        // Without new output in the window, the last capture stands
+        show(&tmux, "%1", "Do you want to proceed?\n");
+        assert_eq!(statuses(&monitor, &tmux, NOW), ["idle"]);
+
+        tmux.panes.borrow_mut()[0].window_activity = LONG_AGO + 1;
+        assert_eq!(statuses(&monitor, &tmux, NOW), ["waiting"]);
+    }
+}
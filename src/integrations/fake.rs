@@ -0,0 +1,348 @@
+//! In-memory backends for driving App and the components in tests.
+//!
+//! Each fake keeps just enough state to answer its own queries and records every call
+//! that changes something, e.g. `"create_session api /work/api"`.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::config::SessionLayout;
+use crate::error::{PmanError, Result};
+use crate::integrations::{EditorBackend, GitBackend, TmuxBackend};
+use crate::models::{
+    parse_diff, AgentStatus, Diff, GitRef, GitWorktree, MergeStrategy, NvimBuffer, NvimInstance,
+    PatchAction, RefKind, TmuxPane, TmuxSession, WorktreeDetails,
+};
+
+#[derive(Default)]
+pub struct FakeTmux {
+    pub sessions: RefCell<Vec<TmuxSession>>,
+    pub panes: RefCell<Vec<TmuxPane>>,
+    /// What `capture_pane` shows, by pane id
+    pub contents: RefCell<BTreeMap<String, String>>,
+    pub calls: RefCell<Vec<String>>,
+    /// Names of windows that fail to open
+    pub failing_windows: RefCell<Vec<String>>,
+}
+
+impl FakeTmux {
+    pub fn with_sessions(names: &[&str]) -> Self {
+        let fake = Self::default();
+        for name in names {
+            fake.sessions.borrow_mut().push(session(name, None));
+        }
+        fake
+    }
+
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.borrow().clone()
+    }
+
+    fn record(&self, call: String) {
+        self.calls.borrow_mut().push(call);
+    }
+}
+
+fn session(name: &str, path: Option<&PathBuf>) -> TmuxSession {
+    TmuxSession {
+        name: name.to_string(),
+        attached: false,
+        path: path.cloned(),
+        agent: None,
+        status: AgentStatus::Unknown,
+    }
+}
+
+impl TmuxBackend for FakeTmux {
+    fn list_sessions(&self) -> Result<Vec<TmuxSession>> {
+        Ok(self.sessions.borrow().clone())
+    }
+
+    fn list_panes(&self) -> Result<Vec<TmuxPane>> {
+        Ok(self.panes.borrow().clone())
+    }
+
+    fn capture_pane(&self, target: &str, _lines: usize) -> Result<String> {
+        Ok(self.contents.borrow().get(target).cloned().unwrap_or_default())
+    }
+
+    fn capture_pane_ansi(&self, _target: &str, _lines: usize) -> Result<Vec<u8>> {
+        Ok(Vec::new())
+    }
+
+    fn switch_session(&self, session_name: &str) -> Result<()> {
+        self.record(format!("switch_session {}", session_name));
+        Ok(())
+    }
+
+    fn create_session(&self, name: &str, path: Option<&PathBuf>) -> Result<String> {
+        let dir = path.map(|p| p.display().to_string()).unwrap_or_default();
+        self.record(format!("create_session {} {}", name, dir).trim_end().to_string());
+        // Like tmux
+        let name = name.replace(['.', ':'], "_");
+        self.sessions.borrow_mut().push(session(&name, path));
+        Ok(name)
+    }
+
+    fn kill_session(&self, session_name: &str) -> Result<()> {
+        self.record(format!("kill_session {}", session_name));
+        self.sessions.borrow_mut().retain(|s| s.name != session_name);
+        Ok(())
+    }
+
+    fn current_session(&self) -> Result<String> {
+        self.sessions
+            .borrow()
+            .first()
+            .map(|s| s.name.clone())
+            .ok_or_else(|| PmanError::Tmux("no sessions".to_string()))
+    }
+
+    fn current_path(&self) -> Result<PathBuf> {
+        Ok(PathBuf::from("/work/repo"))
+    }
+
+    fn new_window(
+        &self,
+        session: &str,
+        name: &str,
+        _path: &Path,
+        command: &str,
+        _env: &BTreeMap<String, String>,
+    ) -> Result<String> {
+        self.record(format!("new_window {} {} {}", session, name, command));
+        if self.failing_windows.borrow().iter().any(|w| w == name) {
+            return Err(PmanError::Tmux(format!("could not open window '{}'", name)));
+        }
+        Ok("@1".to_string())
+    }
+
+    fn new_window_remain_on_exit(
+        &self,
+        session: &str,
+        name: &str,
+        path: &Path,
+        command: &str,
+        env: &BTreeMap<String, String>,
+    ) -> Result<String> {
+        self.new_window(session, name, path, command, env)
+    }
+
+    fn set_session_option(&self, session: &str, option: &str, value: &str) -> Result<()> {
+        self.record(format!("set_session_option {} {} {}", session, option, value));
+        Ok(())
+    }
+
+    fn apply_layout(&self, session: &str, _root: &Path, layout: &SessionLayout) -> Result<()> {
+        self.record(format!("apply_layout {} {} windows", session, layout.windows.len()));
+        Ok(())
+    }
+
+    fn get_or_create_editor_window(&self) -> Result<String> {
+        Ok("@0".to_string())
+    }
+
+    fn send_keys(&self, target: &str, keys: &str) -> Result<()> {
+        self.record(format!("send_keys {} {}", target, keys));
+        Ok(())
+    }
+
+    fn select_window(&self, window_id: &str) -> Result<()> {
+        self.record(format!("select_window {}", window_id));
+        Ok(())
+    }
+
+    fn popup_command(&self, command: &str, _width: &str, _height: &str) -> Result<()> {
+        self.record(format!("popup_command {}", command));
+        Ok(())
+    }
+}
+
+/// A repository at `/work/repo` whose worktrees live in `/work/<branch>`.
+pub struct FakeGit {
+    pub worktrees: RefCell<Vec<GitWorktree>>,
+    pub refs: Vec<GitRef>,
+    /// Files `merge_to_main` reports as conflicting; empty means merges succeed
+    pub conflicts: Vec<String>,
+    /// `git diff` output every worktree reports, unstaged and staged
+    pub diff: String,
+    pub staged_diff: String,
+    /// `git diff` output between any two worktrees
+    pub compare_diff: String,
+    pub calls: RefCell<Vec<String>>,
+}
+
+impl FakeGit {
+    pub fn new() -> Self {
+        Self {
+            worktrees: RefCell::new(vec![worktree("main", true)]),
+            refs: vec![GitRef {
+                name: "main".to_string(),
+                kind: RefKind::Branch,
+            }],
+            conflicts: Vec::new(),
+            diff: String::new(),
+            staged_diff: String::new(),
+            compare_diff: String::new(),
+            calls: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn with_worktree(self, branch: &str) -> Self {
+        self.worktrees.borrow_mut().push(worktree(branch, false));
+        self
+    }
+
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.borrow().clone()
+    }
+
+    fn record(&self, call: String) {
+        self.calls.borrow_mut().push(call);
+    }
+}
+
+fn worktree(branch: &str, is_main: bool) -> GitWorktree {
+    let path = if is_main {
+        PathBuf::from("/work/repo")
+    } else {
+        PathBuf::from("/work").join(branch)
+    };
+    GitWorktree {
+        path,
+        branch: branch.to_string(),
+        is_main,
+        commit_hash: "0123456789abcdef".to_string(),
+        has_changes: false,
+    }
+}
+
+impl GitBackend for FakeGit {
+    fn main_worktree(&self) -> Result<PathBuf> {
+        Ok(PathBuf::from("/work/repo"))
+    }
+
+    fn list_worktrees(&self) -> Result<Vec<GitWorktree>> {
+        Ok(self.worktrees.borrow().clone())
+    }
+
+    fn worktree_details(&self, _worktree: &GitWorktree) -> Result<WorktreeDetails> {
+        Ok(WorktreeDetails::default())
+    }
+
+    fn list_refs(&self) -> Result<Vec<GitRef>> {
+        Ok(self.refs.clone())
+    }
+
+    fn branch_exists(&self, branch: &str) -> bool {
+        self.refs.iter().any(|r| r.kind == RefKind::Branch && r.name == branch)
+    }
+
+    fn create_worktree(&self, branch_name: &str) -> Result<PathBuf> {
+        self.add_worktree(branch_name, None, false)
+    }
+
+    fn add_worktree(&self, branch_name: &str, base: Option<&str>, track: bool) -> Result<PathBuf> {
+        self.record(format!(
+            "add_worktree {} {} {}",
+            branch_name,
+            base.unwrap_or("-"),
+            track
+        ));
+        let worktree = worktree(branch_name, false);
+        let path = worktree.path.clone();
+        self.worktrees.borrow_mut().push(worktree);
+        Ok(path)
+    }
+
+    fn delete_worktree(&self, path: &Path) -> Result<()> {
+        self.record(format!("delete_worktree {}", path.display()));
+        self.worktrees.borrow_mut().retain(|w| w.path != path);
+        Ok(())
+    }
+
+    fn branch_subjects(&self, _branch: &str) -> Result<Vec<String>> {
+        Ok(vec!["Add feature".to_string()])
+    }
+
+    fn merge_to_main(
+        &self,
+        worktree_path: &Path,
+        branch: &str,
+        strategy: MergeStrategy,
+        _message: Option<&str>,
+    ) -> Result<()> {
+        self.record(format!("merge_to_main {} {:?}", branch, strategy));
+        if !self.conflicts.is_empty() {
+            return Err(PmanError::MergeConflict {
+                files: self.conflicts.clone(),
+            });
+        }
+        self.worktrees.borrow_mut().retain(|w| w.path != worktree_path);
+        Ok(())
+    }
+
+    fn diff(&self, path: &Path, staged: bool) -> Result<Diff> {
+        let diff = if staged { &self.staged_diff } else { &self.diff };
+        Ok(Diff {
+            root: path.to_path_buf(),
+            files: parse_diff(diff),
+        })
+    }
+
+    /// Recorded with the patch's hunks, e.g.
+    /// `"apply_patch /work/repo Stage @@ -1,2 +1,1 @@\n-a\n b\n"`.
+    fn apply_patch(&self, root: &Path, patch: &str, action: PatchAction) -> Result<()> {
+        let hunks = patch.find("\n@@").map_or("", |start| &patch[start + 1..]);
+        self.record(format!("apply_patch {} {:?} {}", root.display(), action, hunks));
+        Ok(())
+    }
+
+    fn commit(&self, root: &Path, message: &str) -> Result<()> {
+        self.record(format!("commit {} {}", root.display(), message));
+        Ok(())
+    }
+
+    fn compare(&self, target: &Path, _source: &Path) -> Result<Diff> {
+        Ok(Diff {
+            root: target.to_path_buf(),
+            files: parse_diff(&self.compare_diff),
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct FakeEditor {
+    pub buffers: Vec<(NvimInstance, NvimBuffer)>,
+    pub calls: RefCell<Vec<String>>,
+}
+
+impl FakeEditor {
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.borrow().clone()
+    }
+}
+
+impl EditorBackend for FakeEditor {
+    fn open_file(&self, file_path: &Path, position: Option<(usize, usize)>) -> Result<()> {
+        let position = position.map(|(line, column)| format!(":{}:{}", line, column));
+        self.calls.borrow_mut().push(format!(
+            "open_file {}{}",
+            file_path.display(),
+            position.unwrap_or_default()
+        ));
+        Ok(())
+    }
+
+    fn open_buffer(&self, nvim: &NvimInstance, bufnr: i64) -> Result<()> {
+        self.calls
+            .borrow_mut()
+            .push(format!("open_buffer {} {}", nvim.pane_id, bufnr));
+        Ok(())
+    }
+
+    fn list_buffers(&self) -> Result<Vec<(NvimInstance, NvimBuffer)>> {
+        Ok(self.buffers.clone())
+    }
+}
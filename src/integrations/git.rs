@@ -1,21 +1,97 @@
-use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::fs;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
 
+use crate::config::Config;
 use crate::error::{PmanError, Result};
-use crate::models::GitWorktree;
+use crate::models::{
+    parse_diff, Diff, GitRef, GitWorktree, MergeStrategy, PatchAction, RefKind, UpstreamStatus,
+    WorktreeDetails,
+};
+
+/// The git operations behind the worktree views, so they can run against a fake.
+pub trait GitBackend {
+    /// The repository's main worktree, even when running inside a linked one.
+    fn main_worktree(&self) -> Result<PathBuf>;
+
+    fn list_worktrees(&self) -> Result<Vec<GitWorktree>>;
+
+    fn worktree_details(&self, worktree: &GitWorktree) -> Result<WorktreeDetails>;
+
+    /// Local branches, remote branches and tags, in that order.
+    fn list_refs(&self) -> Result<Vec<GitRef>>;
+
+    fn branch_exists(&self, branch: &str) -> bool;
+
+    /// Create a worktree for `branch_name`, checking it out if it already exists locally
+    /// or on exactly one remote, and branching from HEAD otherwise.
+    fn create_worktree(&self, branch_name: &str) -> Result<PathBuf>;
+
+    /// Create a worktree for `branch_name`. Existing local branches are checked out;
+    /// otherwise a new branch starts at `base` (HEAD if `None`), tracking it if `track`.
+    fn add_worktree(
+        &self,
+        branch_name: &str,
+        base: Option<&str>,
+        track: bool,
+    ) -> Result<PathBuf>;
+
+    fn delete_worktree(&self, path: &Path) -> Result<()>;
+
+    /// Subjects of the commits on `branch` that are not on main, oldest first.
+    fn branch_subjects(&self, branch: &str) -> Result<Vec<String>>;
+
+    /// Bring `branch` into main with `strategy`, then delete its worktree and the branch.
+    ///
+    /// `message` is the commit message for squash merges. The worktree and branch are
+    /// only deleted once main is verified to contain the branch's changes.
+    fn merge_to_main(
+        &self,
+        worktree_path: &Path,
+        branch: &str,
+        strategy: MergeStrategy,
+        message: Option<&str>,
+    ) -> Result<()>;
+
+    /// Changes in the worktree containing `path` that are not staged yet, or the staged
+    /// ones if `staged`.
+    fn diff(&self, path: &Path, staged: bool) -> Result<Diff>;
+
+    /// Apply a patch made from a `diff` of the worktree at `root`.
+    fn apply_patch(&self, root: &Path, patch: &str, action: PatchAction) -> Result<()>;
+
+    /// Commit what is staged in the worktree at `root`.
+    fn commit(&self, root: &Path, message: &str) -> Result<()>;
+
+    /// What it takes to turn the worktree containing `target` into the one containing
+    /// `source`, both as they are on disk: their HEADs with every uncommitted change,
+    /// including untracked files.
+    fn compare(&self, target: &Path, source: &Path) -> Result<Diff>;
+}
+
+/// Options that keep `git diff` output parseable whatever the user's git config says.
+const DIFF_OPTIONS: &[&str] = &[
+    "--no-color",
+    "--no-ext-diff",
+    "--find-renames",
+    "--src-prefix=a/",
+    "--dst-prefix=b/",
+];
 
 pub struct GitClient {
     repo_root: PathBuf,
+    /// `worktree.path` template for new worktrees
+    path_template: String,
 }
 
 impl GitClient {
-    pub fn new(path: &Path) -> Result<Self> {
+    pub fn new(path: &Path, config: &Config) -> Result<Self> {
         let repo_root = Self::find_repo_root(path)?;
-        Ok(Self { repo_root })
-    }
-
-    pub fn is_git_repo(path: &Path) -> bool {
-        Self::find_repo_root(path).is_ok()
+        Ok(Self {
+            repo_root,
+            path_template: config.worktree.path.clone(),
+        })
     }
 
     fn find_repo_root(path: &Path) -> Result<PathBuf> {
@@ -34,11 +110,444 @@ impl GitClient {
         ))
     }
 
-    pub fn repo_root(&self) -> &Path {
-        &self.repo_root
+    /// Main worktree of the repository containing `path`, if any.
+    pub fn main_worktree_of(path: &Path) -> Option<PathBuf> {
+        let common_dir = Self::run(
+            path,
+            &["rev-parse", "--path-format=absolute", "--git-common-dir"],
+        )
+        .ok()?;
+        // Bare repositories have no main worktree
+        let common_dir = PathBuf::from(common_dir);
+        match common_dir.file_name() {
+            Some(name) if name == ".git" => common_dir.parent().map(Path::to_path_buf),
+            _ => None,
+        }
+    }
+
+    /// The linked worktree `path` is in, or `None` in a main worktree or outside git.
+    pub fn linked_worktree_of(path: &Path) -> Option<PathBuf> {
+        let root = Self::find_repo_root(path).ok()?;
+        match Self::main_worktree_of(path) {
+            Some(main) if main == root => None,
+            _ => Some(root),
+        }
+    }
+
+    pub fn has_uncommitted_changes(&self, path: &Path) -> Result<bool> {
+        let output = Command::new("git")
+            .args(["status", "--porcelain"])
+            .current_dir(path)
+            .output()
+            .map_err(|e| PmanError::Git(e.to_string()))?;
+
+        Ok(!output.stdout.is_empty())
+    }
+
+    pub fn get_main_branch(&self) -> Result<String> {
+        // Try to get the default branch name
+        let output = Command::new("git")
+            .args(["symbolic-ref", "refs/remotes/origin/HEAD"])
+            .current_dir(&self.repo_root)
+            .output();
+
+        if let Ok(output) = output {
+            if output.status.success() {
+                let branch = String::from_utf8_lossy(&output.stdout)
+                    .trim()
+                    .strip_prefix("refs/remotes/origin/")
+                    .unwrap_or("main")
+                    .to_string();
+                return Ok(branch);
+            }
+        }
+
+        // Fallback: check if main or master exists
+        for branch in ["main", "master"] {
+            let output = Command::new("git")
+                .args(["rev-parse", "--verify", branch])
+                .current_dir(&self.repo_root)
+                .output()
+                .map_err(|e| PmanError::Git(e.to_string()))?;
+
+            if output.status.success() {
+                return Ok(branch.to_string());
+            }
+        }
+
+        Ok("main".to_string())
+    }
+
+    /// `git rev-list --left-right --count <range>` as (left, right).
+    fn left_right_count(path: &Path, range: &str) -> Result<(usize, usize)> {
+        let counts = Self::run(path, &["rev-list", "--left-right", "--count", range])?;
+        let mut parts = counts.split_whitespace().map(|n| n.parse().unwrap_or(0));
+        Ok((parts.next().unwrap_or(0), parts.next().unwrap_or(0)))
+    }
+
+    /// Run git in `dir` and return trimmed stdout.
+    fn run(dir: &Path, args: &[&str]) -> Result<String> {
+        Self::run_raw(dir, args).map(|stdout| stdout.trim_end().to_string())
+    }
+
+    /// Run git in `dir` and return stdout as is, e.g. for patches where trailing
+    /// whitespace matters.
+    fn run_raw(dir: &Path, args: &[&str]) -> Result<String> {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .map_err(|e| PmanError::Git(e.to_string()))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(PmanError::Git(stderr.to_string()));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Run git in `dir` with `input` on stdin.
+    fn run_with_input(dir: &Path, args: &[&str], input: &str) -> Result<()> {
+        let mut child = Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| PmanError::Git(e.to_string()))?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(input.as_bytes())?;
+        }
+        let output = child.wait_with_output()?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(PmanError::Git(stderr.trim_end().to_string()));
+        }
+        Ok(())
+    }
+
+    /// A tree object of the worktree at `root` as it is on disk, untracked files
+    /// included and ignored ones not. It is built in a scratch copy of the index, so
+    /// what is staged stays as it is.
+    fn snapshot(root: &Path) -> Result<String> {
+        let index = root.join(Self::run(root, &["rev-parse", "--git-path", "index"])?);
+        let scratch = index.with_file_name("pman-snapshot-index");
+        // A copy rather than a fresh index, so unchanged files aren't hashed again
+        if index.exists() {
+            fs::copy(&index, &scratch)?;
+        }
+
+        let run = |args: &[&str]| {
+            let output = Command::new("git")
+                .args(args)
+                .current_dir(root)
+                .env("GIT_INDEX_FILE", &scratch)
+                .output()
+                .map_err(|e| PmanError::Git(e.to_string()))?;
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                return Err(PmanError::Git(stderr.trim_end().to_string()));
+            }
+            Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        };
+        let tree = run(&["add", "--all", "--", "."]).and_then(|_| run(&["write-tree"]));
+
+        let _ = fs::remove_file(&scratch);
+        tree
+    }
+
+    /// Where the worktree for `branch` goes, from the `worktree.path` template.
+    /// Fails if something already exists there.
+    fn worktree_path(&self, branch: &str) -> Result<PathBuf> {
+        let slug = branch_slug(branch);
+        if slug.is_empty() {
+            return Err(PmanError::Git(format!(
+                "Branch '{}' has no characters usable in a directory name",
+                branch
+            )));
+        }
+
+        // Named after the main worktree, even when pman runs inside a linked one
+        let main = self.main_worktree()?;
+        let repo = main
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let repo_parent = main.parent().unwrap_or(&main);
+
+        let rendered = self
+            .path_template
+            .replace("{repo_parent}", &repo_parent.to_string_lossy())
+            .replace("{repo}", &repo)
+            .replace("{branch_slug}", &slug)
+            .replace("{branch}", branch);
+        // Relative templates are relative to the main worktree
+        let mut path = PathBuf::new();
+        for component in main.join(rendered).components() {
+            match component {
+                Component::ParentDir => {
+                    path.pop();
+                }
+                Component::CurDir => {}
+                other => path.push(other),
+            }
+        }
+
+        let registered = Self::run(&self.repo_root, &["worktree", "list", "--porcelain"])?
+            .lines()
+            .filter_map(|line| line.strip_prefix("worktree "))
+            .any(|existing| Path::new(existing) == path);
+        if registered || path.exists() {
+            return Err(PmanError::Git(format!(
+                "Cannot create worktree for '{}': {} already exists",
+                branch,
+                path.display()
+            )));
+        }
+
+        Ok(path)
+    }
+
+    /// Merge `branch` into the branch checked out (and clean) in `dir`.
+    fn merge_in_checkout(
+        dir: &Path,
+        branch: &str,
+        strategy: MergeStrategy,
+        message: Option<&str>,
+    ) -> Result<()> {
+        match strategy {
+            MergeStrategy::Merge => {
+                if let Err(e) = Self::run(dir, &["merge", branch]) {
+                    return Err(Self::roll_back(dir, &["merge", "--abort"], e));
+                }
+            }
+            MergeStrategy::Squash => {
+                let message = message.unwrap_or_default();
+                // Squash merges leave no MERGE_HEAD, so `merge --abort` cannot undo them
+                if let Err(e) = Self::run(dir, &["merge", "--squash", branch])
+                    .and_then(|_| Self::run(dir, &["commit", "-m", message]))
+                {
+                    return Err(Self::roll_back(dir, &["reset", "--merge"], e));
+                }
+            }
+            MergeStrategy::Rebase | MergeStrategy::FfOnly => {
+                Self::run(dir, &["merge", "--ff-only", branch])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Merge `branch` into `main_branch` when no worktree has it checked out, by
+    /// building the commit with `merge-tree` and `commit-tree` and moving the ref.
+    fn merge_refs(
+        &self,
+        main_branch: &str,
+        branch: &str,
+        strategy: MergeStrategy,
+        message: Option<&str>,
+    ) -> Result<()> {
+        let old = Self::run(&self.repo_root, &["rev-parse", main_branch])?;
+        let new = self.merge_commit(main_branch, &old, branch, strategy, message)?;
+        self.move_branch(main_branch, &old, &new)
+    }
+
+    /// The commit `main_branch` would point to after merging `branch` into `old`, its
+    /// current commit.
+    fn merge_commit(
+        &self,
+        main_branch: &str,
+        old: &str,
+        branch: &str,
+        strategy: MergeStrategy,
+        message: Option<&str>,
+    ) -> Result<String> {
+        let rev_parse = |rev: &str| Self::run(&self.repo_root, &["rev-parse", rev]);
+        let head = rev_parse(branch)?;
+        let fast_forward = self.is_ancestor(old, &head)?;
+
+        let new = match strategy {
+            MergeStrategy::Merge | MergeStrategy::Rebase | MergeStrategy::FfOnly
+                if fast_forward =>
+            {
+                head
+            }
+            MergeStrategy::Rebase | MergeStrategy::FfOnly => {
+                return Err(PmanError::Git(format!(
+                    "Not possible to fast-forward '{}' to '{}'",
+                    main_branch, branch
+                )));
+            }
+            MergeStrategy::Merge | MergeStrategy::Squash => {
+                // Conflicts were ruled out before, so the first line is a clean tree
+                let merged =
+                    Self::run(&self.repo_root, &["merge-tree", "--write-tree", old, &head])?;
+                let tree = merged.lines().next().unwrap_or_default().to_string();
+
+                if strategy == MergeStrategy::Merge {
+                    let message = format!("Merge branch '{}'", branch);
+                    Self::run(
+                        &self.repo_root,
+                        &["commit-tree", &tree, "-p", old, "-p", &head, "-m", &message],
+                    )?
+                } else {
+                    if tree == rev_parse(&format!("{}^{{tree}}", old))? {
+                        return Err(PmanError::Git(format!(
+                            "'{}' has no changes to squash into '{}'",
+                            branch, main_branch
+                        )));
+                    }
+                    let message = message.unwrap_or_default();
+                    Self::run(
+                        &self.repo_root,
+                        &["commit-tree", &tree, "-p", old, "-m", message],
+                    )?
+                }
+            }
+        };
+        Ok(new)
+    }
+
+    /// Point `main_branch` at `new`, unless it no longer points at `old`.
+    fn move_branch(&self, main_branch: &str, old: &str, new: &str) -> Result<()> {
+        // Passing the old value makes this fail if main moved in the meantime
+        let main_ref = format!("refs/heads/{}", main_branch);
+        match Self::run(&self.repo_root, &["update-ref", &main_ref, new, old]) {
+            Ok(_) => Ok(()),
+            Err(PmanError::Git(stderr)) => Err(PmanError::Git(format!(
+                "'{}' moved during the merge and was left as it is: {}",
+                main_branch,
+                stderr.trim_end()
+            ))),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn is_ancestor(&self, ancestor: &str, descendant: &str) -> Result<bool> {
+        let status = Command::new("git")
+            .args(["merge-base", "--is-ancestor", ancestor, descendant])
+            .current_dir(&self.repo_root)
+            .status()
+            .map_err(|e| PmanError::Git(e.to_string()))?;
+        Ok(status.success())
+    }
+
+    /// Files that would conflict when merging `branch` into `main_branch`, without
+    /// touching any working tree.
+    pub fn merge_conflicts(&self, main_branch: &str, branch: &str) -> Result<Vec<String>> {
+        let output = Command::new("git")
+            .args([
+                "merge-tree",
+                "--write-tree",
+                "--name-only",
+                "--no-messages",
+                main_branch,
+                branch,
+            ])
+            .current_dir(&self.repo_root)
+            .output()
+            .map_err(|e| PmanError::Git(e.to_string()))?;
+
+        // Exit code 1 means the merge has conflicts; anything else non-zero is an error
+        match output.status.code() {
+            Some(0) => Ok(Vec::new()),
+            Some(1) => {
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                let mut files: Vec<String> = Vec::new();
+                // The first line is the (conflicted) tree id
+                for file in stdout.lines().skip(1).filter(|l| !l.is_empty()) {
+                    if !files.iter().any(|f| f == file) {
+                        files.push(file.to_string());
+                    }
+                }
+                Ok(files)
+            }
+            _ => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                Err(PmanError::Git(stderr.to_string()))
+            }
+        }
+    }
+
+    /// Undo a failed merge or rebase in `dir` with `abort`, turning conflicts into
+    /// `MergeConflict`.
+    fn roll_back(dir: &Path, abort: &[&str], error: PmanError) -> PmanError {
+        let files: Vec<String> = Self::run(dir, &["diff", "--name-only", "--diff-filter=U"])
+            .map(|out| out.lines().map(str::to_string).collect())
+            .unwrap_or_default();
+
+        if let Err(PmanError::Git(stderr)) = Self::run(dir, abort) {
+            let cause = match error {
+                PmanError::Git(cause) => cause,
+                other => other.to_string(),
+            };
+            return PmanError::Git(format!(
+                "{}\n'git {}' could not roll back {}: {}",
+                cause,
+                abort.join(" "),
+                dir.display(),
+                stderr
+            ));
+        }
+
+        if files.is_empty() {
+            error
+        } else {
+            PmanError::MergeConflict { files }
+        }
+    }
+
+    fn verify_merged(
+        &self,
+        main_branch: &str,
+        branch: &str,
+        strategy: MergeStrategy,
+    ) -> Result<()> {
+        let rev_parse = |rev: &str| Self::run(&self.repo_root, &["rev-parse", rev]);
+
+        let merged = match strategy {
+            MergeStrategy::Merge => self.is_ancestor(branch, main_branch)?,
+            // Merging the branch again must not change main's tree
+            MergeStrategy::Squash => {
+                let merged_tree = Self::run(
+                    &self.repo_root,
+                    &["merge-tree", "--write-tree", main_branch, branch],
+                )?;
+                let main_tree = rev_parse(&format!("{}^{{tree}}", main_branch))?;
+                merged_tree.lines().next() == Some(main_tree.as_str())
+            }
+            MergeStrategy::Rebase | MergeStrategy::FfOnly => {
+                rev_parse(main_branch)? == rev_parse(branch)?
+            }
+        };
+
+        if !merged {
+            return Err(PmanError::Git(format!(
+                "'{}' does not contain all of '{}' after {}; worktree and branch were kept",
+                main_branch,
+                branch,
+                strategy.label().to_lowercase()
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+impl GitBackend for GitClient {
+    fn main_worktree(&self) -> Result<PathBuf> {
+        let list = Self::run(&self.repo_root, &["worktree", "list", "--porcelain"])?;
+        list.lines()
+            .next()
+            .and_then(|line| line.strip_prefix("worktree "))
+            .map(PathBuf::from)
+            .ok_or_else(|| PmanError::Git("git worktree list returned nothing".to_string()))
     }
 
-    pub fn list_worktrees(&self) -> Result<Vec<GitWorktree>> {
+    fn list_worktrees(&self) -> Result<Vec<GitWorktree>> {
         let output = Command::new("git")
             .args(["worktree", "list", "--porcelain"])
             .current_dir(&self.repo_root)
@@ -99,78 +608,142 @@ impl GitClient {
         Ok(worktrees)
     }
 
-    pub fn has_uncommitted_changes(&self, path: &Path) -> Result<bool> {
-        let output = Command::new("git")
-            .args(["status", "--porcelain"])
-            .current_dir(path)
-            .output()
-            .map_err(|e| PmanError::Git(e.to_string()))?;
+    fn worktree_details(&self, worktree: &GitWorktree) -> Result<WorktreeDetails> {
+        let path = worktree.path.as_path();
+        let main_branch = self.get_main_branch()?;
 
-        Ok(!output.stdout.is_empty())
+        let (behind, ahead) =
+            Self::left_right_count(path, &format!("{}...HEAD", main_branch)).unwrap_or((0, 0));
+
+        let upstream = Self::run(path, &["rev-parse", "--abbrev-ref", "@{upstream}"])
+            .ok()
+            .map(|name| {
+                let (behind, ahead) = Self::left_right_count(path, "@{upstream}...HEAD")
+                    .unwrap_or((0, 0));
+                UpstreamStatus { name, ahead, behind }
+            });
+
+        let uncommitted_stat = Self::run(path, &["diff", "HEAD", "--stat"]).unwrap_or_default();
+        let unmerged_stat = if worktree.is_main {
+            String::new()
+        } else {
+            Self::run(path, &["diff", "--stat", &format!("{}...HEAD", main_branch)])
+                .unwrap_or_default()
+        };
+
+        let recent_commits = Self::run(path, &["log", "-5", "--format=%h %s"])
+            .map(|log| log.lines().map(str::to_string).collect())
+            .unwrap_or_default();
+
+        Ok(WorktreeDetails {
+            main_branch,
+            ahead,
+            behind,
+            upstream,
+            uncommitted_stat,
+            unmerged_stat,
+            recent_commits,
+        })
     }
 
-    pub fn get_main_branch(&self) -> Result<String> {
-        // Try to get the default branch name
-        let output = Command::new("git")
-            .args(["symbolic-ref", "refs/remotes/origin/HEAD"])
-            .current_dir(&self.repo_root)
-            .output();
+    fn list_refs(&self) -> Result<Vec<GitRef>> {
+        let output = Self::run(
+            &self.repo_root,
+            &[
+                "for-each-ref",
+                "--format=%(refname)",
+                "refs/heads",
+                "refs/remotes",
+                "refs/tags",
+            ],
+        )?;
 
-        if let Ok(output) = output {
-            if output.status.success() {
-                let branch = String::from_utf8_lossy(&output.stdout)
-                    .trim()
-                    .strip_prefix("refs/remotes/origin/")
-                    .unwrap_or("main")
-                    .to_string();
-                return Ok(branch);
-            }
-        }
+        let refs = output
+            .lines()
+            .filter_map(|refname| {
+                let (name, kind) = if let Some(name) = refname.strip_prefix("refs/heads/") {
+                    (name, RefKind::Branch)
+                } else if let Some(name) = refname.strip_prefix("refs/remotes/") {
+                    (name, RefKind::RemoteBranch)
+                } else {
+                    (refname.strip_prefix("refs/tags/")?, RefKind::Tag)
+                };
+                // Skip symbolic refs like origin/HEAD
+                if kind == RefKind::RemoteBranch && name.ends_with("/HEAD") {
+                    return None;
+                }
+                Some(GitRef {
+                    name: name.to_string(),
+                    kind,
+                })
+            })
+            .collect();
 
-        // Fallback: check if main or master exists
-        for branch in ["main", "master"] {
-            let output = Command::new("git")
-                .args(["rev-parse", "--verify", branch])
-                .current_dir(&self.repo_root)
-                .output()
-                .map_err(|e| PmanError::Git(e.to_string()))?;
+        Ok(refs)
+    }
 
-            if output.status.success() {
-                return Ok(branch.to_string());
-            }
-        }
+    fn branch_exists(&self, branch: &str) -> bool {
+        Self::run(
+            &self.repo_root,
+            &["rev-parse", "--verify", "--quiet", &format!("refs/heads/{}", branch)],
+        )
+        .is_ok()
+    }
 
-        Ok("main".to_string())
+    fn create_worktree(&self, branch_name: &str) -> Result<PathBuf> {
+        self.add_worktree(branch_name, None, false)
     }
 
-    pub fn create_worktree(&self, branch_name: &str) -> Result<PathBuf> {
-        let worktree_path = self.repo_root.parent().unwrap_or(&self.repo_root).join(branch_name);
+    fn add_worktree(
+        &self,
+        branch_name: &str,
+        base: Option<&str>,
+        track: bool,
+    ) -> Result<PathBuf> {
+        let worktree_path = self.worktree_path(branch_name)?;
 
         let path_str = worktree_path
             .to_str()
             .ok_or_else(|| PmanError::Git("Invalid path encoding".to_string()))?;
 
-        let output = Command::new("git")
-            .args([
-                "worktree",
-                "add",
-                "-b",
-                branch_name,
-                path_str,
-            ])
-            .current_dir(&self.repo_root)
-            .output()
-            .map_err(|e| PmanError::Git(e.to_string()))?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(PmanError::Git(stderr.to_string()));
+        let mut args = vec!["worktree", "add"];
+        let remote_branch;
+        if self.branch_exists(branch_name) {
+            args.extend([path_str, branch_name]);
+        } else if let Some(base) = base {
+            let track = if track { "--track" } else { "--no-track" };
+            args.extend([track, "-b", branch_name, path_str, base]);
+        } else {
+            // A branch of the same name on exactly one remote is checked out tracking it
+            let remotes: Vec<String> = self
+                .list_refs()?
+                .into_iter()
+                .filter(|r| r.remote_parts().is_some_and(|(_, name)| name == branch_name))
+                .map(|r| r.name)
+                .collect();
+            match remotes.as_slice() {
+                [] => args.extend(["-b", branch_name, path_str]),
+                [remote] => {
+                    remote_branch = remote.clone();
+                    args.extend(["--track", "-b", branch_name, path_str, &remote_branch]);
+                }
+                // Branching from HEAD instead would quietly ignore all of them
+                _ => {
+                    return Err(PmanError::Git(format!(
+                        "'{}' is on several remotes ({}); choose one as the base",
+                        branch_name,
+                        remotes.join(", ")
+                    )));
+                }
+            }
         }
 
+        Self::run(&self.repo_root, &args)?;
+
         Ok(worktree_path)
     }
 
-    pub fn delete_worktree(&self, path: &Path) -> Result<()> {
+    fn delete_worktree(&self, path: &Path) -> Result<()> {
         if self.has_uncommitted_changes(path)? {
             return Err(PmanError::UncommittedChanges);
         }
@@ -193,48 +766,386 @@ impl GitClient {
         Ok(())
     }
 
-    pub fn merge_to_main(&self, worktree_path: &Path, branch: &str) -> Result<()> {
+    fn branch_subjects(&self, branch: &str) -> Result<Vec<String>> {
         let main_branch = self.get_main_branch()?;
+        let log = Self::run(
+            &self.repo_root,
+            &["log", "--reverse", "--format=%s", &format!("{}..{}", main_branch, branch)],
+        )?;
+        Ok(log.lines().map(str::to_string).collect())
+    }
 
-        // Checkout main
-        let output = Command::new("git")
-            .args(["checkout", &main_branch])
-            .current_dir(&self.repo_root)
-            .output()
-            .map_err(|e| PmanError::Git(e.to_string()))?;
+    fn merge_to_main(
+        &self,
+        worktree_path: &Path,
+        branch: &str,
+        strategy: MergeStrategy,
+        message: Option<&str>,
+    ) -> Result<()> {
+        let main_branch = self.get_main_branch()?;
+        let message = message.filter(|m| !m.trim().is_empty());
+        if strategy == MergeStrategy::Squash && message.is_none() {
+            return Err(PmanError::Git("Squash merge needs a commit message".to_string()));
+        }
+        // Uncommitted work would be left out of the merge and keep the worktree from
+        // being deleted after main has already moved
+        if self.has_uncommitted_changes(worktree_path)? {
+            return Err(PmanError::UncommittedChanges);
+        }
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(PmanError::Git(stderr.to_string()));
+        // Fast-forwards cannot conflict; everything else is checked before touching anything
+        if strategy != MergeStrategy::FfOnly {
+            let files = self.merge_conflicts(&main_branch, branch)?;
+            if !files.is_empty() {
+                return Err(PmanError::MergeConflict { files });
+            }
         }
 
-        // Merge branch
-        let output = Command::new("git")
-            .args(["merge", branch])
-            .current_dir(&self.repo_root)
-            .output()
-            .map_err(|e| PmanError::Git(e.to_string()))?;
+        // Never check out main: merge where it is already checked out, or only move the ref
+        let main_checkout = self
+            .list_worktrees()?
+            .into_iter()
+            .find(|wt| wt.branch == main_branch);
+        if let Some(wt) = main_checkout.as_ref().filter(|wt| wt.has_changes) {
+            return Err(PmanError::Git(format!(
+                "'{}' is checked out in {} with uncommitted changes; \
+                 commit or stash them before merging",
+                main_branch,
+                wt.path.display()
+            )));
+        }
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(PmanError::Git(stderr.to_string()));
+        if strategy == MergeStrategy::Rebase {
+            // Rebase in the branch's own worktree, which is deleted afterwards anyway
+            if let Err(e) = Self::run(worktree_path, &["rebase", &main_branch]) {
+                return Err(Self::roll_back(worktree_path, &["rebase", "--abort"], e));
+            }
+        }
+
+        match main_checkout {
+            Some(wt) => Self::merge_in_checkout(&wt.path, branch, strategy, message)?,
+            None => self.merge_refs(&main_branch, branch, strategy, message)?,
         }
 
+        self.verify_merged(&main_branch, branch, strategy)?;
+
         // Delete worktree
         self.delete_worktree(worktree_path)?;
 
-        // Delete branch
+        // Squashed branches are not ancestors of main, so -d would refuse them
+        Self::run(&self.repo_root, &["branch", "-D", branch])?;
+
+        Ok(())
+    }
+
+    fn diff(&self, path: &Path, staged: bool) -> Result<Diff> {
+        let root = Self::find_repo_root(path)?;
+        let mut args = vec!["-c", "core.quotePath=false", "diff"];
+        if staged {
+            args.push("--cached");
+        }
+        args.extend_from_slice(DIFF_OPTIONS);
+        let output = Self::run_raw(&root, &args)?;
+
+        Ok(Diff {
+            files: parse_diff(&output),
+            root,
+        })
+    }
+
+    fn apply_patch(&self, root: &Path, patch: &str, action: PatchAction) -> Result<()> {
+        let mut args = vec!["apply", "--whitespace=nowarn"];
+        if matches!(action, PatchAction::Stage | PatchAction::Unstage) {
+            args.push("--cached");
+        }
+        if action.is_reverse() {
+            args.push("--reverse");
+        }
+        args.push("-");
+        Self::run_with_input(root, &args, patch)
+    }
+
+    fn commit(&self, root: &Path, message: &str) -> Result<()> {
+        // From stdin, so hooks and the message keep their own lines
+        Self::run_with_input(root, &["commit", "--quiet", "--file=-"], message)
+    }
+
+    fn compare(&self, target: &Path, source: &Path) -> Result<Diff> {
+        let root = Self::find_repo_root(target)?;
+        let from = Self::snapshot(&root)?;
+        let to = Self::snapshot(&Self::find_repo_root(source)?)?;
+
+        let mut args = vec!["-c", "core.quotePath=false", "diff"];
+        args.extend_from_slice(DIFF_OPTIONS);
+        args.extend([from.as_str(), to.as_str()]);
+        let output = Self::run_raw(&root, &args)?;
+
+        Ok(Diff {
+            files: parse_diff(&output),
+            root,
+        })
+    }
+}
+
+/// `branch` reduced to characters safe in a single directory name, e.g.
+/// `feature/auth` becomes `feature-auth`.
+pub(super) fn branch_slug(branch: &str) -> String {
+    let mut slug = String::new();
+    for c in branch.chars() {
+        let c = if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') {
+            c
+        } else {
+            '-'
+        };
+        if !(c == '-' && slug.ends_with('-')) {
+            slug.push(c);
+        }
+    }
+    slug.trim_matches(['-', '.']).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    /// A repository at `<tempdir>/repo` on `main`, with one commit.
+    fn repo() -> (TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap().join("repo");
+        fs::create_dir(&root).unwrap();
+        git(&root, &["init", "--quiet", "--initial-branch=main"]);
+        // For the commits pman makes itself
+        git(&root, &["config", "user.name", "pman"]);
+        git(&root, &["config", "user.email", "pman@example.com"]);
+        fs::write(root.join("README"), "hello\n").unwrap();
+        git(&root, &["add", "README"]);
+        git(&root, &["commit", "--quiet", "-m", "Initial commit"]);
+        (dir, root)
+    }
+
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let identity = ["-c", "user.name=pman", "-c", "user.email=pman@example.com"];
         let output = Command::new("git")
-            .args(["branch", "-d", branch])
-            .current_dir(&self.repo_root)
+            .args(identity)
+            .args(args)
+            .current_dir(dir)
             .output()
-            .map_err(|e| PmanError::Git(e.to_string()))?;
+            .unwrap();
+        assert!(output.status.success(), "git {:?}: {:?}", args, output);
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(PmanError::Git(stderr.to_string()));
+    fn commit_file(dir: &Path, name: &str, subject: &str) {
+        fs::write(dir.join(name), format!("{}\n", name)).unwrap();
+        git(dir, &["add", name]);
+        git(dir, &["commit", "--quiet", "-m", subject]);
+    }
+
+    /// A repository whose `feat` worktree adds `feature` while `main` has moved on and
+    /// added `other`. Unless `main_checked_out`, the main worktree is detached.
+    fn diverged(main_checked_out: bool) -> (TempDir, PathBuf, GitClient, PathBuf) {
+        let (dir, root) = repo();
+        let client = GitClient::new(&root, &Config::default()).unwrap();
+        let worktree = client.create_worktree("feat").unwrap();
+        commit_file(&worktree, "feature", "Add feature");
+        commit_file(&root, "other", "Add other");
+        if !main_checked_out {
+            git(&root, &["switch", "--quiet", "--detach"]);
+        }
+        (dir, root, client, worktree)
+    }
+
+    fn hook(root: &Path, name: &str, script: &str) {
+        let hooks = root.join(".git/hooks");
+        git(root, &["config", "core.hooksPath", hooks.to_str().unwrap()]);
+        let path = hooks.join(name);
+        fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn worktree_paths_are_named_after_the_main_worktree() {
+        let (dir, root) = repo();
+        let config = Config::default();
+
+        let main = GitClient::new(&root, &config).unwrap();
+        let linked = main.create_worktree("feat-x").unwrap();
+        assert_eq!(linked, dir.path().canonicalize().unwrap().join("repo.worktrees/feat-x"));
+
+        let from_linked = GitClient::new(&linked, &config).unwrap();
+        let other = from_linked.add_worktree("feature/other", Some("main"), false).unwrap();
+        assert_eq!(other, linked.with_file_name("feature-other"));
+    }
+    #[test]
+    fn merging_a_dirty_worktree_is_refused_before_main_moves() {
+        let (_dir, root) = repo();
+        let client = GitClient::new(&root, &Config::default()).unwrap();
+        let worktree = client.create_worktree("feat").unwrap();
+        fs::write(worktree.join("feature"), "done\n").unwrap();
+        git(&worktree, &["add", "feature"]);
+        git(&worktree, &["commit", "--quiet", "-m", "Add feature"]);
+        fs::write(worktree.join("feature"), "not yet committed\n").unwrap();
+        let main = git(&root, &["rev-parse", "main"]);
+
+        for strategy in [MergeStrategy::Merge, MergeStrategy::Squash, MergeStrategy::Rebase] {
+            let result = client.merge_to_main(&worktree, "feat", strategy, Some("Feature"));
+            assert!(matches!(result, Err(PmanError::UncommittedChanges)), "{:?}", result);
         }
+        assert_eq!(git(&root, &["rev-parse", "main"]), main);
+        assert!(worktree.exists());
+    }
 
-        Ok(())
+    #[test]
+    fn every_strategy_lands_the_branch_on_main_and_cleans_up() {
+        for main_checked_out in [true, false] {
+            for strategy in [MergeStrategy::Merge, MergeStrategy::Squash, MergeStrategy::Rebase] {
+                let case = format!("{:?} with main checked out: {}", strategy, main_checked_out);
+                let (_dir, root, client, worktree) = diverged(main_checked_out);
+
+                client
+                    .merge_to_main(&worktree, "feat", strategy, Some("Feature"))
+                    .unwrap_or_else(|e| panic!("{}: {}", case, e));
+
+                assert_eq!(git(&root, &["show", "main:feature"]), "feature", "{}", case);
+                assert_eq!(git(&root, &["show", "main:other"]), "other", "{}", case);
+                let parents = git(&root, &["log", "-1", "--format=%P", "main"]);
+                let subject = git(&root, &["log", "-1", "--format=%s", "main"]);
+                let expected = match strategy {
+                    MergeStrategy::Merge => (2, "Merge branch 'feat'"),
+                    MergeStrategy::Squash => (1, "Feature"),
+                    _ => (1, "Add feature"),
+                };
+                assert_eq!(
+                    (parents.split_whitespace().count(), subject.as_str()),
+                    expected,
+                    "{}",
+                    case
+                );
+
+                // A checkout of main moves with it; otherwise nothing is checked out
+                assert_eq!(root.join("feature").exists(), main_checked_out, "{}", case);
+                assert!(!worktree.exists(), "{}", case);
+                assert!(!client.branch_exists("feat"), "{}", case);
+            }
+        }
+    }
+
+    #[test]
+    fn a_failed_squash_commit_is_rolled_back() {
+        let (_dir, root, client, worktree) = diverged(true);
+        hook(&root, "pre-commit", "exit 1");
+        let main = git(&root, &["rev-parse", "main"]);
+
+        let squash = MergeStrategy::Squash;
+        let result = client.merge_to_main(&worktree, "feat", squash, Some("Feature"));
+
+        assert!(matches!(result, Err(PmanError::Git(_))), "{:?}", result);
+        assert_eq!(git(&root, &["rev-parse", "main"]), main);
+        // `reset --merge` took the squashed changes back out of main's checkout
+        assert_eq!(git(&root, &["status", "--porcelain"]), "");
+        assert!(worktree.exists());
+        assert!(client.branch_exists("feat"));
+    }
+
+    #[test]
+    fn a_merge_that_did_not_land_keeps_the_worktree_and_branch() {
+        let (_dir, root, client, worktree) = diverged(true);
+        // Something undoes the merge as soon as it is made
+        hook(&root, "post-merge", "git reset --quiet --hard HEAD~1");
+
+        let result = client.merge_to_main(&worktree, "feat", MergeStrategy::Merge, None);
+
+        let Err(PmanError::Git(message)) = result else {
+            panic!("{:?}", result);
+        };
+        assert!(message.starts_with("'main' does not contain all of 'feat'"), "{}", message);
+        assert!(worktree.exists());
+        assert!(client.branch_exists("feat"));
+    }
+
+    #[test]
+    fn refs_are_merged_without_a_checkout_of_main() {
+        let (_dir, root, client, _worktree) = diverged(false);
+        let detached = git(&root, &["rev-parse", "HEAD"]);
+
+        // Diverged branches can't be fast-forwarded
+        let result = client.merge_refs("main", "feat", MergeStrategy::FfOnly, None);
+        assert!(matches!(result, Err(PmanError::Git(_))), "{:?}", result);
+        assert_eq!(git(&root, &["rev-parse", "main"]), detached);
+
+        client.merge_refs("main", "feat", MergeStrategy::Merge, None).unwrap();
+
+        let feat = git(&root, &["rev-parse", "feat"]);
+        let parents = git(&root, &["log", "-1", "--format=%P", "main"]);
+        assert_eq!(parents, format!("{} {}", detached, feat));
+        assert_eq!(git(&root, &["show", "main:feature"]), "feature");
+        // Nothing checked out was touched
+        assert_eq!(git(&root, &["rev-parse", "HEAD"]), detached);
+        assert_eq!(git(&root, &["status", "--porcelain"]), "");
+    }
+
+    #[test]
+    fn conflicts_are_reported_by_file_before_refs_move() {
+        let (_dir, root, client, worktree) = diverged(false);
+        fs::write(worktree.join("README"), "feat\n").unwrap();
+        git(&worktree, &["commit", "--quiet", "-am", "Change README on feat"]);
+        git(&root, &["switch", "--quiet", "main"]);
+        fs::write(root.join("README"), "main\n").unwrap();
+        git(&root, &["commit", "--quiet", "-am", "Change README on main"]);
+        git(&root, &["switch", "--quiet", "--detach"]);
+        let main = git(&root, &["rev-parse", "main"]);
+
+        let result = client.merge_to_main(&worktree, "feat", MergeStrategy::Merge, None);
+
+        let Err(PmanError::MergeConflict { files }) = result else {
+            panic!("{:?}", result);
+        };
+        assert_eq!(files, ["README"]);
+        assert_eq!(git(&root, &["rev-parse", "main"]), main);
+        assert!(client.branch_exists("feat"));
+    }
+
+    #[test]
+    fn main_is_not_moved_if_it_moved_during_the_merge() {
+        let (_dir, root, client, _worktree) = diverged(false);
+        let old = git(&root, &["rev-parse", "main"]);
+        let merged = client
+            .merge_commit("main", &old, "feat", MergeStrategy::Merge, None)
+            .unwrap();
+
+        // Someone else commits to main in the meantime
+        let theirs = git(&root, &["commit-tree", "main^{tree}", "-p", "main", "-m", "Theirs"]);
+        git(&root, &["update-ref", "refs/heads/main", &theirs]);
+
+        let result = client.move_branch("main", &old, &merged);
+
+        let Err(PmanError::Git(message)) = result else {
+            panic!("{:?}", result);
+        };
+        assert!(message.starts_with("'main' moved during the merge"), "{}", message);
+        assert_eq!(git(&root, &["rev-parse", "main"]), theirs);
+    }
+
+    #[test]
+    fn a_branch_on_several_remotes_is_not_branched_from_head() {
+        let (_dir, root) = repo();
+        git(&root, &["update-ref", "refs/remotes/origin/feat", "HEAD"]);
+        git(&root, &["update-ref", "refs/remotes/upstream/feat", "HEAD"]);
+        let client = GitClient::new(&root, &Config::default()).unwrap();
+
+        let result = client.create_worktree("feat");
+
+        let Err(PmanError::Git(message)) = result else {
+            panic!("{:?}", result);
+        };
+        assert_eq!(
+            message,
+            "'feat' is on several remotes (origin/feat, upstream/feat); choose one as the base"
+        );
+        assert!(!client.branch_exists("feat"));
     }
 }
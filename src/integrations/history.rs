@@ -0,0 +1,57 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::error::{PmanError, Result};
+
+/// Entries from input dialogs by history key, kept in a JSON file between runs.
+pub struct InputHistory {
+    path: PathBuf,
+    max_entries: usize,
+    /// Oldest first
+    entries: BTreeMap<String, Vec<String>>,
+}
+
+impl InputHistory {
+    /// Read the saved history. A missing or unreadable file starts an empty one, since
+    /// losing history is no reason to refuse to start.
+    pub fn load(path: PathBuf, max_entries: usize) -> Self {
+        let entries = fs::read_to_string(&path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            max_entries,
+            entries,
+        }
+    }
+
+    pub fn entries(&self, key: &str) -> Vec<String> {
+        self.entries.get(key).cloned().unwrap_or_default()
+    }
+
+    /// Remember `entry` as the newest for `key` and save. An entry already there moves
+    /// to the end rather than repeating.
+    pub fn add(&mut self, key: &str, entry: &str) -> Result<()> {
+        if self.max_entries == 0 || entry.trim().is_empty() {
+            return Ok(());
+        }
+        let entries = self.entries.entry(key.to_string()).or_default();
+        entries.retain(|e| e != entry);
+        entries.push(entry.to_string());
+        let excess = entries.len().saturating_sub(self.max_entries);
+        entries.drain(..excess);
+
+        let json = serde_json::to_string_pretty(&self.entries)
+            .map_err(|e| PmanError::Config(format!("could not encode history: {}", e)))?;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Write beside the old history and swap, so a crash never leaves half a file
+        let partial = self.path.with_extension("json.partial");
+        fs::write(&partial, json)?;
+        fs::rename(&partial, &self.path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::os::unix::fs::symlink;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::config::HooksConfig;
+use crate::error::Result;
+use crate::integrations::{shell_quote, worktree_session, GitBackend, TmuxBackend};
+
+/// Window that setup commands run in.
+pub const SETUP_WINDOW: &str = "setup";
+
+/// Prepares a freshly created worktree with the configured `[hooks]`.
+pub struct WorktreeHooks {
+    tmux: Rc<dyn TmuxBackend>,
+    hooks: HooksConfig,
+}
+
+impl WorktreeHooks {
+    pub fn new(tmux: Rc<dyn TmuxBackend>, hooks: HooksConfig) -> Self {
+        Self { tmux, hooks }
+    }
+
+    /// Run the hooks for a worktree `git` just created, in the worktree's session,
+    /// creating the session if needed.
+    ///
+    /// Setup runs in the background. Returns a shell command that waits for it to finish,
+    /// to put before anything that needs it, or `None` if there is nothing to wait for.
+    pub fn run_for(&self, git: &dyn GitBackend, worktree: &Path) -> Result<Option<String>> {
+        if self.hooks.is_empty() {
+            return Ok(None);
+        }
+        let main_worktree = git.main_worktree()?;
+
+        let session = worktree_session(self.tmux.as_ref(), git, worktree)?;
+
+        self.run(&main_worktree, worktree, &session)?;
+        Ok(Some(format!("tmux wait-for {}", shell_quote(&setup_channel(&session)))))
+    }
+
+    /// Copy and link files from `main_worktree` into `worktree`, then start the setup
+    /// commands in a window of `session`.
+    ///
+    /// Failures are reported in the setup window rather than returned, so a broken hook
+    /// never costs the worktree.
+    fn run(&self, main_worktree: &Path, worktree: &Path, session: &str) -> Result<()> {
+        let mut problems = Vec::new();
+        for path in &self.hooks.copy {
+            if let Err(e) = copy_recursive(&main_worktree.join(path), &worktree.join(path)) {
+                problems.push(format!("could not copy {}: {}", path.display(), e));
+            }
+        }
+        for path in &self.hooks.symlink {
+            let target = main_worktree.join(path);
+            let link = worktree.join(path);
+            let result = if target.exists() {
+                create_parent(&link).and_then(|_| symlink(&target, &link))
+            } else {
+                Err(io::Error::new(io::ErrorKind::NotFound, "not in the main worktree"))
+            };
+            if let Err(e) = result {
+                problems.push(format!("could not link {}: {}", path.display(), e));
+            }
+        }
+
+        let script = self.setup_script(&problems, &setup_channel(session));
+        let command = format!("sh -c {}", shell_quote(&script));
+        self.tmux.new_window_remain_on_exit(
+            session,
+            SETUP_WINDOW,
+            worktree,
+            &command,
+            &BTreeMap::new(),
+        )?;
+
+        Ok(())
+    }
+
+    /// Shell script that reports `problems`, runs the setup commands, prints the final
+    /// exit status and signals `channel`.
+    fn setup_script(&self, problems: &[String], channel: &str) -> String {
+        let mut script = Vec::new();
+        for problem in problems {
+            script.push(format!("echo {}", shell_quote(&format!("pman: {}", problem))));
+        }
+
+        if self.hooks.setup.is_empty() {
+            script.push("status=0".to_string());
+        } else {
+            // Each command in a subshell of its own, so `;` or `||` in one can't break out
+            // of the chain, and on a line of its own, so a trailing comment can't swallow
+            // the `)`
+            let steps: Vec<String> = self
+                .hooks
+                .setup
+                .iter()
+                .map(|cmd| format!("echo {} && (\n{}\n)", shell_quote(&format!("$ {}", cmd)), cmd))
+                .collect();
+            script.push(format!("( {} )", steps.join(" && ")));
+            script.push("status=$?".to_string());
+        }
+
+        if !problems.is_empty() {
+            script.push("[ $status -eq 0 ] && status=1".to_string());
+        }
+        script.push(r#"echo; echo "pman: setup finished with exit status $status""#.to_string());
+        // A signal nobody waits for yet is kept, so late waiters don't hang
+        script.push(format!("tmux wait-for -S {}", shell_quote(channel)));
+        script.push("exit $status".to_string());
+
+        script.join("\n")
+    }
+}
+
+/// `tmux wait-for` channel the setup of `session` signals when it finishes.
+fn setup_channel(session: &str) -> String {
+    format!("pman-setup-{}", session)
+}
+
+fn create_parent(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) => fs::create_dir_all(parent),
+        None => Ok(()),
+    }
+}
+
+fn copy_recursive(from: &Path, to: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(from)?;
+    create_parent(to)?;
+
+    if metadata.file_type().is_symlink() {
+        symlink(fs::read_link(from)?, to)
+    } else if metadata.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        fs::copy(from, to).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::*;
+    use crate::integrations::fake::FakeTmux;
+
+    /// Run the setup script for `setup` and return its output and exit status.
+    fn run_setup(setup: &[&str]) -> (String, i32) {
+        let hooks = HooksConfig {
+            setup: setup.iter().map(|s| s.to_string()).collect(),
+            ..HooksConfig::default()
+        };
+        let script = WorktreeHooks::new(Rc::new(FakeTmux::default()), hooks)
+            .setup_script(&[], "pman-setup-test");
+
+        // Nobody waits on the channel here
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(format!("tmux() {{ :; }}\n{}", script))
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        (stdout, output.status.code().unwrap())
+    }
+
+    #[test]
+    fn setup_stops_at_the_first_failing_command() {
+        let (output, status) = run_setup(&["false", "echo a; echo b"]);
+
+        assert_eq!(status, 1, "{}", output);
+        assert_eq!(output, "$ false\n\npman: setup finished with exit status 1\n");
+    }
+
+    #[test]
+    fn each_command_keeps_its_own_operators_and_comments() {
+        let (output, status) = run_setup(&["false || echo recovered # fine", "echo done"]);
+
+        assert_eq!(status, 0, "{}", output);
+        assert!(output.contains("recovered\n$ echo done\ndone\n"), "{}", output);
+    }
+}
@@ -1,7 +1,24 @@
+mod activity;
+mod history;
+mod hooks;
+mod snapshot;
 mod tmux;
+mod trust;
 mod git;
 mod nvim;
+mod nvim_rpc;
+#[cfg(test)]
+pub mod fake;
 
-pub use tmux::TmuxClient;
-pub use git::GitClient;
-pub use nvim::NvimIntegration;
+pub use activity::ActivityMonitor;
+pub use history::InputHistory;
+pub use hooks::{WorktreeHooks, SETUP_WINDOW};
+pub use snapshot::SnapshotStore;
+pub use tmux::{
+    find_worktree_session, shell_quote, worktree_session, worktree_session_name, TmuxBackend,
+    TmuxClient,
+};
+pub use git::{GitBackend, GitClient};
+pub use trust::TrustStore;
+pub use nvim::{nvim_command, EditorBackend, NvimIntegration};
+pub use nvim_rpc::NvimRpc;
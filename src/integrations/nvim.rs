@@ -1,163 +1,220 @@
+use std::fs;
+use std::os::unix::net::UnixStream;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::rc::Rc;
 
-use crate::error::{PmanError, Result};
-use crate::integrations::TmuxClient;
-use crate::models::NvimBuffer;
+use rmpv::Value;
+
+use crate::error::Result;
+use crate::integrations::{shell_quote, NvimRpc, TmuxBackend};
+use crate::models::{NvimBuffer, NvimInstance};
+
+/// The editor files and buffers are opened in, so App can run against a fake.
+pub trait EditorBackend {
+    /// Open `file_path` in the current session's nvim, starting one if there is none.
+    /// `position` is a 1-based line and column.
+    fn open_file(&self, file_path: &Path, position: Option<(usize, usize)>) -> Result<()>;
+
+    fn open_buffer(&self, nvim: &NvimInstance, bufnr: i64) -> Result<()>;
+
+    /// Listed buffers of every nvim running in tmux, with the nvim each is in.
+    fn list_buffers(&self) -> Result<Vec<(NvimInstance, NvimBuffer)>>;
+}
 
 pub struct NvimIntegration {
-    tmux: TmuxClient,
+    tmux: Rc<dyn TmuxBackend>,
 }
 
 impl NvimIntegration {
-    pub fn new(tmux: TmuxClient) -> Self {
+    pub fn new(tmux: Rc<dyn TmuxBackend>) -> Self {
         Self { tmux }
     }
 
-    pub fn open_file(&self, file_path: &Path) -> Result<()> {
-        let window_id = self.tmux.get_or_create_editor_window()?;
+    /// Every nvim running in a tmux pane that answers on its socket.
+    fn find_instances(&self) -> Result<Vec<NvimInstance>> {
+        remove_dead_pane_sockets();
+        let processes = processes();
 
-        let nvim_cmd = format!("nvim \"{}\"", file_path.display());
-        self.tmux.send_keys(&window_id, &nvim_cmd)?;
-        self.tmux.select_window(&window_id)?;
+        let mut instances: Vec<NvimInstance> = Vec::new();
+        for pane in self.tmux.list_panes()? {
+            if pane.dead {
+                continue;
+            }
+            let pids = nvim_pids(pane.pid, &processes);
+            if pids.is_empty() {
+                continue;
+            }
+
+            // One nvim runs in a pane, though it may be a TUI process and its server
+            let candidates = std::iter::once(pane_socket(&pane.pane_id))
+                .chain(pids.iter().flat_map(|pid| default_sockets(*pid)));
+            let socket = candidates
+                .filter(|socket| !instances.iter().any(|i| i.socket == *socket))
+                .find(|socket| UnixStream::connect(socket).is_ok());
+
+            if let Some(socket) = socket {
+                instances.push(NvimInstance {
+                    socket,
+                    session: pane.session,
+                    window: pane.window_name,
+                    pane_id: pane.pane_id,
+                });
+            }
+        }
 
-        Ok(())
+        Ok(instances)
     }
+}
+
+/// Prefix of the sockets nvims started by pman listen on, followed by the pane id.
+const PANE_SOCKET_PREFIX: &str = "pman-nvim";
+
+/// `nvim args` listening on a socket named after the pane it runs in, so it can be
+/// found without searching.
+///
+/// The pane id is left for the pane's shell to expand, since the pane may not exist yet.
+pub fn nvim_command(args: &str) -> String {
+    let prefix = socket_dir().join(PANE_SOCKET_PREFIX);
+    format!(
+        "nvim --listen {}\"$TMUX_PANE\" {}",
+        shell_quote(&prefix.to_string_lossy()),
+        args
+    )
+}
 
-    pub fn open_buffer(&self, socket: &Path, bufnr: i64) -> Result<()> {
-        // Switch to buffer in nvim
-        let cmd = format!(":buffer {}", bufnr);
-        Command::new("nvim")
-            .args(["--server", socket.to_str().unwrap_or(""), "--remote-send", &cmd])
-            .output()
-            .map_err(|e| PmanError::Nvim(e.to_string()))?;
+fn pane_socket(pane_id: &str) -> PathBuf {
+    socket_dir().join(format!("{}{}", PANE_SOCKET_PREFIX, pane_id))
+}
 
-        // Switch to editor window
-        let _ = self.tmux.get_or_create_editor_window().and_then(|w| self.tmux.select_window(&w));
+fn socket_dir() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+}
 
-        Ok(())
+/// Sockets of pman-started nvims that have exited or crashed.
+fn remove_dead_pane_sockets() {
+    let Ok(entries) = fs::read_dir(socket_dir()) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let ours = entry.file_name().to_string_lossy().starts_with(PANE_SOCKET_PREFIX);
+        if ours && UnixStream::connect(&path).is_err() {
+            let _ = fs::remove_file(&path);
+        }
     }
+}
 
-    pub fn list_buffers() -> Result<Vec<(PathBuf, NvimBuffer)>> {
-        let sockets = Self::find_nvim_sockets()?;
-        let mut all_buffers = Vec::new();
+/// Where nvim puts the server socket of process `pid` when not told otherwise:
+/// `$XDG_RUNTIME_DIR/nvim.<pid>.0`, or `nvim.<pid>.0` in a random directory under
+/// `$TMPDIR/nvim.$USER` (macOS, or Linux without a runtime dir).
+fn default_sockets(pid: u32) -> Vec<PathBuf> {
+    let name = format!("nvim.{}.0", pid);
+    let mut sockets = Vec::new();
 
-        for socket in sockets {
-            if let Ok(buffers) = Self::get_buffers_from_socket(&socket) {
-                for buf in buffers {
-                    all_buffers.push((socket.clone(), buf));
-                }
-            }
+    if let Some(runtime_dir) = std::env::var_os("XDG_RUNTIME_DIR") {
+        sockets.push(PathBuf::from(runtime_dir).join(&name));
+    }
+    let user_dir = format!("nvim.{}", std::env::var("USER").unwrap_or_default());
+    for tmp in [std::env::temp_dir(), PathBuf::from("/tmp")] {
+        if let Ok(entries) = fs::read_dir(tmp.join(&user_dir)) {
+            sockets.extend(entries.flatten().map(|entry| entry.path().join(&name)));
         }
+    }
 
-        Ok(all_buffers)
+    sockets
+}
+
+/// `(pid, parent pid, executable name)` of every process.
+fn processes() -> Vec<(u32, u32, String)> {
+    let Ok(output) = Command::new("ps").args(["-A", "-o", "pid=,ppid=,comm="]).output() else {
+        return Vec::new();
+    };
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let pid = parts.next()?.parse().ok()?;
+            let ppid = parts.next()?.parse().ok()?;
+            // macOS prints the full path
+            let command = parts.collect::<Vec<_>>().join(" ");
+            let name = Path::new(&command).file_name()?.to_string_lossy().to_string();
+            Some((pid, ppid, name))
+        })
+        .collect()
+}
+
+/// The nvim processes among `root` and its descendants.
+fn nvim_pids(root: u32, processes: &[(u32, u32, String)]) -> Vec<u32> {
+    let mut tree = vec![root];
+    let mut i = 0;
+    while i < tree.len() {
+        let parent = tree[i];
+        let children = processes.iter().filter(|(_, ppid, _)| *ppid == parent);
+        tree.extend(children.map(|(pid, _, _)| *pid));
+        i += 1;
     }
 
-    fn find_nvim_sockets() -> Result<Vec<PathBuf>> {
-        let mut sockets = Vec::new();
-
-        // Check common nvim socket locations
-        let user = std::env::var("USER").unwrap_or_default();
-
-        // macOS: /tmp/nvim.<user>/
-        let tmp_dir = PathBuf::from(format!("/tmp/nvim.{}", user));
-        if tmp_dir.exists() {
-            if let Ok(entries) = std::fs::read_dir(&tmp_dir) {
-                for entry in entries.flatten() {
-                    let path = entry.path();
-                    if path.is_dir() {
-                        if let Ok(inner_entries) = std::fs::read_dir(&path) {
-                            for inner in inner_entries.flatten() {
-                                let inner_path = inner.path();
-                                if inner_path.to_string_lossy().contains("nvim.") {
-                                    sockets.push(inner_path);
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
+    tree.into_iter()
+        .filter(|pid| processes.iter().any(|(p, _, name)| p == pid && name == "nvim"))
+        .collect()
+}
 
-        // Also check XDG_RUNTIME_DIR (Linux)
-        if let Ok(runtime_dir) = std::env::var("XDG_RUNTIME_DIR") {
-            let nvim_dir = PathBuf::from(runtime_dir);
-            if let Ok(entries) = std::fs::read_dir(&nvim_dir) {
-                for entry in entries.flatten() {
-                    let path = entry.path();
-                    if path.to_string_lossy().contains("nvim") {
-                        sockets.push(path);
-                    }
-                }
+impl EditorBackend for NvimIntegration {
+    fn open_file(&self, file_path: &Path, position: Option<(usize, usize)>) -> Result<()> {
+        let session = self.tmux.current_session()?;
+        let nvim = self.find_instances()?.into_iter().find(|n| n.session == session);
+
+        let Some(nvim) = nvim else {
+            // Nothing to talk to: start one in the editor window
+            let window_id = self.tmux.get_or_create_editor_window()?;
+            let mut args = Vec::new();
+            if let Some((line, column)) = position {
+                args.push(shell_quote(&format!("+call cursor({}, {})", line, column)));
             }
+            args.push("--".to_string());
+            args.push(shell_quote(&file_path.to_string_lossy()));
+
+            self.tmux.send_keys(&window_id, &nvim_command(&args.join(" ")))?;
+            return self.tmux.select_window(&window_id);
+        };
+
+        let mut rpc = NvimRpc::connect(&nvim.socket)?;
+        let path = Value::from(file_path.to_string_lossy().as_ref());
+        let escaped = rpc.call_function("fnameescape", vec![path])?;
+        // `:drop` jumps to a window already showing the file instead of opening it twice
+        rpc.command(&format!("drop {}", escaped.as_str().unwrap_or_default()))?;
+        if let Some((line, column)) = position {
+            // cursor() clamps rather than fails, so there is no reply worth waiting for
+            let args = vec![Value::from(line), Value::from(column)];
+            rpc.notify("nvim_call_function", vec![Value::from("cursor"), Value::Array(args)])?;
         }
 
-        Ok(sockets)
+        self.tmux.switch_session(&nvim.pane_id)
     }
 
-    fn get_buffers_from_socket(socket: &Path) -> Result<Vec<NvimBuffer>> {
-        let expr = r#"json_encode(map(getbufinfo({'buflisted': 1}), {_, v -> {'bufnr': v.bufnr, 'name': v.name, 'changed': v.changed}}))"#;
-
-        let output = Command::new("nvim")
-            .args([
-                "--server",
-                socket.to_str().unwrap_or(""),
-                "--remote-expr",
-                expr,
-            ])
-            .output()
-            .map_err(|e| PmanError::Nvim(e.to_string()))?;
-
-        if !output.status.success() {
-            return Err(PmanError::Nvim("Failed to get buffers".to_string()));
-        }
-
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        Self::parse_buffer_json(&stdout)
+    fn open_buffer(&self, nvim: &NvimInstance, bufnr: i64) -> Result<()> {
+        NvimRpc::connect(&nvim.socket)?.command(&format!("buffer {}", bufnr))?;
+        // Targeting the pane brings its session, window and pane to the front
+        self.tmux.switch_session(&nvim.pane_id)
     }
 
-    fn parse_buffer_json(json: &str) -> Result<Vec<NvimBuffer>> {
-        // Simple JSON parsing without external crate
-        let json = json.trim();
-        if !json.starts_with('[') || !json.ends_with(']') {
-            return Ok(Vec::new());
-        }
+    fn list_buffers(&self) -> Result<Vec<(NvimInstance, NvimBuffer)>> {
+        let mut all_buffers = Vec::new();
 
-        let mut buffers = Vec::new();
-        let inner = &json[1..json.len() - 1];
-
-        // Split by },{ to get individual objects
-        for obj_str in inner.split("},{") {
-            let obj_str = obj_str.trim_start_matches('{').trim_end_matches('}');
-
-            let mut bufnr: i64 = 0;
-            let mut name = String::new();
-            let mut changed = false;
-
-            for part in obj_str.split(',') {
-                let part = part.trim();
-                if let Some((key, value)) = part.split_once(':') {
-                    let key = key.trim().trim_matches('"');
-                    let value = value.trim();
-
-                    match key {
-                        "bufnr" => bufnr = value.parse().unwrap_or(0),
-                        "name" => name = value.trim_matches('"').to_string(),
-                        "changed" => changed = value == "1" || value == "true",
-                        _ => {}
-                    }
+        for nvim in self.find_instances()? {
+            // An nvim busy at a prompt times out; its buffers are left out
+            let buffers = NvimRpc::connect(&nvim.socket).and_then(|mut rpc| rpc.listed_buffers());
+            if let Ok(buffers) = buffers {
+                for buf in buffers {
+                    all_buffers.push((nvim.clone(), buf));
                 }
             }
-
-            if !name.is_empty() {
-                buffers.push(NvimBuffer {
-                    bufnr,
-                    name: PathBuf::from(name),
-                    modified: changed,
-                });
-            }
         }
 
-        Ok(buffers)
+        Ok(all_buffers)
     }
 }
@@ -0,0 +1,340 @@
+use std::io::{BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::time::Duration;
+
+use rmpv::Value;
+
+use crate::error::{PmanError, Result};
+use crate::models::NvimBuffer;
+
+/// How long to wait on an nvim that has stopped answering, e.g. one stuck at a prompt.
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+const REQUEST: u64 = 0;
+const RESPONSE: u64 = 1;
+const NOTIFICATION: u64 = 2;
+
+/// A msgpack-RPC connection to a running nvim's `--listen` socket.
+pub struct NvimRpc {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+    next_id: u64,
+}
+
+impl NvimRpc {
+    pub fn connect(socket: &Path) -> Result<Self> {
+        let stream = UnixStream::connect(socket)
+            .map_err(|e| PmanError::Nvim(format!("{}: {}", socket.display(), e)))?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+
+        Ok(Self {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+            next_id: 0,
+        })
+    }
+
+    /// Call an API method and wait for its result.
+    pub fn call(&mut self, method: &str, params: Vec<Value>) -> Result<Value> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(vec![
+            Value::from(REQUEST),
+            Value::from(id),
+            Value::from(method),
+            Value::Array(params),
+        ])?;
+
+        loop {
+            let message = self.receive()?;
+            match message.as_slice() {
+                [kind, msg_id, error, result]
+                    if kind.as_u64() == Some(RESPONSE) && msg_id.as_u64() == Some(id) =>
+                {
+                    if !error.is_nil() {
+                        return Err(PmanError::Nvim(format!(
+                            "{}: {}",
+                            method,
+                            error_message(error)
+                        )));
+                    }
+                    return Ok(result.clone());
+                }
+                // Notifications (e.g. UI events), requests from nvim and stale responses
+                // aren't ours to handle
+                _ => {}
+            }
+        }
+    }
+
+    /// Send a notification: nvim runs it without replying, even on error.
+    pub fn notify(&mut self, method: &str, params: Vec<Value>) -> Result<()> {
+        self.send(vec![
+            Value::from(NOTIFICATION),
+            Value::from(method),
+            Value::Array(params),
+        ])
+    }
+
+    /// Handles of every buffer, listed or not.
+    pub fn list_bufs(&mut self) -> Result<Vec<i64>> {
+        let bufs = self.call("nvim_list_bufs", Vec::new())?;
+        bufs.as_array()
+            .ok_or_else(|| unexpected("nvim_list_bufs", &bufs))?
+            .iter()
+            .map(|buf| handle(buf).ok_or_else(|| unexpected("nvim_list_bufs", buf)))
+            .collect()
+    }
+
+    pub fn buf_get_name(&mut self, buf: i64) -> Result<String> {
+        let name = self.call("nvim_buf_get_name", vec![Value::from(buf)])?;
+        name.as_str()
+            .map(str::to_string)
+            .ok_or_else(|| unexpected("nvim_buf_get_name", &name))
+    }
+
+    /// A buffer-local option's value.
+    pub fn get_option_value(&mut self, name: &str, buf: i64) -> Result<Value> {
+        let opts = Value::Map(vec![(Value::from("buf"), Value::from(buf))]);
+        self.call("nvim_get_option_value", vec![Value::from(name), opts])
+    }
+
+    /// Run an Ex command, e.g. `buffer 3`.
+    pub fn command(&mut self, command: &str) -> Result<()> {
+        self.call("nvim_command", vec![Value::from(command)])?;
+        Ok(())
+    }
+
+    pub fn call_function(&mut self, name: &str, args: Vec<Value>) -> Result<Value> {
+        self.call("nvim_call_function", vec![Value::from(name), Value::Array(args)])
+    }
+
+    /// The buffers `:ls` shows, skipping unnamed ones.
+    pub fn listed_buffers(&mut self) -> Result<Vec<NvimBuffer>> {
+        let mut buffers = Vec::new();
+        for buf in self.list_bufs()? {
+            if self.get_option_value("buflisted", buf)?.as_bool() != Some(true) {
+                continue;
+            }
+            let name = self.buf_get_name(buf)?;
+            if name.is_empty() {
+                continue;
+            }
+            let modified = self.get_option_value("modified", buf)?.as_bool() == Some(true);
+            buffers.push(NvimBuffer {
+                bufnr: buf,
+                name: name.into(),
+                modified,
+            });
+        }
+        Ok(buffers)
+    }
+
+    fn send(&mut self, message: Vec<Value>) -> Result<()> {
+        let mut bytes = Vec::new();
+        rmpv::encode::write_value(&mut bytes, &Value::Array(message))
+            .map_err(|e| PmanError::Nvim(e.to_string()))?;
+        self.writer.write_all(&bytes)?;
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Result<Vec<Value>> {
+        let message = rmpv::decode::read_value(&mut self.reader)
+            .map_err(|e| PmanError::Nvim(format!("could not read from nvim: {}", e)))?;
+        match message {
+            Value::Array(parts) => Ok(parts),
+            other => Err(unexpected("message", &other)),
+        }
+    }
+}
+
+/// A buffer handle: a msgpack extension wrapping the number, or the bare number.
+fn handle(value: &Value) -> Option<i64> {
+    match value {
+        Value::Ext(_, data) => rmpv::decode::read_value(&mut data.as_slice()).ok()?.as_i64(),
+        other => other.as_i64(),
+    }
+}
+
+/// nvim reports errors as `[type, message]`.
+fn error_message(error: &Value) -> String {
+    match error.as_array().and_then(|e| e.get(1)).and_then(Value::as_str) {
+        Some(message) => message.to_string(),
+        None => error.to_string(),
+    }
+}
+
+fn unexpected(what: &str, value: &Value) -> PmanError {
+    PmanError::Nvim(format!("unexpected {} from nvim: {}", what, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::UnixListener;
+    use std::path::PathBuf;
+    use std::thread::{self, JoinHandle};
+
+    use super::*;
+
+    /// An nvim stand-in that answers each request with `respond` and hands back every
+    /// message it received once the client disconnects.
+    fn fake_nvim<F>(respond: F) -> (tempfile::TempDir, PathBuf, JoinHandle<Vec<Value>>)
+    where
+        F: Fn(&str, &[Value]) -> Vec<Value> + Send + 'static,
+    {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("nvim.sock");
+        let listener = UnixListener::bind(&socket).unwrap();
+
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut writer = stream;
+            let mut received = Vec::new();
+
+            while let Ok(message) = rmpv::decode::read_value(&mut reader) {
+                received.push(message.clone());
+                let parts = message.as_array().unwrap();
+                if parts[0].as_u64() != Some(REQUEST) {
+                    continue;
+                }
+                let method = parts[2].as_str().unwrap();
+                let params = parts[3].as_array().unwrap();
+                for reply in respond(method, params) {
+                    let reply = match reply {
+                        // Fill in the request's id
+                        Value::Array(mut reply) if reply[0].as_u64() == Some(RESPONSE) => {
+                            reply[1] = parts[1].clone();
+                            Value::Array(reply)
+                        }
+                        other => other,
+                    };
+                    rmpv::encode::write_value(&mut writer, &reply).unwrap();
+                }
+            }
+            received
+        });
+
+        (dir, socket, server)
+    }
+
+    fn ok(result: Value) -> Value {
+        Value::Array(vec![Value::from(RESPONSE), Value::Nil, Value::Nil, result])
+    }
+
+    fn buffer_ext(bufnr: i64) -> Value {
+        let mut data = Vec::new();
+        rmpv::encode::write_value(&mut data, &Value::from(bufnr)).unwrap();
+        Value::Ext(0, data)
+    }
+
+    #[test]
+    fn lists_only_named_listed_buffers() {
+        let (_dir, socket, server) = fake_nvim(|method, params| {
+            let buf = params.first().and_then(Value::as_i64).unwrap_or(0);
+            let option = params.first().and_then(Value::as_str).unwrap_or_default();
+            let opts_buf = params
+                .get(1)
+                .and_then(Value::as_map)
+                .and_then(|m| m[0].1.as_i64())
+                .unwrap_or(0);
+            let result = match method {
+                "nvim_list_bufs" => Value::Array((1..=4).map(buffer_ext).collect()),
+                "nvim_buf_get_name" => Value::from(match buf {
+                    1 => "/src/{weird},name.rs",
+                    3 => "",
+                    _ => "/src/main.rs",
+                }),
+                "nvim_get_option_value" if option == "buflisted" => Value::from(opts_buf != 2),
+                "nvim_get_option_value" => Value::from(opts_buf == 4),
+                _ => unreachable!("{}", method),
+            };
+            vec![ok(result)]
+        });
+
+        let buffers = NvimRpc::connect(&socket).unwrap().listed_buffers().unwrap();
+        server.join().unwrap();
+
+        let listed: Vec<(i64, String, bool)> = buffers
+            .into_iter()
+            .map(|b| (b.bufnr, b.name.display().to_string(), b.modified))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                (1, "/src/{weird},name.rs".to_string(), false),
+                (4, "/src/main.rs".to_string(), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn command_sends_the_ex_command() {
+        let (_dir, socket, server) = fake_nvim(|_, _| vec![ok(Value::Nil)]);
+
+        NvimRpc::connect(&socket).unwrap().command("buffer 3").unwrap();
+        let received = server.join().unwrap();
+
+        assert_eq!(received.len(), 1);
+        let request = received[0].as_array().unwrap();
+        assert_eq!(request[2].as_str(), Some("nvim_command"));
+        assert_eq!(request[3], Value::Array(vec![Value::from("buffer 3")]));
+    }
+
+    #[test]
+    fn errors_carry_nvim_message() {
+        let (_dir, socket, server) = fake_nvim(|_, _| {
+            let message = Value::from("E86: Buffer 9 does not exist");
+            let error = Value::Array(vec![Value::from(0), message]);
+            vec![Value::Array(vec![Value::from(RESPONSE), Value::Nil, error, Value::Nil])]
+        });
+
+        let result = NvimRpc::connect(&socket).unwrap().command("buffer 9");
+        server.join().unwrap();
+
+        match result {
+            Err(PmanError::Nvim(message)) => {
+                assert_eq!(message, "nvim_command: E86: Buffer 9 does not exist")
+            }
+            other => panic!("expected an nvim error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn notifications_before_the_response_are_skipped() {
+        let (_dir, socket, server) = fake_nvim(|_, _| {
+            let notification = Value::Array(vec![
+                Value::from(NOTIFICATION),
+                Value::from("redraw"),
+                Value::Array(vec![Value::from("flush")]),
+            ]);
+            vec![notification, ok(Value::from("/src/lib.rs"))]
+        });
+
+        let mut rpc = NvimRpc::connect(&socket).unwrap();
+        assert_eq!(rpc.buf_get_name(1).unwrap(), "/src/lib.rs");
+        drop(rpc);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn notify_does_not_wait_for_a_reply() {
+        let (_dir, socket, server) = fake_nvim(|_, _| Vec::new());
+
+        let mut rpc = NvimRpc::connect(&socket).unwrap();
+        rpc.notify("nvim_command", vec![Value::from("redraw")]).unwrap();
+        drop(rpc);
+        let received = server.join().unwrap();
+
+        assert_eq!(
+            received,
+            [Value::Array(vec![
+                Value::from(NOTIFICATION),
+                Value::from("nvim_command"),
+                Value::Array(vec![Value::from("redraw")]),
+            ])]
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::{PmanError, Result};
+use crate::integrations::{GitClient, TmuxBackend, TmuxClient};
+use crate::models::{Snapshot, SNAPSHOT_VERSION};
+
+/// Saves tmux sessions to a JSON file and recreates them from it.
+pub struct SnapshotStore {
+    tmux: TmuxClient,
+    path: PathBuf,
+}
+
+/// What `SnapshotStore::restore` did with each session.
+#[derive(Debug, Default)]
+pub struct RestoreReport {
+    pub restored: Vec<String>,
+    /// Session names with the reason they were left alone
+    pub skipped: Vec<(String, String)>,
+}
+
+impl SnapshotStore {
+    pub fn new(tmux: TmuxClient, path: PathBuf) -> Self {
+        Self { tmux, path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Capture every session and replace the saved snapshot with it.
+    ///
+    /// An empty server is never saved, so a snapshot taken after the sessions are gone
+    /// can't overwrite the one that would bring them back.
+    pub fn save(&self) -> Result<Snapshot> {
+        let mut sessions = self.tmux.snapshot_sessions()?;
+        if sessions.is_empty() {
+            return Err(PmanError::Tmux(format!(
+                "no sessions to snapshot; keeping {}",
+                self.path.display()
+            )));
+        }
+        for session in &mut sessions {
+            session.worktree = session.path.as_deref().and_then(GitClient::linked_worktree_of);
+        }
+
+        let snapshot = Snapshot {
+            version: SNAPSHOT_VERSION,
+            created: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            sessions,
+        };
+        let json = serde_json::to_string_pretty(&snapshot)
+            .map_err(|e| PmanError::Config(format!("could not encode snapshot: {}", e)))?;
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Write beside the old snapshot and swap, so a crash never leaves half a file
+        let partial = self.path.with_extension("json.partial");
+        fs::write(&partial, json)?;
+        fs::rename(&partial, &self.path)?;
+
+        Ok(snapshot)
+    }
+
+    pub fn load(&self) -> Result<Snapshot> {
+        let json = fs::read_to_string(&self.path).map_err(|e| {
+            PmanError::Config(format!("could not read {}: {}", self.path.display(), e))
+        })?;
+        let snapshot: Snapshot = serde_json::from_str(&json)
+            .map_err(|e| PmanError::Config(format!("{}: {}", self.path.display(), e)))?;
+
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(PmanError::Config(format!(
+                "{}: snapshot version {} is not supported (expected {})",
+                self.path.display(),
+                snapshot.version,
+                SNAPSHOT_VERSION
+            )));
+        }
+
+        Ok(snapshot)
+    }
+
+    /// Recreate the saved sessions that aren't running, running saved commands again only
+    /// for the `replay` programs. Sessions whose worktree has been removed since are skipped.
+    pub fn restore(&self, snapshot: &Snapshot, replay: &[String]) -> Result<RestoreReport> {
+        let running: Vec<String> =
+            self.tmux.list_sessions()?.into_iter().map(|s| s.name).collect();
+
+        let mut report = RestoreReport::default();
+        for session in &snapshot.sessions {
+            if running.contains(&session.name) {
+                report.skipped.push((session.name.clone(), "already running".to_string()));
+                continue;
+            }
+            if let Some(ref worktree) = session.worktree {
+                if !worktree.is_dir() {
+                    let reason = format!("worktree {} no longer exists", worktree.display());
+                    report.skipped.push((session.name.clone(), reason));
+                    continue;
+                }
+            }
+
+            self.tmux.restore_session(session, replay)?;
+            report.restored.push(session.name.clone());
+        }
+
+        Ok(report)
+    }
+
+    /// Whether the saved snapshot is missing or older than `interval`.
+    pub fn is_older_than(&self, interval: Duration) -> bool {
+        fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_none_or(|age| age >= interval)
+    }
+}
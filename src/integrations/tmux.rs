@@ -1,22 +1,357 @@
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
+use crate::config::{Config, PaneLayout, SessionLayout, SplitDirection};
 use crate::error::{PmanError, Result};
-use crate::models::TmuxSession;
+use crate::integrations::git::branch_slug;
+use crate::integrations::GitBackend;
+use crate::models::{
+    AgentStatus, PaneSnapshot, SessionSnapshot, TmuxPane, TmuxSession, WindowSnapshot,
+};
 
-pub struct TmuxClient;
+/// Everything pman asks of tmux, so App and the components can run against a fake.
+pub trait TmuxBackend {
+    fn list_sessions(&self) -> Result<Vec<TmuxSession>>;
+
+    /// Every pane on the server, across all sessions.
+    fn list_panes(&self) -> Result<Vec<TmuxPane>>;
+
+    /// The last `lines` lines of a pane's visible content and scrollback.
+    fn capture_pane(&self, target: &str, lines: usize) -> Result<String>;
+
+    /// Like `capture_pane`, but keeps the ANSI escape sequences for colors and attributes.
+    fn capture_pane_ansi(&self, target: &str, lines: usize) -> Result<Vec<u8>>;
+
+    fn switch_session(&self, session_name: &str) -> Result<()>;
+
+    /// Create a detached session, returning its name as tmux reports it: tmux replaces
+    /// `.` and `:` in names.
+    fn create_session(&self, name: &str, path: Option<&PathBuf>) -> Result<String>;
+
+    fn kill_session(&self, session_name: &str) -> Result<()>;
+
+    fn current_session(&self) -> Result<String>;
+
+    fn current_path(&self) -> Result<PathBuf>;
+
+    /// Create a window in `session` running `command`, returning the new window id.
+    fn new_window(
+        &self,
+        session: &str,
+        name: &str,
+        path: &Path,
+        command: &str,
+        env: &BTreeMap<String, String>,
+    ) -> Result<String>;
+
+    /// Like `new_window`, but the window stays open with its output and exit status
+    /// after `command` exits.
+    fn new_window_remain_on_exit(
+        &self,
+        session: &str,
+        name: &str,
+        path: &Path,
+        command: &str,
+        env: &BTreeMap<String, String>,
+    ) -> Result<String>;
+
+    /// Set a user option (`@name`) on a session.
+    fn set_session_option(&self, session: &str, option: &str, value: &str) -> Result<()>;
+
+    /// Turn the single window of a fresh `session` into the windows and panes of `layout`.
+    /// Relative roots are resolved against `root`, the session's directory.
+    fn apply_layout(&self, session: &str, root: &Path, layout: &SessionLayout) -> Result<()>;
+
+    fn get_or_create_editor_window(&self) -> Result<String>;
+
+    fn send_keys(&self, target: &str, keys: &str) -> Result<()>;
+
+    fn select_window(&self, window_id: &str) -> Result<()>;
+
+    fn popup_command(&self, command: &str, width: &str, height: &str) -> Result<()>;
+}
+
+#[derive(Clone)]
+pub struct TmuxClient {
+    editor_window: String,
+}
 
 impl TmuxClient {
-    pub fn new() -> Self {
-        Self
+    pub fn new(config: &Config) -> Self {
+        Self {
+            editor_window: config.tmux.editor_window.clone(),
+        }
+    }
+
+    fn capture(&self, target: &str, lines: usize, ansi: bool) -> Result<Vec<u8>> {
+        let start = format!("-{}", lines);
+        let mut args = vec!["capture-pane", "-p", "-J", "-t", target, "-S", &start];
+        if ansi {
+            args.push("-e");
+        }
+
+        let output = Command::new("tmux")
+            .args(&args)
+            .output()
+            .map_err(|e| PmanError::Tmux(e.to_string()))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(PmanError::Tmux(stderr.to_string()));
+        }
+
+        Ok(output.stdout)
+    }
+
+    /// Windows and panes of every session, without the `worktree` they belong to.
+    pub fn snapshot_sessions(&self) -> Result<Vec<SessionSnapshot>> {
+        let sessions = self.list_sessions()?;
+        if sessions.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let windows = self.run(&[
+            "list-windows",
+            "-a",
+            "-F",
+            "#{session_name}\t#{window_index}\t#{window_name}\t#{window_layout}\t#{window_active}",
+        ])?;
+        let panes = self.run(&[
+            "list-panes",
+            "-a",
+            "-F",
+            "#{session_name}\t#{window_index}\t#{pane_current_path}\t#{pane_pid}\t#{pane_dead}\t#{pane_active}",
+        ])?;
+
+        let mut snapshots: Vec<SessionSnapshot> = sessions
+            .into_iter()
+            .map(|session| SessionSnapshot {
+                name: session.name,
+                path: session.path,
+                worktree: None,
+                agent: session.agent,
+                windows: Vec::new(),
+            })
+            .collect();
+
+        let mut window_index = BTreeMap::new();
+        for line in windows.lines() {
+            let parts: Vec<&str> = line.split('\t').collect();
+            if parts.len() < 5 {
+                continue;
+            }
+            let Some(session) = snapshots.iter_mut().find(|s| s.name == parts[0]) else {
+                continue;
+            };
+            window_index.insert((parts[0], parts[1]), session.windows.len());
+            session.windows.push(WindowSnapshot {
+                name: parts[2].to_string(),
+                layout: parts[3].to_string(),
+                active: parts[4] == "1",
+                panes: Vec::new(),
+            });
+        }
+
+        for line in panes.lines() {
+            let parts: Vec<&str> = line.split('\t').collect();
+            if parts.len() < 6 {
+                continue;
+            }
+            let Some(&index) = window_index.get(&(parts[0], parts[1])) else {
+                continue;
+            };
+            let Some(session) = snapshots.iter_mut().find(|s| s.name == parts[0]) else {
+                continue;
+            };
+            let dead = parts[4] == "1";
+            session.windows[index].panes.push(PaneSnapshot {
+                path: PathBuf::from(parts[2]),
+                command: if dead { None } else { foreground_command(parts[3]) },
+                active: parts[5] == "1",
+            });
+        }
+
+        Ok(snapshots)
+    }
+
+    /// Recreate a session from a snapshot, re-typing a pane's command into its new shell
+    /// only when it runs one of the `replay` programs. Anything else, say `make deploy`,
+    /// is not run again on its own.
+    pub fn restore_session(&self, session: &SessionSnapshot, replay: &[String]) -> Result<()> {
+        let session_dir = session.path.as_deref().filter(|p| p.is_dir());
+        self.create_session(&session.name, session_dir.map(Path::to_path_buf).as_ref())?;
+
+        let mut active_window = None;
+        for (i, window) in session.windows.iter().enumerate() {
+            let pane_dir = |pane: &PaneSnapshot| {
+                Some(pane.path.as_path())
+                    .filter(|p| p.is_dir())
+                    .or(session_dir)
+                    .map(Path::to_path_buf)
+                    .unwrap_or_default()
+            };
+            let Some(first_pane) = window.panes.first() else {
+                continue;
+            };
+
+            let (window_id, pane_id) = self.open_window(
+                &session.name,
+                i == 0,
+                session_dir.unwrap_or(Path::new("")),
+                &pane_dir(first_pane),
+            )?;
+            self.run(&["rename-window", "-t", &window_id, &window.name])?;
+
+            let mut pane_ids = vec![pane_id];
+            for pane in &window.panes[1..] {
+                let previous = &pane_ids[pane_ids.len() - 1];
+                pane_ids.push(self.split_pane(previous, "-v", None, &pane_dir(pane))?);
+            }
+            // The window may be a different size than when it was saved
+            if self.run(&["select-layout", "-t", &window_id, &window.layout]).is_err() {
+                self.run(&["select-layout", "-t", &window_id, "tiled"])?;
+            }
+
+            for (pane_id, pane) in pane_ids.iter().zip(&window.panes) {
+                if let Some(command) = pane.command.as_deref().filter(|c| replays(c, replay)) {
+                    self.send_keys(pane_id, command)?;
+                }
+                if pane.active {
+                    self.run(&["select-pane", "-t", pane_id])?;
+                }
+            }
+            if window.active || active_window.is_none() {
+                active_window = Some(window_id);
+            }
+        }
+
+        if let Some(window_id) = active_window {
+            self.select_window(&window_id)?;
+        }
+        if let Some(ref agent) = session.agent {
+            self.set_session_option(&session.name, "@pman_agent", agent)?;
+        }
+
+        Ok(())
+    }
+
+    /// The first window of a fresh session, moved to `dir`, or else a new window in `dir`.
+    /// Returns the window and pane ids.
+    fn open_window(
+        &self,
+        session: &str,
+        reuse_first: bool,
+        session_dir: &Path,
+        dir: &Path,
+    ) -> Result<(String, String)> {
+        let target = format!("{}:", session);
+        let ids = if reuse_first {
+            let ids = self.run(&["display-message", "-p", "-t", &target, IDS_FORMAT])?;
+            if dir != session_dir && !dir.as_os_str().is_empty() {
+                let (_, pane_id) = split_ids(&ids)?;
+                self.run(&["respawn-pane", "-k", "-t", pane_id, "-c", &path_arg(dir)])?;
+            }
+            ids
+        } else {
+            let mut args = vec!["new-window", "-d", "-t", &target, "-P", "-F", IDS_FORMAT];
+            let dir = path_arg(dir);
+            if !dir.is_empty() {
+                args.extend(["-c", &dir]);
+            }
+            self.run(&args)?
+        };
+
+        let (window_id, pane_id) = split_ids(&ids)?;
+        Ok((window_id.to_string(), pane_id.to_string()))
     }
 
-    pub fn list_sessions(&self) -> Result<Vec<TmuxSession>> {
+    /// Split `pane` to the right (`-h`) or below (`-v`), returning the new pane's id.
+    fn split_pane(
+        &self,
+        pane: &str,
+        direction: &str,
+        size: Option<&str>,
+        dir: &Path,
+    ) -> Result<String> {
+        let dir = path_arg(dir);
+        let mut args = vec!["split-window", "-d", direction, "-t", pane, "-P", "-F", "#{pane_id}"];
+        if let Some(size) = size {
+            args.extend(["-l", size]);
+        }
+        if !dir.is_empty() {
+            args.extend(["-c", &dir]);
+        }
+        self.run(&args)
+    }
+
+    fn run(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("tmux")
+            .args(args)
+            .output()
+            .map_err(|e| PmanError::Tmux(e.to_string()))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(PmanError::Tmux(stderr.to_string()));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    fn create_window(
+        &self,
+        session: &str,
+        name: &str,
+        path: &Path,
+        command: &str,
+        env: &BTreeMap<String, String>,
+        remain_on_exit: bool,
+    ) -> Result<String> {
+        let target = format!("{}:", session);
+        let mut args = vec![
+            "new-window".to_string(),
+            "-t".to_string(),
+            target,
+            "-n".to_string(),
+            name.to_string(),
+            "-c".to_string(),
+            path.to_string_lossy().to_string(),
+            "-P".to_string(),
+            "-F".to_string(),
+            "#{window_id}".to_string(),
+        ];
+        for (key, value) in env {
+            args.push("-e".to_string());
+            args.push(format!("{}={}", key, value));
+        }
+        args.push(command.to_string());
+        if remain_on_exit {
+            // Same command list, so tmux applies it before it can reap a fast command
+            args.extend([";", "set-option", "-w", "remain-on-exit", "on"].map(String::from));
+        }
+
+        let output = Command::new("tmux")
+            .args(&args)
+            .output()
+            .map_err(|e| PmanError::Tmux(e.to_string()))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(PmanError::Tmux(stderr.to_string()));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+}
+
+impl TmuxBackend for TmuxClient {
+    fn list_sessions(&self) -> Result<Vec<TmuxSession>> {
         let output = Command::new("tmux")
             .args([
                 "list-sessions",
                 "-F",
-                "#{session_name}\t#{session_attached}\t#{session_path}\t#{session_windows}\t#{session_created}",
+                "#{session_name}\t#{session_attached}\t#{session_path}\t#{@pman_agent}",
             ])
             .output()
             .map_err(|e| PmanError::Tmux(e.to_string()))?;
@@ -35,7 +370,7 @@ impl TmuxClient {
             .filter(|line| !line.is_empty())
             .filter_map(|line| {
                 let parts: Vec<&str> = line.split('\t').collect();
-                if parts.len() >= 4 {
+                if parts.len() >= 3 {
                     Some(TmuxSession {
                         name: parts[0].to_string(),
                         attached: parts[1] == "1",
@@ -44,8 +379,11 @@ impl TmuxClient {
                         } else {
                             Some(PathBuf::from(parts[2]))
                         },
-                        windows: parts[3].parse().unwrap_or(1),
-                        created: parts.get(4).and_then(|s| s.parse().ok()),
+                        agent: parts
+                            .get(3)
+                            .filter(|s| !s.is_empty())
+                            .map(|s| s.to_string()),
+                        status: AgentStatus::Unknown,
                     })
                 } else {
                     None
@@ -56,7 +394,57 @@ impl TmuxClient {
         Ok(sessions)
     }
 
-    pub fn switch_session(&self, session_name: &str) -> Result<()> {
+    fn list_panes(&self) -> Result<Vec<TmuxPane>> {
+        let output = Command::new("tmux")
+            .args([
+                "list-panes",
+                "-a",
+                "-F",
+                "#{session_name}\t#{window_name}\t#{pane_id}\t#{pane_current_command}\t\
+                 #{pane_dead}\t#{window_activity}\t#{pane_dead_status}\t#{pane_pid}\t#{window_id}",
+            ])
+            .output()
+            .map_err(|e| PmanError::Tmux(e.to_string()))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(PmanError::Tmux(stderr.to_string()));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let panes = stdout
+            .lines()
+            .filter_map(|line| {
+                let parts: Vec<&str> = line.split('\t').collect();
+                if parts.len() < 9 {
+                    return None;
+                }
+                Some(TmuxPane {
+                    session: parts[0].to_string(),
+                    window_name: parts[1].to_string(),
+                    pane_id: parts[2].to_string(),
+                    current_command: parts[3].to_string(),
+                    dead: parts[4] == "1",
+                    window_activity: parts[5].parse().unwrap_or(0),
+                    dead_status: parts[6].parse().ok(),
+                    pid: parts[7].parse().unwrap_or(0),
+                    window_id: parts[8].to_string(),
+                })
+            })
+            .collect();
+
+        Ok(panes)
+    }
+
+    fn capture_pane(&self, target: &str, lines: usize) -> Result<String> {
+        self.capture(target, lines, false).map(|bytes| String::from_utf8_lossy(&bytes).to_string())
+    }
+
+    fn capture_pane_ansi(&self, target: &str, lines: usize) -> Result<Vec<u8>> {
+        self.capture(target, lines, true)
+    }
+
+    fn switch_session(&self, session_name: &str) -> Result<()> {
         let output = Command::new("tmux")
             .args(["switch-client", "-t", session_name])
             .output()
@@ -70,8 +458,8 @@ impl TmuxClient {
         Ok(())
     }
 
-    pub fn create_session(&self, name: &str, path: Option<&PathBuf>) -> Result<()> {
-        let mut args = vec!["new-session", "-d", "-s", name];
+    fn create_session(&self, name: &str, path: Option<&PathBuf>) -> Result<String> {
+        let mut args = vec!["new-session", "-d", "-P", "-F", "#{session_name}", "-s", name];
 
         if let Some(p) = path {
             args.push("-c");
@@ -88,10 +476,94 @@ impl TmuxClient {
             return Err(PmanError::Tmux(stderr.to_string()));
         }
 
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    fn apply_layout(&self, session: &str, root: &Path, layout: &SessionLayout) -> Result<()> {
+        let mut first_window = None;
+
+        for window in &layout.windows {
+            let window_root = join_root(root, window.root.as_deref());
+            let pane_root = |pane: Option<&PaneLayout>| {
+                join_root(&window_root, pane.and_then(|p| p.root.as_deref()))
+            };
+            let first_pane = window.panes.first();
+
+            let (window_id, pane_id) = self.open_window(
+                session,
+                first_window.is_none(),
+                root,
+                &pane_root(first_pane),
+            )?;
+            self.run(&["rename-window", "-t", &window_id, &window.name])?;
+
+            let mut panes = vec![(pane_id, first_pane)];
+            for pane in window.panes.iter().skip(1) {
+                let direction = match pane.split {
+                    SplitDirection::Right => "-h",
+                    SplitDirection::Below => "-v",
+                };
+                let previous = &panes[panes.len() - 1].0;
+                let dir = pane_root(Some(pane));
+                let id = self.split_pane(previous, direction, pane.size.as_deref(), &dir)?;
+                panes.push((id, Some(pane)));
+            }
+
+            if let Some(ref name) = window.layout {
+                self.run(&["select-layout", "-t", &window_id, name])?;
+            }
+            for (pane_id, pane) in &panes {
+                if let Some(command) = pane.and_then(|p| p.command.as_deref()) {
+                    self.send_keys(pane_id, command)?;
+                }
+            }
+            first_window.get_or_insert(window_id);
+        }
+
+        if let Some(window_id) = first_window {
+            self.select_window(&window_id)?;
+        }
+
         Ok(())
     }
 
-    pub fn kill_session(&self, session_name: &str) -> Result<()> {
+    fn new_window(
+        &self,
+        session: &str,
+        name: &str,
+        path: &Path,
+        command: &str,
+        env: &BTreeMap<String, String>,
+    ) -> Result<String> {
+        self.create_window(session, name, path, command, env, false)
+    }
+
+    fn new_window_remain_on_exit(
+        &self,
+        session: &str,
+        name: &str,
+        path: &Path,
+        command: &str,
+        env: &BTreeMap<String, String>,
+    ) -> Result<String> {
+        self.create_window(session, name, path, command, env, true)
+    }
+
+    fn set_session_option(&self, session: &str, option: &str, value: &str) -> Result<()> {
+        let output = Command::new("tmux")
+            .args(["set-option", "-t", session, option, value])
+            .output()
+            .map_err(|e| PmanError::Tmux(e.to_string()))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(PmanError::Tmux(stderr.to_string()));
+        }
+
+        Ok(())
+    }
+
+    fn kill_session(&self, session_name: &str) -> Result<()> {
         let output = Command::new("tmux")
             .args(["kill-session", "-t", session_name])
             .output()
@@ -105,7 +577,7 @@ impl TmuxClient {
         Ok(())
     }
 
-    pub fn current_session(&self) -> Result<String> {
+    fn current_session(&self) -> Result<String> {
         let output = Command::new("tmux")
             .args(["display-message", "-p", "#{session_name}"])
             .output()
@@ -119,7 +591,7 @@ impl TmuxClient {
         Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
     }
 
-    pub fn current_path(&self) -> Result<PathBuf> {
+    fn current_path(&self) -> Result<PathBuf> {
         let output = Command::new("tmux")
             .args(["display-message", "-p", "#{pane_current_path}"])
             .output()
@@ -135,8 +607,8 @@ impl TmuxClient {
         ))
     }
 
-    pub fn get_or_create_editor_window(&self) -> Result<String> {
-        // Check if "editor" window exists
+    fn get_or_create_editor_window(&self) -> Result<String> {
+        // Check if the editor window exists
         let output = Command::new("tmux")
             .args([
                 "list-windows",
@@ -149,14 +621,14 @@ impl TmuxClient {
         let stdout = String::from_utf8_lossy(&output.stdout);
         for line in stdout.lines() {
             let parts: Vec<&str> = line.split('\t').collect();
-            if parts.first() == Some(&"editor") {
+            if parts.first() == Some(&self.editor_window.as_str()) {
                 return Ok(parts.get(1).unwrap_or(&"").to_string());
             }
         }
 
         // Create editor window
         let output = Command::new("tmux")
-            .args(["new-window", "-n", "editor", "-P", "-F", "#{window_id}"])
+            .args(["new-window", "-n", &self.editor_window, "-P", "-F", "#{window_id}"])
             .output()
             .map_err(|e| PmanError::Tmux(e.to_string()))?;
 
@@ -168,7 +640,7 @@ impl TmuxClient {
         Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
     }
 
-    pub fn send_keys(&self, target: &str, keys: &str) -> Result<()> {
+    fn send_keys(&self, target: &str, keys: &str) -> Result<()> {
         let output = Command::new("tmux")
             .args(["send-keys", "-t", target, keys, "Enter"])
             .output()
@@ -182,7 +654,7 @@ impl TmuxClient {
         Ok(())
     }
 
-    pub fn select_window(&self, window_id: &str) -> Result<()> {
+    fn select_window(&self, window_id: &str) -> Result<()> {
         let output = Command::new("tmux")
             .args(["select-window", "-t", window_id])
             .output()
@@ -196,7 +668,7 @@ impl TmuxClient {
         Ok(())
     }
 
-    pub fn popup_command(&self, command: &str, width: &str, height: &str) -> Result<()> {
+    fn popup_command(&self, command: &str, width: &str, height: &str) -> Result<()> {
         let output = Command::new("tmux")
             .args([
                 "display-popup",
@@ -220,8 +692,128 @@ impl TmuxClient {
     }
 }
 
+const IDS_FORMAT: &str = "#{window_id}\t#{pane_id}";
+
+fn split_ids(ids: &str) -> Result<(&str, &str)> {
+    ids.split_once('\t')
+        .ok_or_else(|| PmanError::Tmux(format!("unexpected window ids '{}'", ids)))
+}
+
+/// Command line of the foreground process in the pane whose first process is `pane_pid`,
+/// or `None` at a shell prompt.
+fn foreground_command(pane_pid: &str) -> Option<String> {
+    let ps = |args: &[&str]| {
+        Command::new("ps")
+            .args(args)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+            .filter(|output| !output.is_empty())
+    };
+
+    let foreground = ps(&["-o", "tpgid=", "-p", pane_pid])?;
+    if foreground != pane_pid {
+        return ps(&["-o", "args=", "-p", &foreground]);
+    }
+    // A pane started with a command rather than a shell
+    let command = ps(&["-o", "comm=", "-p", pane_pid])?;
+    let shell = Path::new(command.trim_start_matches('-'))
+        .file_name()
+        .is_some_and(|name| SHELLS.iter().any(|shell| name == *shell));
+    if shell {
+        None
+    } else {
+        ps(&["-o", "args=", "-p", pane_pid])
+    }
+}
+
+/// Whether `command` runs one of `programs`, by the name of its executable.
+fn replays(command: &str, programs: &[String]) -> bool {
+    let program = command.split_whitespace().next().unwrap_or_default();
+    let name = Path::new(program).file_name().unwrap_or_default();
+    programs.iter().any(|p| name == p.as_str())
+}
+
+const SHELLS: [&str; 7] = ["sh", "bash", "zsh", "fish", "dash", "ksh", "tcsh"];
+
+fn join_root(base: &Path, root: Option<&Path>) -> PathBuf {
+    match root {
+        Some(root) => base.join(root),
+        None => base.to_path_buf(),
+    }
+}
+
+fn path_arg(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
 impl Default for TmuxClient {
     fn default() -> Self {
-        Self::new()
+        Self::new(&Config::default())
+    }
+}
+
+/// Name for a new session in the worktree of `branch`: the repository and the branch,
+/// e.g. `pman/feature-auth`, so equal branches in two repositories get two sessions.
+/// `.` and `:` become `_`, as tmux would rename the session otherwise.
+pub fn worktree_session_name(main_worktree: &Path, branch: &str) -> String {
+    let repo = main_worktree
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    format!("{}/{}", repo, branch_slug(branch)).replace(['.', ':'], "_")
+}
+
+/// The session whose directory is the worktree at `path`, whatever it is called.
+pub fn find_worktree_session(tmux: &dyn TmuxBackend, path: &Path) -> Result<Option<String>> {
+    Ok(tmux
+        .list_sessions()?
+        .into_iter()
+        .find(|s| s.path.as_deref() == Some(path))
+        .map(|s| s.name))
+}
+
+/// The session for the worktree at `path`, created if there is none yet.
+pub fn worktree_session(
+    tmux: &dyn TmuxBackend,
+    git: &dyn GitBackend,
+    path: &Path,
+) -> Result<String> {
+    if let Some(name) = find_worktree_session(tmux, path)? {
+        return Ok(name);
+    }
+
+    // Detached worktrees are named after their directory instead
+    let branch = git
+        .list_worktrees()?
+        .into_iter()
+        .find(|wt| wt.path == path && wt.branch != "(detached)")
+        .map(|wt| wt.branch)
+        .or_else(|| path.file_name().map(|n| n.to_string_lossy().to_string()))
+        .unwrap_or_default();
+    let name = worktree_session_name(&git.main_worktree()?, &branch);
+    tmux.create_session(&name, Some(&path.to_path_buf()))
+}
+
+/// Quote a string for use as a single word in a POSIX shell command.
+pub fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_listed_programs_are_replayed() {
+        let programs = ["claude".to_string(), "nvim".to_string()];
+
+        assert!(replays("claude --continue", &programs));
+        assert!(replays("/usr/bin/nvim src/main.rs", &programs));
+        assert!(!replays("make deploy", &programs));
+        assert!(!replays("nvimdiff a b", &programs));
+        assert!(!replays("", &programs));
+        assert!(!replays("nvim", &[]));
     }
 }
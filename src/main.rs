@@ -1,6 +1,8 @@
 mod actions;
 mod app;
+mod cli;
 mod components;
+mod config;
 mod error;
 mod integrations;
 mod models;
@@ -12,7 +14,10 @@ use std::path::PathBuf;
 use clap::{Parser, Subcommand};
 
 use app::{App, View};
+use cli::{BufferCommand, SessionCommand, WorktreeCommand};
+use config::Config;
 use error::{PmanError, Result};
+use integrations::{SnapshotStore, TmuxBackend, TmuxClient, TrustStore};
 use tui::terminal::install_panic_hook;
 
 #[derive(Parser)]
@@ -32,46 +37,138 @@ enum Commands {
     CommandPalette,
     /// Open worktree picker
     Worktrees,
-    /// Find files with fzf
+    /// Find files and open them in nvim
     FindFiles,
-    /// Show git diff
+    /// Browse uncommitted changes
     GitDiff,
     /// Install tmux keybindings
     Install,
     /// Uninstall tmux keybindings
     Uninstall,
+    /// Save every tmux session to a snapshot file
+    Snapshot {
+        /// Snapshot file instead of `snapshot.path` from the config
+        #[arg(long)]
+        file: Option<PathBuf>,
+    },
+    /// Recreate sessions from a snapshot file
+    Restore {
+        /// Snapshot file instead of `snapshot.path` from the config
+        #[arg(long)]
+        file: Option<PathBuf>,
+    },
+    /// Allow the nearest .pman.toml to set commands pman runs
+    Trust,
+    /// Manage tmux sessions without the TUI
+    Session {
+        /// Print JSON instead of tab-separated lines
+        #[arg(long, global = true)]
+        json: bool,
+        #[command(subcommand)]
+        command: SessionCommand,
+    },
+    /// Manage git worktrees without the TUI
+    Worktree {
+        /// Print JSON instead of tab-separated lines
+        #[arg(long, global = true)]
+        json: bool,
+        #[command(subcommand)]
+        command: WorktreeCommand,
+    },
+    /// List nvim buffers without the TUI
+    Buffer {
+        /// Print JSON instead of tab-separated lines
+        #[arg(long, global = true)]
+        json: bool,
+        #[command(subcommand)]
+        command: BufferCommand,
+    },
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
 
+    // Project config is looked up from the directory pman was opened in: the script's for
+    // scripting subcommands, the active pane's for popups
+    let scripting = matches!(
+        cli.command,
+        Some(Commands::Session { .. } | Commands::Worktree { .. } | Commands::Buffer { .. })
+    );
+    let current_path = if scripting {
+        std::env::current_dir().ok()
+    } else {
+        TmuxClient::new(&Config::default()).current_path().ok()
+    }
+    .or_else(|| std::env::current_dir().ok())
+    .unwrap_or_else(|| PathBuf::from("."));
+    let config = match Config::load(&current_path) {
+        Ok(config) => config,
+        Err(e) if scripting => cli::exit_with(e),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return Err(e);
+        }
+    };
+
+    if let (true, Some(untrusted)) = (scripting, &config.untrusted) {
+        eprintln!(
+            "pman: ignoring {} in {} until it is trusted with `pman trust`",
+            untrusted.keys.join(", "),
+            untrusted.file.display()
+        );
+    }
+
     match cli.command {
-        Some(Commands::Install) => install_keybindings(),
+        Some(Commands::Install) => install_keybindings(&config),
         Some(Commands::Uninstall) => uninstall_keybindings(),
+        Some(Commands::Snapshot { file }) => save_snapshot(&config, file),
+        Some(Commands::Restore { file }) => restore_snapshot(&config, file),
+        Some(Commands::Trust) => trust_project(),
+        Some(Commands::Session { json, command }) => {
+            cli::exit_on_error(cli::session(&config, command, json))
+        }
+        Some(Commands::Worktree { json, command }) => {
+            cli::exit_on_error(cli::worktree(&config, &current_path, command, json))
+        }
+        Some(Commands::Buffer { json, command }) => {
+            cli::exit_on_error(cli::buffer(&config, command, json))
+        }
         Some(Commands::SessionPicker) | None => {
             check_prerequisites()?;
             install_panic_hook();
-            let mut app = App::new(View::SessionPicker)?;
+            let mut app = App::new(View::SessionPicker, config)?;
             app.run()
         }
         Some(Commands::CommandPalette) => {
             check_prerequisites()?;
             install_panic_hook();
-            let mut app = App::new(View::CommandPalette)?;
+            let mut app = App::new(View::CommandPalette, config)?;
             app.run()
         }
         Some(Commands::Worktrees) => {
             check_prerequisites()?;
             install_panic_hook();
-            let mut app = App::new(View::WorktreePicker)?;
+            let mut app = App::new(View::WorktreePicker, config)?;
             app.run()
         }
-        Some(Commands::FindFiles) => {
-            run_find_files()
-        }
-        Some(Commands::GitDiff) => {
-            run_git_diff()
-        }
+        Some(Commands::FindFiles) => match config.commands.find_files {
+            Some(ref command) => run_find_files(command),
+            None => {
+                check_prerequisites()?;
+                install_panic_hook();
+                let mut app = App::new(View::FilePicker, config)?;
+                app.run()
+            }
+        },
+        Some(Commands::GitDiff) => match config.commands.git_diff {
+            Some(ref command) => run_git_diff(command),
+            None => {
+                check_prerequisites()?;
+                install_panic_hook();
+                let mut app = App::new(View::DiffViewer, config)?;
+                app.run()
+            }
+        },
     }
 }
 
@@ -79,10 +176,6 @@ fn check_prerequisites() -> Result<()> {
     let prerequisites = [
         ("tmux", "tmux is required. Install with: brew install tmux"),
         ("nvim", "nvim is required. Install with: brew install neovim"),
-        ("fd", "fd is required for file finding. Install with: brew install fd"),
-        ("fzf", "fzf is required for fuzzy finding. Install with: brew install fzf"),
-        ("bat", "bat is required for file preview. Install with: brew install bat"),
-        ("delta", "delta is required for git diffs. Install with: brew install git-delta"),
     ];
 
     for (cmd, install_msg) in prerequisites {
@@ -101,25 +194,36 @@ fn check_prerequisites() -> Result<()> {
     Ok(())
 }
 
-fn install_keybindings() -> Result<()> {
+fn install_keybindings(config: &Config) -> Result<()> {
     let home = std::env::var("HOME").map_err(|_| PmanError::Io(std::io::Error::other("HOME not set")))?;
     let tmux_conf_path = PathBuf::from(&home).join(".tmux.conf");
 
     let pman_path = std::env::current_exe()
-        .map_err(|e| PmanError::Io(e))?
+        .map_err(PmanError::Io)?
         .display()
         .to_string();
 
+    let popup = &config.popup;
     let keybindings = format!(
         r#"
 # pman keybindings (managed by pman)
-bind s display-popup -E -w 80% -h 80% "{pman_path} session-picker"
-bind p display-popup -E -w 80% -h 80% "{pman_path} command-palette"
-bind w display-popup -E -w 80% -h 80% "{pman_path} worktrees"
-bind f display-popup -E -w 90% -h 90% "{pman_path} find-files"
-bind d display-popup -E -w 90% -h 90% "{pman_path} git-diff"
+bind s display-popup -E -w {} -h {} "{pman_path} session-picker"
+bind p display-popup -E -w {} -h {} "{pman_path} command-palette"
+bind w display-popup -E -w {} -h {} "{pman_path} worktrees"
+bind f display-popup -E -w {} -h {} "{pman_path} find-files"
+bind d display-popup -E -w {} -h {} "{pman_path} git-diff"
 # end pman keybindings
-"#
+"#,
+        popup.sessions.width,
+        popup.sessions.height,
+        popup.palette.width,
+        popup.palette.height,
+        popup.worktrees.width,
+        popup.worktrees.height,
+        popup.find_files.width,
+        popup.find_files.height,
+        popup.git_diff.width,
+        popup.git_diff.height,
     );
 
     // Read existing config
@@ -186,14 +290,67 @@ fn uninstall_keybindings() -> Result<()> {
     Ok(())
 }
 
-fn run_find_files() -> Result<()> {
+fn snapshot_store(config: &Config, file: Option<PathBuf>) -> SnapshotStore {
+    let path = file.unwrap_or_else(|| config.snapshot.path.clone());
+    SnapshotStore::new(TmuxClient::new(config), path)
+}
+
+fn save_snapshot(config: &Config, file: Option<PathBuf>) -> Result<()> {
+    let store = snapshot_store(config, file);
+    let snapshot = store.save().inspect_err(|e| eprintln!("Error: {}", e))?;
+
+    println!(
+        "✓ Saved {} sessions to {}",
+        snapshot.sessions.len(),
+        store.path().display()
+    );
+    Ok(())
+}
+
+fn trust_project() -> Result<()> {
+    let current_dir = std::env::current_dir()?;
+    let Some(file) = Config::project_path(&current_dir) else {
+        let e = PmanError::Config(format!(
+            "no {} in {} or above",
+            config::PROJECT_FILE,
+            current_dir.display()
+        ));
+        eprintln!("Error: {}", e);
+        return Err(e);
+    };
+
+    fs::read_to_string(&file)
+        .map_err(PmanError::from)
+        .and_then(|contents| TrustStore::load(Config::trust_path()).trust(&file, &contents))
+        .inspect_err(|e| eprintln!("Error: {}", e))?;
+    println!("✓ Trusted commands in {}", file.display());
+    Ok(())
+}
+
+fn restore_snapshot(config: &Config, file: Option<PathBuf>) -> Result<()> {
+    let store = snapshot_store(config, file);
+    let report = store
+        .load()
+        .and_then(|snapshot| store.restore(&snapshot, &config.snapshot.restore_commands))
+        .inspect_err(|e| eprintln!("Error: {}", e))?;
+
+    for name in &report.restored {
+        println!("✓ Restored {}", name);
+    }
+    for (name, reason) in &report.skipped {
+        println!("- Skipped {}: {}", name, reason);
+    }
+    Ok(())
+}
+
+fn run_find_files(command: &str) -> Result<()> {
     use std::process::Command;
 
     let status = Command::new("sh")
         .arg("-c")
-        .arg(r#"file=$(fd --type f --hidden --exclude .git | fzf --preview 'bat --color=always --style=numbers --line-range=:500 {}') && [ -n "$file" ] && nvim "$file""#)
+        .arg(command)
         .status()
-        .map_err(|e| PmanError::Io(e))?;
+        .map_err(PmanError::Io)?;
 
     if !status.success() {
         // User cancelled fzf, not an error
@@ -201,14 +358,14 @@ fn run_find_files() -> Result<()> {
     Ok(())
 }
 
-fn run_git_diff() -> Result<()> {
+fn run_git_diff(command: &str) -> Result<()> {
     use std::process::Command;
 
     let status = Command::new("sh")
         .arg("-c")
-        .arg("git diff HEAD | delta")
+        .arg(command)
         .status()
-        .map_err(|e| PmanError::Io(e))?;
+        .map_err(PmanError::Io)?;
 
     if !status.success() {
         eprintln!("git diff failed");
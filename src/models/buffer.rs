@@ -1,12 +1,29 @@
 use std::path::PathBuf;
 
-#[derive(Debug, Clone)]
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
 pub struct NvimBuffer {
     pub bufnr: i64,
     pub name: PathBuf,
     pub modified: bool,
 }
 
+/// A running nvim and the tmux pane it runs in.
+#[derive(Debug, Clone, Serialize)]
+pub struct NvimInstance {
+    pub socket: PathBuf,
+    pub session: String,
+    pub window: String,
+    pub pane_id: String,
+}
+
+impl NvimInstance {
+    pub fn label(&self) -> String {
+        format!("{}:{}", self.session, self.window)
+    }
+}
+
 impl NvimBuffer {
     pub fn display_name(&self) -> String {
         let name = self.name.file_name()
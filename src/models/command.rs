@@ -7,11 +7,15 @@ pub enum PaletteCommand {
     // Worktrees
     ListWorktrees,
     CreateWorktree,
+    LaunchAgent,
     // Files
     FindFiles,
     ListBuffers,
     // Git
     GitDiff,
+    CompareWorktrees,
+    // Config
+    ShowConfig,
 }
 
 impl PaletteCommand {
@@ -22,9 +26,12 @@ impl PaletteCommand {
             Self::KillSession,
             Self::ListWorktrees,
             Self::CreateWorktree,
+            Self::LaunchAgent,
             Self::FindFiles,
             Self::ListBuffers,
             Self::GitDiff,
+            Self::CompareWorktrees,
+            Self::ShowConfig,
         ]
     }
 
@@ -35,6 +42,7 @@ impl PaletteCommand {
             Self::KillSession,
             Self::FindFiles,
             Self::ListBuffers,
+            Self::ShowConfig,
         ]
     }
 
@@ -45,9 +53,12 @@ impl PaletteCommand {
             Self::KillSession => "Kill Session",
             Self::ListWorktrees => "List Worktrees",
             Self::CreateWorktree => "Create Worktree",
+            Self::LaunchAgent => "Launch Agent",
             Self::FindFiles => "Find Files",
             Self::ListBuffers => "List Buffers",
             Self::GitDiff => "Git Diff",
+            Self::CompareWorktrees => "Compare Worktrees",
+            Self::ShowConfig => "Show Effective Config",
         }
     }
 
@@ -58,9 +69,12 @@ impl PaletteCommand {
             Self::KillSession => "Kill the current tmux session",
             Self::ListWorktrees => "List and manage git worktrees",
             Self::CreateWorktree => "Create a new git worktree",
-            Self::FindFiles => "Find and open files with fzf",
+            Self::LaunchAgent => "Start an agent profile in a new worktree session",
+            Self::FindFiles => "Find and open files in the project",
             Self::ListBuffers => "List open buffers in nvim",
-            Self::GitDiff => "Show git diff in popup",
+            Self::GitDiff => "Review, stage and commit uncommitted changes",
+            Self::CompareWorktrees => "Diff two worktrees and copy files or hunks between them",
+            Self::ShowConfig => "Show merged settings and where each one comes from",
         }
     }
 
@@ -0,0 +1,495 @@
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+/// Changes in a worktree, split into files and hunks.
+#[derive(Debug, Clone, Default)]
+pub struct Diff {
+    /// Directory the file paths are relative to
+    pub root: PathBuf,
+    pub files: Vec<FileDiff>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileDiff {
+    /// Path after the change, or before it for deleted files
+    pub path: PathBuf,
+    /// Path before a rename
+    pub old_path: Option<PathBuf>,
+    pub status: FileStatus,
+    pub binary: bool,
+    /// Lines from `diff --git` up to the first hunk, as git printed them
+    pub header: Vec<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    Added,
+    Deleted,
+    Modified,
+    Renamed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_count: usize,
+    pub new_start: usize,
+    pub new_count: usize,
+    /// Text after the closing `@@`, usually the enclosing function
+    pub section: String,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffLine {
+    pub kind: LineKind,
+    /// Without the leading ` `, `+` or `-`
+    pub content: String,
+    /// Followed by `\ No newline at end of file`
+    pub no_newline: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    Context,
+    Added,
+    Removed,
+}
+
+/// What applying a patch made from a diff does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchAction {
+    /// Add unstaged changes to the index
+    Stage,
+    /// Take staged changes out of the index
+    Unstage,
+    /// Undo unstaged changes in the working tree
+    Discard,
+    /// Make the working tree of the diff's old side look like its new side, e.g. to copy
+    /// changes from one worktree into another
+    Copy,
+}
+
+impl PatchAction {
+    /// Whether the patch is applied with `--reverse`, from the diff's new side back to
+    /// its old one.
+    pub fn is_reverse(&self) -> bool {
+        matches!(self, Self::Unstage | Self::Discard)
+    }
+}
+
+impl FileDiff {
+    pub fn additions(&self) -> usize {
+        self.count(LineKind::Added)
+    }
+
+    pub fn deletions(&self) -> usize {
+        self.count(LineKind::Removed)
+    }
+
+    fn count(&self, kind: LineKind) -> usize {
+        self.hunks
+            .iter()
+            .flat_map(|hunk| &hunk.lines)
+            .filter(|line| line.kind == kind)
+            .count()
+    }
+
+    /// A patch for `git apply` with one hunk, or just the header for files without
+    /// hunks such as pure renames.
+    ///
+    /// `lines` limits the patch to those changed lines of the hunk, by index. The lines
+    /// left out stay as they are on the side the patch is applied to: the old side, or
+    /// the new one for `reverse` patches.
+    pub fn patch(&self, hunk: usize, lines: Option<&BTreeSet<usize>>, reverse: bool) -> String {
+        let mut patch = self.header.iter().map(|line| format!("{}\n", line)).collect();
+        if let Some(hunk) = self.hunks.get(hunk) {
+            push_hunk(&mut patch, hunk, lines, reverse);
+        }
+        patch
+    }
+
+    /// A patch for `git apply` with every hunk of the file.
+    pub fn file_patch(&self) -> String {
+        let mut patch = self.header.iter().map(|line| format!("{}\n", line)).collect();
+        for hunk in &self.hunks {
+            push_hunk(&mut patch, hunk, None, false);
+        }
+        patch
+    }
+}
+
+/// Append `hunk`, or the chosen `lines` of it, to `patch`.
+fn push_hunk(patch: &mut String, hunk: &Hunk, lines: Option<&BTreeSet<usize>>, reverse: bool) {
+    let mut body = String::new();
+    let (mut old_count, mut new_count) = (0, 0);
+    for (index, line) in hunk.lines.iter().enumerate() {
+        let chosen = lines.is_none_or(|lines| lines.contains(&index));
+        let sign = match (line.kind, chosen) {
+            (LineKind::Context, _) => ' ',
+            (LineKind::Added, true) => '+',
+            (LineKind::Removed, true) => '-',
+            (LineKind::Added, false) if reverse => ' ',
+            (LineKind::Removed, false) if !reverse => ' ',
+            _ => continue,
+        };
+        if sign != '+' {
+            old_count += 1;
+        }
+        if sign != '-' {
+            new_count += 1;
+        }
+        body.push(sign);
+        body.push_str(&line.content);
+        body.push('\n');
+        if line.no_newline {
+            body.push_str("\\ No newline at end of file\n");
+        }
+    }
+
+    // An empty range starts before the line where a non-empty one would
+    let start = |start: usize, count: usize, new_count: usize| match (count, new_count) {
+        (0, 0) => start,
+        (0, _) => start + 1,
+        (_, 0) => start.saturating_sub(1),
+        _ => start,
+    };
+    patch.push_str(&format!(
+        "@@ -{},{} +{},{} @@\n",
+        start(hunk.old_start, hunk.old_count, old_count),
+        old_count,
+        start(hunk.new_start, hunk.new_count, new_count),
+        new_count
+    ));
+    patch.push_str(&body);
+}
+
+impl FileStatus {
+    pub fn marker(&self) -> char {
+        match self {
+            Self::Added => 'A',
+            Self::Deleted => 'D',
+            Self::Modified => 'M',
+            Self::Renamed => 'R',
+        }
+    }
+}
+
+impl Hunk {
+    pub fn header(&self) -> String {
+        format!(
+            "@@ -{},{} +{},{} @@{}",
+            self.old_start, self.old_count, self.new_start, self.new_count, self.section
+        )
+    }
+
+    /// Index of the first added or removed line.
+    pub fn first_change(&self) -> usize {
+        self.lines
+            .iter()
+            .position(|line| line.kind != LineKind::Context)
+            .unwrap_or(0)
+    }
+
+    /// 1-based line of the new file that the line at `index` is, or comes before if it
+    /// was removed.
+    pub fn new_line(&self, index: usize) -> usize {
+        let before = self.lines[..index.min(self.lines.len())]
+            .iter()
+            .filter(|line| line.kind != LineKind::Removed)
+            .count();
+        (self.new_start + before).max(1)
+    }
+
+    fn parse_header(line: &str) -> Option<Self> {
+        let (ranges, section) = line.strip_prefix("@@ -")?.split_once(" @@")?;
+        let (old, new) = ranges.split_once(" +")?;
+        let (old_start, old_count) = parse_range(old)?;
+        let (new_start, new_count) = parse_range(new)?;
+
+        Some(Self {
+            old_start,
+            old_count,
+            new_start,
+            new_count,
+            section: section.to_string(),
+            lines: Vec::new(),
+        })
+    }
+}
+
+/// `start,count`, where a missing count means 1.
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    match range.split_once(',') {
+        Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
+/// Split `git diff` output with `a/` and `b/` prefixes into files and hunks.
+pub fn parse_diff(text: &str) -> Vec<FileDiff> {
+    let mut files: Vec<FileDiff> = Vec::new();
+    // Old and new lines the current hunk still has to come, so that a removed
+    // "-- x" isn't mistaken for a "--- a/x" header
+    let mut remaining = (0, 0);
+
+    for line in text.strip_suffix('\n').unwrap_or(text).split('\n') {
+        if let Some(header) = line.strip_prefix("diff --git ") {
+            files.push(FileDiff {
+                path: header_path(header),
+                old_path: None,
+                status: FileStatus::Modified,
+                binary: false,
+                header: vec![line.to_string()],
+                hunks: Vec::new(),
+            });
+            remaining = (0, 0);
+            continue;
+        }
+        let Some(file) = files.last_mut() else {
+            continue;
+        };
+
+        if line.starts_with('\\') {
+            if let Some(last) = file.hunks.last_mut().and_then(|hunk| hunk.lines.last_mut()) {
+                last.no_newline = true;
+            }
+            continue;
+        }
+
+        if remaining != (0, 0) {
+            let Some(hunk) = file.hunks.last_mut() else {
+                continue;
+            };
+            let (kind, content) = match line.split_at_checked(1) {
+                Some(("+", content)) => (LineKind::Added, content),
+                Some(("-", content)) => (LineKind::Removed, content),
+                Some((" ", content)) => (LineKind::Context, content),
+                // Some tools strip the space from empty context lines
+                _ if line.is_empty() => (LineKind::Context, ""),
+                _ => continue,
+            };
+            if kind != LineKind::Added {
+                remaining.0 -= 1.min(remaining.0);
+            }
+            if kind != LineKind::Removed {
+                remaining.1 -= 1.min(remaining.1);
+            }
+            hunk.lines.push(DiffLine {
+                kind,
+                content: content.to_string(),
+                no_newline: false,
+            });
+            continue;
+        }
+
+        if let Some(hunk) = Hunk::parse_header(line) {
+            remaining = (hunk.old_count, hunk.new_count);
+            file.hunks.push(hunk);
+            continue;
+        }
+        if file.hunks.is_empty() {
+            file.header.push(line.to_string());
+        }
+
+        if line.starts_with("new file mode") {
+            file.status = FileStatus::Added;
+        } else if line.starts_with("deleted file mode") {
+            file.status = FileStatus::Deleted;
+        } else if let Some(from) = line.strip_prefix("rename from ") {
+            file.status = FileStatus::Renamed;
+            file.old_path = Some(PathBuf::from(unquote(from)));
+        } else if let Some(to) = line.strip_prefix("rename to ") {
+            file.path = PathBuf::from(unquote(to));
+        } else if line.starts_with("Binary files ") {
+            file.binary = true;
+        } else if let Some(path) = line.strip_prefix("+++ ").and_then(|p| side_path(p, "b/")) {
+            file.path = path;
+        } else if let Some(path) = line.strip_prefix("--- ").and_then(|p| side_path(p, "a/")) {
+            if file.status == FileStatus::Deleted {
+                file.path = path;
+            }
+        }
+    }
+
+    files
+}
+
+/// The path in a `--- a/<path>` or `+++ b/<path>` line; `None` for `/dev/null`.
+fn side_path(path: &str, prefix: &str) -> Option<PathBuf> {
+    // git ends names with spaces in a tab
+    let path = unquote(path.trim_end_matches('\t'));
+    path.strip_prefix(prefix).map(PathBuf::from)
+}
+
+/// The path in `a/<path> b/<path>`. Both halves name the same file except for renames,
+/// whose paths come again on `rename` lines.
+fn header_path(header: &str) -> PathBuf {
+    if header.ends_with('"') {
+        if let Some(start) = header.rfind(" \"") {
+            let path = unquote(&header[start + 1..]);
+            return PathBuf::from(path.strip_prefix("b/").unwrap_or(&path));
+        }
+    }
+
+    // "a/" + path + " b/" + path
+    let len = (header.len().saturating_sub(5)) / 2;
+    if let (Some(old), Some(new)) = (header.get(2..2 + len), header.get(header.len() - len..)) {
+        if old == new && header.starts_with("a/") {
+            return PathBuf::from(new);
+        }
+    }
+    let new = header.rsplit_once(" b/").map_or(header, |(_, new)| new);
+    PathBuf::from(new)
+}
+
+/// Undo git's C-style quoting of unusual paths, e.g. `"tab\there"`.
+fn unquote(path: &str) -> String {
+    let Some(inner) = path.strip_prefix('"').and_then(|p| p.strip_suffix('"')) else {
+        return path.to_string();
+    };
+
+    let mut bytes = Vec::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('n') => bytes.push(b'\n'),
+            Some('t') => bytes.push(b'\t'),
+            Some(digit @ '0'..='7') => {
+                // Octal escapes are the bytes of non-ASCII names
+                let octal: String = std::iter::once(digit).chain(chars.by_ref().take(2)).collect();
+                bytes.push(u8::from_str_radix(&octal, 8).unwrap_or(b'?'));
+            }
+            Some(other) => bytes.push(other as u8),
+            None => {}
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODIFIED: &str = "\
+diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,3 +1,3 @@ mod a;
+ fn one() {}
+--- removed comment
++// added comment
+ fn two() {}
+@@ -10 +10,2 @@
+-last
+\\ No newline at end of file
++last
++more
+";
+
+    #[test]
+    fn parses_hunks_and_counts() {
+        let files = parse_diff(MODIFIED);
+
+        assert_eq!(files.len(), 1);
+        let file = &files[0];
+        assert_eq!(file.path, PathBuf::from("src/lib.rs"));
+        assert_eq!(file.status, FileStatus::Modified);
+        assert_eq!((file.additions(), file.deletions()), (3, 2));
+
+        let first = &file.hunks[0];
+        assert_eq!(first.header(), "@@ -1,3 +1,3 @@ mod a;");
+        assert_eq!(first.lines[1].kind, LineKind::Removed);
+        assert_eq!(first.lines[1].content, "-- removed comment");
+        assert_eq!(first.new_line(first.first_change()), 2);
+
+        let second = &file.hunks[1];
+        assert_eq!((second.old_start, second.old_count), (10, 1));
+        assert!(second.lines[0].no_newline);
+        assert!(!second.lines[1].no_newline);
+    }
+
+    #[test]
+    fn parses_added_deleted_renamed_and_binary_files() {
+        let text = "\
+diff --git a/new file.txt b/new file.txt
+new file mode 100644
+index 0000000..3333333
+--- /dev/null
++++ b/new file.txt\t
+@@ -0,0 +1 @@
++hello
+diff --git a/gone.rs b/gone.rs
+deleted file mode 100644
+--- a/gone.rs
++++ /dev/null
+@@ -1 +0,0 @@
+-bye
+diff --git a/old.rs b/renamed.rs
+similarity index 100%
+rename from old.rs
+rename to renamed.rs
+diff --git \"a/logo\\tmark.png\" \"b/logo\\tmark.png\"
+Binary files \"a/logo\\tmark.png\" and \"b/logo\\tmark.png\" differ
+";
+        let files = parse_diff(text);
+
+        let summary: Vec<_> = files
+            .iter()
+            .map(|f| (f.status, f.path.display().to_string(), f.binary))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (FileStatus::Added, "new file.txt".to_string(), false),
+                (FileStatus::Deleted, "gone.rs".to_string(), false),
+                (FileStatus::Renamed, "renamed.rs".to_string(), false),
+                (FileStatus::Modified, "logo\tmark.png".to_string(), true),
+            ]
+        );
+        assert_eq!(files[2].old_path, Some(PathBuf::from("old.rs")));
+        assert_eq!(files[1].hunks[0].new_line(0), 1);
+    }
+
+    #[test]
+    fn patches_only_the_chosen_lines() {
+        let file = &parse_diff(MODIFIED)[0];
+        let header = "\
+diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+";
+        // Staging only the addition keeps the removed line as context
+        let added = BTreeSet::from([2]);
+        let staged = [
+            "@@ -1,3 +1,4 @@",
+            " fn one() {}",
+            " -- removed comment",
+            "+// added comment",
+            " fn two() {}",
+        ];
+        assert_eq!(file.patch(0, Some(&added), false), format!("{header}{}\n", staged.join("\n")));
+
+        // Reversed, the unchosen removal has already happened
+        let unstaged = ["@@ -1,2 +1,3 @@", " fn one() {}", "+// added comment", " fn two() {}"];
+        let reversed = file.patch(0, Some(&added), true);
+        assert_eq!(reversed, format!("{header}{}\n", unstaged.join("\n")));
+
+        let whole = [
+            "@@ -10,1 +10,2 @@",
+            "-last",
+            "\\ No newline at end of file",
+            "+last",
+            "+more",
+        ];
+        assert_eq!(file.patch(1, None, false), format!("{header}{}\n", whole.join("\n")));
+    }
+}
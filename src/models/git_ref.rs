@@ -0,0 +1,24 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefKind {
+    Branch,
+    RemoteBranch,
+    Tag,
+}
+
+/// A branch or tag a worktree can be created from.
+#[derive(Debug, Clone)]
+pub struct GitRef {
+    /// Short name, e.g. `main`, `origin/feature` or `v1.0`
+    pub name: String,
+    pub kind: RefKind,
+}
+
+impl GitRef {
+    /// For remote branches, the remote name and the branch name on it.
+    pub fn remote_parts(&self) -> Option<(&str, &str)> {
+        match self.kind {
+            RefKind::RemoteBranch => self.name.split_once('/'),
+            _ => None,
+        }
+    }
+}
@@ -1,9 +1,19 @@
 mod buffer;
 mod command;
+mod diff;
+mod git_ref;
+mod pane;
 mod session;
+mod snapshot;
 mod worktree;
 
-pub use buffer::NvimBuffer;
+pub use buffer::{NvimBuffer, NvimInstance};
 pub use command::PaletteCommand;
-pub use session::TmuxSession;
-pub use worktree::GitWorktree;
+pub use diff::{
+    parse_diff, Diff, DiffLine, FileDiff, FileStatus, Hunk, LineKind, PatchAction,
+};
+pub use git_ref::{GitRef, RefKind};
+pub use pane::TmuxPane;
+pub use session::{AgentStatus, TmuxSession};
+pub use snapshot::{PaneSnapshot, SessionSnapshot, Snapshot, WindowSnapshot, SNAPSHOT_VERSION};
+pub use worktree::{GitWorktree, MergeStrategy, SetupStatus, UpstreamStatus, WorktreeDetails};
@@ -0,0 +1,15 @@
+#[derive(Debug, Clone)]
+pub struct TmuxPane {
+    pub session: String,
+    pub window_name: String,
+    pub window_id: String,
+    pub pane_id: String,
+    /// Process id of the pane's first process, usually its shell
+    pub pid: u32,
+    pub current_command: String,
+    pub dead: bool,
+    /// Exit status of a dead pane's command, if tmux kept it
+    pub dead_status: Option<i32>,
+    /// Unix time of the window's last output, from any of its panes
+    pub window_activity: u64,
+}
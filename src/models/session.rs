@@ -1,16 +1,97 @@
 use std::path::PathBuf;
 
-#[derive(Debug, Clone)]
+use ratatui::{
+    style::{Color, Style},
+    text::{Line, Span},
+};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AgentStatus {
+    /// Producing output or matched a busy detector
+    Busy,
+    /// Matched a prompt detector and needs a response
+    Waiting,
+    /// No recent output
+    Idle,
+    /// The agent process is gone
+    Exited,
+    #[default]
+    Unknown,
+}
+
+impl AgentStatus {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Self::Busy => "◐",
+            Self::Waiting => "◆",
+            Self::Idle => "◇",
+            Self::Exited => "✕",
+            Self::Unknown => " ",
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            Self::Busy => Color::Yellow,
+            Self::Waiting => Color::Magenta,
+            Self::Idle => Color::Green,
+            Self::Exited => Color::Red,
+            Self::Unknown => Color::DarkGray,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Busy => "busy",
+            Self::Waiting => "waiting",
+            Self::Idle => "idle",
+            Self::Exited => "exited",
+            Self::Unknown => "unknown",
+        }
+    }
+
+    /// Sort key for "needs attention": lower comes first.
+    pub fn attention_rank(&self) -> u8 {
+        match self {
+            Self::Waiting => 0,
+            Self::Exited => 1,
+            Self::Idle => 2,
+            Self::Busy => 3,
+            Self::Unknown => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct TmuxSession {
     pub name: String,
     pub attached: bool,
     pub path: Option<PathBuf>,
-    pub windows: usize,
-    pub created: Option<u64>,
+    /// Agent profile launched in this session (the `@pman_agent` option)
+    pub agent: Option<String>,
+    pub status: AgentStatus,
 }
 
 impl TmuxSession {
     pub fn display_name(&self) -> String {
+        format!("{} {}", self.status.symbol(), self.label())
+    }
+
+    /// `display_name` with the status indicator colored.
+    pub fn display_line(&self) -> Line<'static> {
+        Line::from(vec![
+            Span::styled(
+                self.status.symbol(),
+                Style::default().fg(self.status.color()),
+            ),
+            Span::raw(" "),
+            Span::raw(self.label()),
+        ])
+    }
+
+    fn label(&self) -> String {
         let status = if self.attached { "●" } else { "○" };
         let path_str = self
             .path
@@ -19,11 +100,16 @@ impl TmuxSession {
             .map(|s| s.to_string_lossy().to_string())
             .unwrap_or_default();
 
-        if path_str.is_empty() {
+        let mut name = if path_str.is_empty() {
             format!("{} {}", status, self.name)
         } else {
             format!("{} {} ({})", status, self.name, path_str)
+        };
+
+        if let Some(ref agent) = self.agent {
+            name.push_str(&format!(" [{}]", agent));
         }
+        name
     }
 
     pub fn search_text(&self) -> String {
@@ -32,6 +118,11 @@ impl TmuxSession {
             .as_ref()
             .map(|p| p.to_string_lossy().to_string())
             .unwrap_or_default();
-        format!("{} {}", self.name, path_str)
+        format!(
+            "{} {} {}",
+            self.name,
+            path_str,
+            self.agent.as_deref().unwrap_or_default()
+        )
     }
 }
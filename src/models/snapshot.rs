@@ -0,0 +1,43 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// Bumped whenever the snapshot format changes incompatibly.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Every tmux session at one moment, as written by `pman snapshot`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    /// Seconds since the Unix epoch
+    pub created: u64,
+    pub sessions: Vec<SessionSnapshot>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionSnapshot {
+    pub name: String,
+    pub path: Option<PathBuf>,
+    /// The linked git worktree the session works in, if any
+    pub worktree: Option<PathBuf>,
+    /// Agent profile the session was launched with
+    pub agent: Option<String>,
+    pub windows: Vec<WindowSnapshot>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowSnapshot {
+    pub name: String,
+    /// tmux's own layout string, e.g. `b25d,80x24,0,0{40x24,0,0,1,39x24,41,0,2}`
+    pub layout: String,
+    pub active: bool,
+    pub panes: Vec<PaneSnapshot>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaneSnapshot {
+    pub path: PathBuf,
+    /// Full command line of the foreground process, `None` at a shell prompt
+    pub command: Option<String>,
+    pub active: bool,
+}
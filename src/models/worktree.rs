@@ -1,6 +1,8 @@
 use std::path::PathBuf;
 
-#[derive(Debug, Clone)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize)]
 pub struct GitWorktree {
     pub path: PathBuf,
     pub branch: String,
@@ -30,3 +32,99 @@ impl GitWorktree {
         )
     }
 }
+
+/// Merge readiness of a worktree's branch, shown beside the worktree picker.
+#[derive(Debug, Clone, Default)]
+pub struct WorktreeDetails {
+    pub main_branch: String,
+    /// Commits on the branch that are not on main
+    pub ahead: usize,
+    /// Commits on main that are not on the branch
+    pub behind: usize,
+    pub upstream: Option<UpstreamStatus>,
+    /// `git diff --stat` of uncommitted changes
+    pub uncommitted_stat: String,
+    /// `git diff --stat` of committed changes not yet on main
+    pub unmerged_stat: String,
+    /// Latest commits as `<hash> <subject>`
+    pub recent_commits: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct UpstreamStatus {
+    pub name: String,
+    pub ahead: usize,
+    pub behind: usize,
+}
+
+/// How "Merge to main" brings a branch into the main branch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MergeStrategy {
+    /// `git merge`, creating a merge commit unless it fast-forwards
+    #[default]
+    Merge,
+    /// `git merge --squash` and a single commit with a message
+    Squash,
+    /// Rebase the branch onto main, then fast-forward main
+    Rebase,
+    /// Fast-forward main, refusing if the branches diverged
+    FfOnly,
+}
+
+impl MergeStrategy {
+    pub const ALL: [MergeStrategy; 4] = [Self::Merge, Self::Squash, Self::Rebase, Self::FfOnly];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Merge => "Merge commit",
+            Self::Squash => "Squash",
+            Self::Rebase => "Rebase and fast-forward",
+            Self::FfOnly => "Fast-forward only",
+        }
+    }
+
+    /// Starting point for a squash commit's message: a summary line, then the subjects
+    /// of the commits squashed, one per line. A single commit keeps its subject.
+    pub fn squash_message(branch: &str, subjects: &[String]) -> String {
+        match subjects {
+            [subject] => subject.clone(),
+            _ => {
+                let mut message = format!("Squash branch '{}'\n", branch);
+                if !subjects.is_empty() {
+                    message.push('\n');
+                }
+                for subject in subjects {
+                    message.push_str(&format!("- {}\n", subject));
+                }
+                message.trim_end().to_string()
+            }
+        }
+    }
+}
+
+/// Progress of a worktree's `.pman.toml` setup commands, read from its setup window.
+#[derive(Debug, Clone)]
+pub struct SetupStatus {
+    /// `None` while the commands are still running
+    pub exit_status: Option<i32>,
+    /// Last lines of output
+    pub output: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn squash_messages_list_the_squashed_subjects() {
+        let subjects = ["Add login".to_string(), "Fix typo".to_string()];
+
+        assert_eq!(
+            MergeStrategy::squash_message("feature/auth", &subjects),
+            "Squash branch 'feature/auth'\n\n- Add login\n- Fix typo"
+        );
+        assert_eq!(MergeStrategy::squash_message("fix", &subjects[..1]), "Add login");
+        assert_eq!(MergeStrategy::squash_message("empty", &[]), "Squash branch 'empty'");
+    }
+}
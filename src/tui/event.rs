@@ -1,13 +1,13 @@
 use std::time::Duration;
-use crossterm::event::{self, Event as CrosstermEvent, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::event::{self, Event as CrosstermEvent, KeyEvent, KeyEventKind};
 
-use crate::actions::Action;
 use crate::error::Result;
 
 #[derive(Debug)]
 pub enum Event {
     Key(KeyEvent),
-    Resize(u16, u16),
+    Paste(String),
+    Resize,
     Tick,
 }
 
@@ -28,7 +28,8 @@ impl EventHandler {
                 CrosstermEvent::Key(key) if key.kind == KeyEventKind::Press => {
                     Ok(Event::Key(key))
                 }
-                CrosstermEvent::Resize(w, h) => Ok(Event::Resize(w, h)),
+                CrosstermEvent::Paste(text) => Ok(Event::Paste(text)),
+                CrosstermEvent::Resize(_, _) => Ok(Event::Resize),
                 _ => Ok(Event::Tick),
             }
         } else {
@@ -36,25 +37,3 @@ impl EventHandler {
         }
     }
 }
-
-pub fn key_to_action(key: KeyEvent) -> Option<Action> {
-    match key.code {
-        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => Some(Action::Quit),
-        KeyCode::Char('q') => Some(Action::Quit),
-        KeyCode::Esc => Some(Action::Escape),
-        KeyCode::Enter => Some(Action::Enter),
-        KeyCode::Up => Some(Action::MoveUp),
-        KeyCode::Down => Some(Action::MoveDown),
-        KeyCode::Char('k') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-            Some(Action::MoveUp)
-        }
-        KeyCode::Char('j') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-            Some(Action::MoveDown)
-        }
-        KeyCode::PageUp => Some(Action::PageUp),
-        KeyCode::PageDown => Some(Action::PageDown),
-        KeyCode::Backspace => Some(Action::Backspace),
-        KeyCode::Char(c) => Some(Action::Character(c)),
-        _ => None,
-    }
-}
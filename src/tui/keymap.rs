@@ -0,0 +1,638 @@
+use std::collections::BTreeMap;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::actions::Action;
+use crate::config::KeysConfig;
+use crate::error::{PmanError, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyContext {
+    Global,
+    SessionPicker,
+    CommandPalette,
+    FilePicker,
+    WorktreePicker,
+    BufferPicker,
+    ConfigViewer,
+    DiffViewer,
+    InputDialog,
+    ConfirmDialog,
+    SelectDialog,
+}
+
+impl KeyContext {
+    fn config_name(&self) -> &'static str {
+        match self {
+            Self::Global => "global",
+            Self::SessionPicker => "session_picker",
+            Self::CommandPalette => "command_palette",
+            Self::FilePicker => "file_picker",
+            Self::WorktreePicker => "worktree_picker",
+            Self::BufferPicker => "buffer_picker",
+            Self::ConfigViewer => "config_viewer",
+            Self::DiffViewer => "diff_viewer",
+            Self::InputDialog => "input_dialog",
+            Self::ConfirmDialog => "confirm_dialog",
+            Self::SelectDialog => "select_dialog",
+        }
+    }
+}
+
+/// A single key press, normalized so that bindings and incoming events compare equal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    fn from_event(event: KeyEvent) -> Self {
+        let mut modifiers = event.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        // The character already carries the case, so shift is redundant
+        if matches!(event.code, KeyCode::Char(_)) {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        Self {
+            code: event.code,
+            modifiers,
+        }
+    }
+
+    fn parse(s: &str) -> std::result::Result<Self, String> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+
+        // A lone "-" is a key, not a separator
+        while let Some((prefix, tail)) = rest.split_once('-').filter(|(_, tail)| !tail.is_empty()) {
+            match prefix.to_ascii_lowercase().as_str() {
+                "ctrl" | "c" => modifiers |= KeyModifiers::CONTROL,
+                "alt" | "m" => modifiers |= KeyModifiers::ALT,
+                "shift" | "s" => modifiers |= KeyModifiers::SHIFT,
+                _ => return Err(format!("unknown modifier '{}' in '{}'", prefix, s)),
+            }
+            rest = tail;
+        }
+
+        let code = match rest.to_ascii_lowercase().as_str() {
+            _ if rest.chars().count() == 1 && modifiers.contains(KeyModifiers::SHIFT) => {
+                KeyCode::Char(rest.chars().flat_map(char::to_uppercase).next().unwrap_or(' '))
+            }
+            "enter" | "return" => KeyCode::Enter,
+            "esc" | "escape" => KeyCode::Esc,
+            "tab" if modifiers.contains(KeyModifiers::SHIFT) => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::BackTab
+            }
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "backspace" | "bs" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "insert" | "ins" => KeyCode::Insert,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" | "pgup" => KeyCode::PageUp,
+            "pagedown" | "pgdn" => KeyCode::PageDown,
+            "space" => KeyCode::Char(' '),
+            lower => {
+                let mut chars = rest.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => match lower.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                        Some(n) if (1..=12).contains(&n) => KeyCode::F(n),
+                        _ => return Err(format!("unknown key '{}' in '{}'", rest, s)),
+                    },
+                }
+            }
+        };
+
+        Ok(Self::from_event(KeyEvent::new(code, modifiers)))
+    }
+
+    /// Whether this key would otherwise be typed into a query
+    fn is_text(&self) -> bool {
+        matches!(self.code, KeyCode::Char(_))
+            && !self.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+    }
+
+    fn display(&self) -> String {
+        let key = match self.code {
+            KeyCode::Enter => "Enter".to_string(),
+            KeyCode::Esc => "Esc".to_string(),
+            KeyCode::Tab => "Tab".to_string(),
+            KeyCode::BackTab => "S-Tab".to_string(),
+            KeyCode::Backspace => "BS".to_string(),
+            KeyCode::Delete => "Del".to_string(),
+            KeyCode::Insert => "Ins".to_string(),
+            KeyCode::Up => "↑".to_string(),
+            KeyCode::Down => "↓".to_string(),
+            KeyCode::Left => "←".to_string(),
+            KeyCode::Right => "→".to_string(),
+            KeyCode::Home => "Home".to_string(),
+            KeyCode::End => "End".to_string(),
+            KeyCode::PageUp => "PgUp".to_string(),
+            KeyCode::PageDown => "PgDn".to_string(),
+            KeyCode::Char(' ') => "Space".to_string(),
+            KeyCode::Char(c) => c.to_string(),
+            KeyCode::F(n) => format!("F{}", n),
+            _ => "?".to_string(),
+        };
+
+        let mut prefix = String::new();
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            prefix.push_str("C-");
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            prefix.push_str("M-");
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            prefix.push_str("S-");
+        }
+        format!("{}{}", prefix, key)
+    }
+}
+
+fn parse_sequence(s: &str) -> std::result::Result<Vec<Key>, String> {
+    let keys = s
+        .split_whitespace()
+        .map(Key::parse)
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    if keys.is_empty() {
+        return Err("empty key binding".to_string());
+    }
+    Ok(keys)
+}
+
+fn display_sequence(keys: &[Key]) -> String {
+    keys.iter().map(Key::display).collect::<Vec<_>>().join(" ")
+}
+
+/// Names usable as binding targets in `[keys.*]`.
+fn action_from_name(name: &str) -> Option<Action> {
+    let action = match name {
+        "quit" => Action::Quit,
+        "back" => Action::Escape,
+        "confirm" => Action::Enter,
+        "up" => Action::MoveUp,
+        "down" => Action::MoveDown,
+        "page_up" => Action::PageUp,
+        "page_down" => Action::PageDown,
+        "backspace" => Action::Backspace,
+        "left" => Action::CursorLeft,
+        "right" => Action::CursorRight,
+        "home" => Action::CursorHome,
+        "end" => Action::CursorEnd,
+        "word_left" => Action::WordLeft,
+        "word_right" => Action::WordRight,
+        "delete_char" => Action::DeleteChar,
+        "delete_word" => Action::DeleteWord,
+        "delete_to_start" => Action::DeleteToStart,
+        "newline" => Action::Newline,
+        "new" => Action::New,
+        "delete" => Action::Delete,
+        "merge" => Action::Merge,
+        "yes" => Action::Yes,
+        "no" => Action::No,
+        "toggle" => Action::Toggle,
+        "sort" => Action::ToggleSort,
+        "diff" => Action::Diff,
+        "next_hunk" => Action::NextHunk,
+        "prev_hunk" => Action::PrevHunk,
+        "stage" => Action::Stage,
+        "unstage" => Action::Unstage,
+        "commit" => Action::Commit,
+        "staged" => Action::ToggleStaged,
+        "line_up" => Action::LineUp,
+        "line_down" => Action::LineDown,
+        "pick" => Action::Pick,
+        "pick_file" => Action::PickFile,
+        "compare" => Action::Compare,
+        "sessions" => Action::ShowSessionPicker,
+        "palette" => Action::ShowCommandPalette,
+        "files" => Action::ShowFilePicker,
+        "worktrees" => Action::ShowWorktreePicker,
+        "buffers" => Action::ShowBufferPicker,
+        _ => return None,
+    };
+    Some(action)
+}
+
+fn default_bindings(context: KeyContext) -> &'static [(&'static str, &'static str)] {
+    match context {
+        KeyContext::Global => &[
+            ("ctrl-c", "quit"),
+            ("esc", "back"),
+            ("enter", "confirm"),
+            ("up", "up"),
+            ("down", "down"),
+            ("ctrl-k", "up"),
+            ("ctrl-j", "down"),
+            ("pageup", "page_up"),
+            ("pagedown", "page_down"),
+            ("backspace", "backspace"),
+            ("left", "left"),
+            ("right", "right"),
+            ("home", "home"),
+            ("end", "end"),
+            ("ctrl-a", "home"),
+            ("ctrl-e", "end"),
+            ("alt-b", "word_left"),
+            ("alt-f", "word_right"),
+            ("ctrl-left", "word_left"),
+            ("ctrl-right", "word_right"),
+            ("delete", "delete_char"),
+            ("ctrl-w", "delete_word"),
+            ("ctrl-u", "delete_to_start"),
+            ("alt-enter", "newline"),
+        ],
+        KeyContext::SessionPicker => &[("n", "new"), ("d", "delete"), ("s", "sort")],
+        KeyContext::WorktreePicker => &[
+            ("n", "new"),
+            ("d", "delete"),
+            ("m", "merge"),
+            ("v", "diff"),
+            ("c", "compare"),
+        ],
+        KeyContext::DiffViewer => &[
+            ("]", "next_hunk"),
+            ("[", "prev_hunk"),
+            ("n", "next_hunk"),
+            ("N", "prev_hunk"),
+            ("j", "line_down"),
+            ("k", "line_up"),
+            ("space", "toggle"),
+            ("s", "stage"),
+            ("u", "unstage"),
+            ("d", "delete"),
+            ("c", "commit"),
+            ("tab", "staged"),
+            ("p", "pick"),
+            ("P", "pick_file"),
+        ],
+        KeyContext::ConfirmDialog => &[
+            ("y", "yes"),
+            ("Y", "yes"),
+            ("n", "no"),
+            ("N", "no"),
+            ("h", "toggle"),
+            ("l", "toggle"),
+            ("left", "toggle"),
+            ("right", "toggle"),
+        ],
+        KeyContext::CommandPalette
+        | KeyContext::FilePicker
+        | KeyContext::BufferPicker
+        | KeyContext::ConfigViewer
+        | KeyContext::InputDialog
+        | KeyContext::SelectDialog => &[],
+    }
+}
+
+struct Binding {
+    keys: Vec<Key>,
+    action: String,
+}
+
+enum Lookup<'a> {
+    Match(&'a str),
+    Prefix,
+    None,
+}
+
+pub struct Keymap {
+    bindings: Vec<(KeyContext, Vec<Binding>)>,
+    pending: Vec<Key>,
+}
+
+impl Keymap {
+    const CONTEXTS: [KeyContext; 11] = [
+        KeyContext::Global,
+        KeyContext::SessionPicker,
+        KeyContext::CommandPalette,
+        KeyContext::FilePicker,
+        KeyContext::WorktreePicker,
+        KeyContext::BufferPicker,
+        KeyContext::ConfigViewer,
+        KeyContext::DiffViewer,
+        KeyContext::InputDialog,
+        KeyContext::ConfirmDialog,
+        KeyContext::SelectDialog,
+    ];
+
+    pub fn new(config: &KeysConfig) -> Result<Self> {
+        let mut bindings = Vec::new();
+
+        for context in Self::CONTEXTS {
+            let section = context.config_name();
+            let mut context_bindings: Vec<Binding> = Vec::new();
+
+            for (keys, action) in default_bindings(context) {
+                context_bindings.push(Binding {
+                    keys: parse_sequence(keys).expect("default binding"),
+                    action: action.to_string(),
+                });
+            }
+
+            let mut configured: Vec<(&str, Vec<Key>)> = Vec::new();
+            for (keys_str, action) in config.section(context) {
+                let keys = parse_sequence(keys_str)
+                    .map_err(|e| PmanError::Config(format!("keys.{}: {}", section, e)))?;
+
+                if action != "none" && action_from_name(action).is_none() {
+                    return Err(PmanError::Config(format!(
+                        "keys.{}: unknown action '{}' for '{}'",
+                        section, action, keys_str
+                    )));
+                }
+
+                // Two spellings of the same chord, e.g. "ctrl-d" and "C-d"
+                if let Some((other, _)) = configured.iter().find(|(_, k)| *k == keys) {
+                    return Err(PmanError::Config(format!(
+                        "keys.{}: '{}' and '{}' are the same key",
+                        section, other, keys_str
+                    )));
+                }
+                configured.push((keys_str, keys.clone()));
+
+                context_bindings.retain(|b| b.keys != keys);
+                if action != "none" {
+                    context_bindings.push(Binding {
+                        keys,
+                        action: action.clone(),
+                    });
+                }
+            }
+
+            bindings.push((context, context_bindings));
+        }
+
+        let keymap = Self {
+            bindings,
+            pending: Vec::new(),
+        };
+        keymap.check_conflicts()?;
+        Ok(keymap)
+    }
+
+    /// A sequence that is a prefix of another could never be completed.
+    fn check_conflicts(&self) -> Result<()> {
+        for context in Self::CONTEXTS {
+            let effective = self.effective_bindings(context);
+            for a in &effective {
+                for b in &effective {
+                    if a.keys.len() < b.keys.len() && b.keys.starts_with(&a.keys) {
+                        return Err(PmanError::Config(format!(
+                            "keys.{}: '{}' ({}) conflicts with '{}' ({})",
+                            context.config_name(),
+                            display_sequence(&a.keys),
+                            a.action,
+                            display_sequence(&b.keys),
+                            b.action
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn context_bindings(&self, context: KeyContext) -> &[Binding] {
+        self.bindings
+            .iter()
+            .find(|(c, _)| *c == context)
+            .map(|(_, b)| b.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// View bindings first, then global ones they don't override.
+    fn effective_bindings(&self, context: KeyContext) -> Vec<&Binding> {
+        let local = self.context_bindings(context);
+        let mut effective: Vec<&Binding> = local.iter().collect();
+        if context != KeyContext::Global {
+            effective.extend(
+                self.context_bindings(KeyContext::Global)
+                    .iter()
+                    .filter(|g| !local.iter().any(|l| l.keys == g.keys)),
+            );
+        }
+        effective
+    }
+
+    fn lookup(&self, context: KeyContext, keys: &[Key], typing: bool) -> Lookup<'_> {
+        let mut is_prefix = false;
+
+        for binding in self.effective_bindings(context) {
+            // While a query is being typed, plain characters belong to the query
+            if typing && binding.keys[0].is_text() {
+                continue;
+            }
+            if binding.keys == keys {
+                return Lookup::Match(&binding.action);
+            }
+            if binding.keys.starts_with(keys) {
+                is_prefix = true;
+            }
+        }
+
+        if is_prefix {
+            Lookup::Prefix
+        } else {
+            Lookup::None
+        }
+    }
+
+    /// Feed a key press and return the actions it completes.
+    ///
+    /// `typing` is true while the focused view has a non-empty text query.
+    pub fn handle_key(&mut self, context: KeyContext, event: KeyEvent, typing: bool) -> Vec<Action> {
+        let key = Key::from_event(event);
+        let mut keys = std::mem::take(&mut self.pending);
+        keys.push(key);
+
+        match self.lookup(context, &keys, typing) {
+            Lookup::Match(name) => action_from_name(name).into_iter().collect(),
+            Lookup::Prefix => {
+                self.pending = keys;
+                Vec::new()
+            }
+            Lookup::None if keys.len() == 1 => Self::fallback(key).into_iter().collect(),
+            Lookup::None => {
+                // The sequence broke off: the first key stands alone, the rest are replayed
+                let mut actions: Vec<Action> = Self::fallback(keys[0]).into_iter().collect();
+                for key in &keys[1..] {
+                    let event = KeyEvent::new(key.code, key.modifiers);
+                    actions.extend(self.handle_key(context, event, typing));
+                }
+                actions
+            }
+        }
+    }
+
+    fn fallback(key: Key) -> Option<Action> {
+        match key.code {
+            KeyCode::Char(c) if key.is_text() => Some(Action::Character(c)),
+            _ => None,
+        }
+    }
+
+    /// Format `(action, description)` pairs as help bar text using the active bindings.
+    pub fn help_text(&self, context: KeyContext, entries: &[(&str, &str)]) -> String {
+        let effective = self.effective_bindings(context);
+
+        entries
+            .iter()
+            .filter_map(|(action, desc)| {
+                let binding = effective.iter().find(|b| b.action == *action)?;
+                Some(format!("{}:{}", display_sequence(&binding.keys), desc))
+            })
+            .collect::<Vec<_>>()
+            .join("  ")
+    }
+}
+
+impl KeysConfig {
+    fn section(&self, context: KeyContext) -> &BTreeMap<String, String> {
+        match context {
+            KeyContext::Global => &self.global,
+            KeyContext::SessionPicker => &self.session_picker,
+            KeyContext::CommandPalette => &self.command_palette,
+            KeyContext::FilePicker => &self.file_picker,
+            KeyContext::WorktreePicker => &self.worktree_picker,
+            KeyContext::BufferPicker => &self.buffer_picker,
+            KeyContext::ConfigViewer => &self.config_viewer,
+            KeyContext::DiffViewer => &self.diff_viewer,
+            KeyContext::InputDialog => &self.input_dialog,
+            KeyContext::ConfirmDialog => &self.confirm_dialog,
+            KeyContext::SelectDialog => &self.select_dialog,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> Key {
+        Key { code, modifiers }
+    }
+
+    fn keymap(context: &str, bindings: &[(&str, &str)]) -> Result<Keymap> {
+        let mut toml = format!("[{}]\n", context);
+        for (keys, action) in bindings {
+            toml.push_str(&format!("{:?} = {:?}\n", keys, action));
+        }
+        Keymap::new(&toml::from_str(&toml).unwrap())
+    }
+
+    /// Press `keys` one at a time and collect what they trigger.
+    fn press(keymap: &mut Keymap, context: KeyContext, keys: &str, typing: bool) -> String {
+        let mut actions = Vec::new();
+        for key in parse_sequence(keys).unwrap() {
+            let event = KeyEvent::new(key.code, key.modifiers);
+            actions.extend(keymap.handle_key(context, event, typing));
+        }
+        format!("{:?}", actions)
+    }
+
+    #[test]
+    fn parses_chords_and_named_keys() {
+        let ctrl_d = key(KeyCode::Char('d'), KeyModifiers::CONTROL);
+        assert_eq!(Key::parse("ctrl-d"), Ok(ctrl_d));
+        assert_eq!(Key::parse("C-d"), Ok(ctrl_d));
+        assert_eq!(
+            Key::parse("ctrl-alt-x"),
+            Ok(key(KeyCode::Char('x'), KeyModifiers::CONTROL | KeyModifiers::ALT))
+        );
+        assert_eq!(Key::parse("S-a"), Ok(key(KeyCode::Char('A'), KeyModifiers::NONE)));
+        assert_eq!(Key::parse("shift-tab"), Ok(key(KeyCode::BackTab, KeyModifiers::NONE)));
+        assert_eq!(Key::parse("PgUp"), Ok(key(KeyCode::PageUp, KeyModifiers::NONE)));
+        assert_eq!(Key::parse("space"), Ok(key(KeyCode::Char(' '), KeyModifiers::NONE)));
+        assert_eq!(Key::parse("f12"), Ok(key(KeyCode::F(12), KeyModifiers::NONE)));
+        assert_eq!(Key::parse("-"), Ok(key(KeyCode::Char('-'), KeyModifiers::NONE)));
+        assert_eq!(Key::parse("alt--"), Ok(key(KeyCode::Char('-'), KeyModifiers::ALT)));
+
+        assert_eq!(Key::parse("f13"), Err("unknown key 'f13' in 'f13'".to_string()));
+        assert_eq!(
+            Key::parse("hyper-x"),
+            Err("unknown modifier 'hyper' in 'hyper-x'".to_string())
+        );
+        assert!(parse_sequence("  ").is_err());
+    }
+
+    #[test]
+    fn displays_keys_as_the_help_bar_shows_them() {
+        let keys = parse_sequence("ctrl-k alt-enter shift-tab space").unwrap();
+        assert_eq!(display_sequence(&keys), "C-k M-Enter S-Tab Space");
+    }
+
+    #[test]
+    fn sequences_wait_for_their_last_key() {
+        let mut keymap = keymap("session_picker", &[("g g", "up")]).unwrap();
+        let context = KeyContext::SessionPicker;
+
+        assert_eq!(press(&mut keymap, context, "g", false), "[]");
+        assert_eq!(press(&mut keymap, context, "g", false), "[MoveUp]");
+
+        // A broken-off sequence types its first key and replays the rest
+        assert_eq!(
+            press(&mut keymap, context, "g x", false),
+            "[Character('g'), Character('x')]"
+        );
+        assert_eq!(press(&mut keymap, context, "g d", false), "[Character('g'), Delete]");
+    }
+
+    #[test]
+    fn plain_keys_are_typed_while_a_query_is_being_typed() {
+        let mut keymap = keymap("session_picker", &[]).unwrap();
+        let context = KeyContext::SessionPicker;
+
+        assert_eq!(press(&mut keymap, context, "n", false), "[New]");
+        assert_eq!(press(&mut keymap, context, "n", true), "[Character('n')]");
+        assert_eq!(press(&mut keymap, context, "ctrl-k", true), "[MoveUp]");
+        assert_eq!(press(&mut keymap, context, "ctrl-x", false), "[]");
+    }
+
+    #[test]
+    fn configured_bindings_override_and_unbind_defaults() {
+        let mut keymap = keymap("session_picker", &[("n", "none"), ("ctrl-n", "new")]).unwrap();
+        let context = KeyContext::SessionPicker;
+
+        assert_eq!(press(&mut keymap, context, "n", false), "[Character('n')]");
+        assert_eq!(press(&mut keymap, context, "ctrl-n", false), "[New]");
+        // The view's binding wins over the global one for the same key
+        assert_eq!(press(&mut keymap, context, "ctrl-n", true), "[New]");
+        assert_eq!(press(&mut keymap, KeyContext::FilePicker, "ctrl-n", false), "[]");
+    }
+
+    #[test]
+    fn rejects_conflicting_and_unknown_bindings() {
+        let error = |context, bindings| keymap(context, bindings).err().unwrap().to_string();
+
+        assert_eq!(
+            error("session_picker", &[("g", "new"), ("g g", "up")]),
+            "Config error: keys.session_picker: 'g' (new) conflicts with 'g g' (up)"
+        );
+        // A view's sequence may not start with a global key either
+        assert_eq!(
+            error("session_picker", &[("ctrl-k x", "new")]),
+            "Config error: keys.session_picker: 'C-k' (up) conflicts with 'C-k x' (new)"
+        );
+        assert_eq!(
+            error("global", &[("C-d", "quit"), ("ctrl-d", "quit")]),
+            "Config error: keys.global: 'C-d' and 'ctrl-d' are the same key"
+        );
+        assert_eq!(
+            error("global", &[("ctrl-d", "explode")]),
+            "Config error: keys.global: unknown action 'explode' for 'ctrl-d'"
+        );
+        assert_eq!(
+            error("global", &[("hyper-d", "quit")]),
+            "Config error: keys.global: unknown modifier 'hyper' in 'hyper-d'"
+        );
+    }
+}
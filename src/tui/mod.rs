@@ -1,5 +1,7 @@
 pub mod terminal;
 mod event;
+mod keymap;
 
 pub use terminal::Tui;
-pub use event::{Event, EventHandler, key_to_action};
+pub use event::{Event, EventHandler};
+pub use keymap::{KeyContext, Keymap};